@@ -0,0 +1,296 @@
+use crate::config::AgentOptions;
+use ssh_encoding::Encode;
+use ssh_key::PrivateKey;
+use std::env;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::time::Duration;
+
+// draft-miller-ssh-agent のメッセージ番号
+const SSH_AGENT_FAILURE: u8 = 5;
+const SSH_AGENT_SUCCESS: u8 = 6;
+const SSH_AGENTC_REQUEST_IDENTITIES: u8 = 11;
+const SSH_AGENT_IDENTITIES_ANSWER: u8 = 12;
+const SSH_AGENTC_ADD_IDENTITY: u8 = 17;
+const SSH_AGENTC_ADD_ID_CONSTRAINED: u8 = 25;
+const SSH_AGENT_CONSTRAIN_LIFETIME: u8 = 1;
+const SSH_AGENT_CONSTRAIN_CONFIRM: u8 = 2;
+
+// 不正な応答で巨大なメモリを確保しないための上限
+const MAX_MESSAGE_LEN: usize = 256 * 1024;
+
+const AGENT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 鍵を読み込んだ結果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadOutcome {
+    Added,
+    AlreadyLoaded,
+}
+
+/// `SSH_AUTH_SOCK` で動いている ssh-agent との接続
+pub struct AgentClient {
+    stream: UnixStream,
+}
+
+impl AgentClient {
+    pub fn connect() -> io::Result<Self> {
+        let sock = env::var_os("SSH_AUTH_SOCK")
+            .filter(|s| !s.is_empty())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "SSH_AUTH_SOCK is not set"))?;
+        let stream = UnixStream::connect(&sock)?;
+        stream.set_read_timeout(Some(AGENT_TIMEOUT))?;
+        stream.set_write_timeout(Some(AGENT_TIMEOUT))?;
+        Ok(Self { stream })
+    }
+
+    /// メッセージを 1 つ送り、応答の種類と本体を返す
+    fn request(&mut self, kind: u8, body: &[u8]) -> io::Result<(u8, Vec<u8>)> {
+        let mut message = Vec::with_capacity(body.len() + 5);
+        message.extend_from_slice(&(body.len() as u32 + 1).to_be_bytes());
+        message.push(kind);
+        message.extend_from_slice(body);
+        self.stream.write_all(&message)?;
+
+        let mut len = [0u8; 4];
+        self.stream.read_exact(&mut len)?;
+        let len = u32::from_be_bytes(len) as usize;
+        if len == 0 || len > MAX_MESSAGE_LEN {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "invalid agent response length"));
+        }
+        let mut response = vec![0u8; len];
+        self.stream.read_exact(&mut response)?;
+        let kind = response.remove(0);
+        Ok((kind, response))
+    }
+
+    /// エージェントに登録済みの公開鍵（SSH 形式のバイト列）を列挙する
+    pub fn identities(&mut self) -> io::Result<Vec<Vec<u8>>> {
+        let (kind, body) = self.request(SSH_AGENTC_REQUEST_IDENTITIES, &[])?;
+        if kind != SSH_AGENT_IDENTITIES_ANSWER {
+            return Err(io::Error::other(format!("unexpected agent response {}", kind)));
+        }
+
+        let mut reader = body.as_slice();
+        let count = read_u32(&mut reader)?;
+        let mut keys = Vec::new();
+        for _ in 0..count {
+            let blob = read_string(&mut reader)?;
+            let _comment = read_string(&mut reader)?;
+            keys.push(blob.to_vec());
+        }
+        Ok(keys)
+    }
+
+    /// 復号済みの秘密鍵をエージェントに登録する
+    pub fn add_identity(&mut self, key: &PrivateKey, options: &AgentOptions) -> io::Result<()> {
+        // 鍵の種類と各パラメータは OpenSSH の秘密鍵形式と同じ並びで送る
+        let mut body = Vec::new();
+        key.key_data().encode(&mut body).map_err(encoding_error)?;
+        key.comment().encode(&mut body).map_err(encoding_error)?;
+
+        let mut kind = SSH_AGENTC_ADD_IDENTITY;
+        if let Some(lifetime) = options.lifetime {
+            kind = SSH_AGENTC_ADD_ID_CONSTRAINED;
+            body.push(SSH_AGENT_CONSTRAIN_LIFETIME);
+            body.extend_from_slice(&lifetime.to_be_bytes());
+        }
+        if options.confirm {
+            kind = SSH_AGENTC_ADD_ID_CONSTRAINED;
+            body.push(SSH_AGENT_CONSTRAIN_CONFIRM);
+        }
+
+        let result = self.request(kind, &body);
+        // 送信バッファに残った秘密鍵を消しておく
+        body.iter_mut().for_each(|b| *b = 0);
+        match result?.0 {
+            SSH_AGENT_SUCCESS => Ok(()),
+            SSH_AGENT_FAILURE => Err(io::Error::other("agent refused the key")),
+            other => Err(io::Error::other(format!("unexpected agent response {}", other))),
+        }
+    }
+}
+
+fn encoding_error(e: impl std::fmt::Display) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+}
+
+fn read_u32(reader: &mut &[u8]) -> io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_be_bytes(bytes))
+}
+
+fn read_string<'a>(reader: &mut &'a [u8]) -> io::Result<&'a [u8]> {
+    let len = read_u32(reader)? as usize;
+    if len > reader.len() {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "truncated agent response"));
+    }
+    let (value, rest) = reader.split_at(len);
+    *reader = rest;
+    Ok(value)
+}
+
+/// 鍵ファイルをパスフレーズで復号し、まだ登録されていなければエージェントに登録する。
+/// パスフレーズは端末を経由しない
+pub fn load_key(path: &Path, passphrase: &str, options: &AgentOptions) -> io::Result<LoadOutcome> {
+    let pem = fs::read_to_string(path)?;
+    let key = PrivateKey::from_openssh(&pem).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} is not an OpenSSH private key ({})", path.display(), e),
+        )
+    })?;
+
+    let mut agent = AgentClient::connect()?;
+    let public = key.public_key().to_bytes().map_err(encoding_error)?;
+    if agent.identities()?.contains(&public) {
+        return Ok(LoadOutcome::AlreadyLoaded);
+    }
+
+    let key = if key.is_encrypted() {
+        key.decrypt(passphrase).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("wrong passphrase for {}", path.display()),
+            )
+        })?
+    } else {
+        key
+    };
+    agent.add_identity(&key, options)?;
+    Ok(LoadOutcome::Added)
+}
+
+/// テスト用に一時ディレクトリで ssh-agent を起動する
+#[cfg(test)]
+pub(crate) mod test_agent {
+    use std::env;
+    use std::path::{Path, PathBuf};
+    use std::process::{Child, Command, Stdio};
+    use std::sync::{Mutex, MutexGuard};
+    use std::thread;
+    use std::time::{Duration, Instant};
+    use tempfile::TempDir;
+
+    // SSH_AUTH_SOCK はプロセス全体で共有されるので、エージェントを使うテストは順番に実行する
+    static AGENT_LOCK: Mutex<()> = Mutex::new(());
+
+    pub struct TestAgent {
+        child: Child,
+        pub dir: TempDir,
+        _lock: MutexGuard<'static, ()>,
+    }
+
+    impl TestAgent {
+        /// `ssh-agent -D -a <sock>` を起動し、SSH_AUTH_SOCK をそのソケットに向ける
+        pub fn start() -> Self {
+            let lock = AGENT_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+            let dir = tempfile::tempdir().unwrap();
+            let sock = dir.path().join("agent.sock");
+            let child = Command::new("ssh-agent")
+                .arg("-D")
+                .arg("-a")
+                .arg(&sock)
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()
+                .expect("ssh-agent is required for this test");
+
+            let start = Instant::now();
+            while !sock.exists() {
+                assert!(start.elapsed() < Duration::from_secs(5), "ssh-agent did not start");
+                thread::sleep(Duration::from_millis(20));
+            }
+            env::set_var("SSH_AUTH_SOCK", &sock);
+            Self { child, dir, _lock: lock }
+        }
+
+        /// パスフレーズで暗号化した ed25519 鍵を作り、秘密鍵のパスを返す
+        pub fn generate_key(&self, name: &str, passphrase: &str) -> PathBuf {
+            let path = self.dir.path().join(name);
+            let status = Command::new("ssh-keygen")
+                .args(["-q", "-t", "ed25519", "-N", passphrase, "-C", name, "-f"])
+                .arg(&path)
+                .status()
+                .expect("ssh-keygen is required for this test");
+            assert!(status.success());
+            path
+        }
+    }
+
+    impl Drop for TestAgent {
+        fn drop(&mut self) {
+            env::remove_var("SSH_AUTH_SOCK");
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+
+    /// 秘密鍵と同じ場所にある `.pub` の公開鍵（エージェントが返す形式）
+    pub fn public_key_blob(private_key: &Path) -> Vec<u8> {
+        let text = std::fs::read_to_string(private_key.with_extension("pub")).unwrap();
+        ssh_key::PublicKey::from_openssh(&text).unwrap().to_bytes().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::test_agent::{public_key_blob, TestAgent};
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn adds_key_with_lifetime_and_confirm_constraints() {
+        let agent = TestAgent::start();
+        let path = agent.generate_key("constrained", "secret");
+        let options = AgentOptions { lifetime: Some(1), confirm: true };
+
+        assert_eq!(load_key(&path, "secret", &options).unwrap(), LoadOutcome::Added);
+        let mut client = AgentClient::connect().unwrap();
+        assert!(client.identities().unwrap().contains(&public_key_blob(&path)));
+
+        // 寿命が切れるとエージェントから消える
+        thread::sleep(Duration::from_millis(2500));
+        assert!(!client.identities().unwrap().contains(&public_key_blob(&path)));
+    }
+
+    #[test]
+    fn reports_already_loaded_key() {
+        let agent = TestAgent::start();
+        let path = agent.generate_key("plain", "secret");
+        let options = AgentOptions::default();
+
+        assert_eq!(load_key(&path, "secret", &options).unwrap(), LoadOutcome::Added);
+        assert_eq!(load_key(&path, "secret", &options).unwrap(), LoadOutcome::AlreadyLoaded);
+        assert_eq!(AgentClient::connect().unwrap().identities().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn rejects_wrong_passphrase() {
+        let agent = TestAgent::start();
+        let path = agent.generate_key("locked", "secret");
+
+        let error = load_key(&path, "wrong", &AgentOptions::default()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::PermissionDenied);
+        assert!(AgentClient::connect().unwrap().identities().unwrap().is_empty());
+    }
+
+    #[test]
+    fn rejects_oversized_response() {
+        let (stream, mut peer) = UnixStream::pair().unwrap();
+        let server = thread::spawn(move || {
+            let mut request = [0u8; 5];
+            peer.read_exact(&mut request).unwrap();
+            assert_eq!(request, [0, 0, 0, 1, SSH_AGENTC_REQUEST_IDENTITIES]);
+            peer.write_all(&(MAX_MESSAGE_LEN as u32 + 1).to_be_bytes()).unwrap();
+        });
+
+        let mut client = AgentClient { stream };
+        let error = client.identities().unwrap_err();
+        server.join().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,530 @@
+use crate::config::{effective_value, ssh_effective_config, SshConfig};
+use crate::utils::print_table;
+use std::io::{self, BufRead, BufReader, Read};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::process::{Command, Stdio};
+use std::sync::{mpsc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_TIMEOUT_SECS: u64 = 5;
+
+/// 同時に確認するホストの数
+const MAX_WORKERS: usize = 16;
+
+#[derive(Debug, Clone)]
+enum Route {
+    Direct,
+    Jump(String),
+    Command(String),
+}
+
+/// 接続確認の対象（ssh -G で解決した実際の接続先）
+#[derive(Debug, Clone)]
+struct Target {
+    alias: String,
+    hostname: String,
+    port: u16,
+    user: Option<String>,
+    route: Route,
+}
+
+#[derive(Debug)]
+pub struct CheckResult {
+    pub alias: String,
+    pub address: String,
+    pub route: String,
+    pub ok: bool,
+    pub status: String,
+    pub latency: Option<Duration>,
+    pub banner: Option<String>,
+}
+
+fn resolve_target(config: &SshConfig) -> Target {
+    let alias = config.host.clone();
+
+    let Some(values) = ssh_effective_config(&alias) else {
+        // ssh -G が使えない場合は config の値をそのまま使う
+        let route = match &config.proxy_command {
+            Some(cmd) => Route::Command(cmd.clone()),
+            None => Route::Direct,
+        };
+        return Target {
+            hostname: config.hostname.clone().unwrap_or_else(|| alias.clone()),
+            port: config.port.as_deref().and_then(|p| p.parse().ok()).unwrap_or(22),
+            user: config.user.clone(),
+            alias,
+            route,
+        };
+    };
+
+    let route = if let Some(jump) = effective_value(&values, "proxyjump") {
+        Route::Jump(jump.to_string())
+    } else if let Some(cmd) = effective_value(&values, "proxycommand") {
+        Route::Command(cmd.to_string())
+    } else {
+        Route::Direct
+    };
+
+    Target {
+        hostname: effective_value(&values, "hostname").unwrap_or(&alias).to_string(),
+        port: effective_value(&values, "port").and_then(|p| p.parse().ok()).unwrap_or(22),
+        user: effective_value(&values, "user").map(|u| u.to_string()),
+        alias,
+        route,
+    }
+}
+
+/// ProxyJump の 1 ホップ（`[user@]host[:port]`、`[user@][::1]:port`、`ssh://[user@]host[:port]`）から
+/// ホストとポートを取り出す。角括弧のない IPv6 アドレスはポートなしとみなす
+fn parse_jump_hop(hop: &str) -> (&str, Option<u16>) {
+    let hop = hop.trim();
+    let hop = hop.strip_prefix("ssh://").map_or(hop, |rest| rest.trim_end_matches('/'));
+    let hop = hop.rsplit_once('@').map_or(hop, |(_, host)| host);
+    if let Some(rest) = hop.strip_prefix('[') {
+        if let Some((host, after)) = rest.split_once(']') {
+            return (host, after.strip_prefix(':').and_then(|p| p.parse().ok()));
+        }
+    }
+    match hop.split_once(':') {
+        Some((host, port)) if !port.contains(':') => (host, port.parse().ok()),
+        _ => (hop, None),
+    }
+}
+
+/// ProxyJump の最初のホップの接続先を解決する。
+/// 2 番目以降のホップは最初のホップの先にあり、ここからは直接確かめられない
+fn resolve_first_hop(jump: &str) -> (String, u16) {
+    let first = jump.split(',').next().unwrap_or(jump);
+    let (host, port) = parse_jump_hop(first);
+
+    let hop = SshConfig::new(host.to_string());
+    let resolved = resolve_target(&hop);
+    (resolved.hostname, port.unwrap_or(resolved.port))
+}
+
+/// `host:port` の表記（IPv6 アドレスは角括弧で囲む）
+fn host_port(host: &str, port: u16) -> String {
+    if host.contains(':') {
+        format!("[{}]:{}", host, port)
+    } else {
+        format!("{}:{}", host, port)
+    }
+}
+
+/// 踏み台経由の ROUTE 列。確かめるのは最初のホップまでなので、ホップが複数ならそう書く
+fn jump_route(jump: &str, hop_host: &str, hop_port: u16) -> String {
+    let hops = jump.split(',').filter(|h| !h.trim().is_empty()).count();
+    let first = host_port(hop_host, hop_port);
+    if hops > 1 {
+        format!("via {} ({}; later hops not checked)", jump, first)
+    } else {
+        format!("via {} ({})", jump, first)
+    }
+}
+
+/// サーバーの識別文字列（`SSH-2.0-...`）を 1 行読む。読み取りのタイムアウトはエラーで返す
+fn read_banner<R: Read>(reader: R) -> io::Result<Option<String>> {
+    let mut reader = BufReader::new(reader);
+    let mut line = String::new();
+    // RFC 4253 ではバナーの前に任意の行が来ることがある
+    for _ in 0..10 {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        if line.starts_with("SSH-") {
+            return Ok(Some(line.trim_end().to_string()));
+        }
+    }
+    Ok(None)
+}
+
+/// 別スレッドで `f` を実行し、`timeout` までに終わらなければ None を返す（スレッドはそのまま終わらせる）
+fn with_timeout<T: Send + 'static>(timeout: Duration, f: impl FnOnce() -> T + Send + 'static) -> Option<T> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let _ = tx.send(f());
+    });
+    rx.recv_timeout(timeout).ok()
+}
+
+/// 名前解決する。getaddrinfo にはタイムアウトがないので、待つのは `timeout` までにする
+fn resolve_addrs(host: &str, port: u16, timeout: Duration) -> Result<Vec<SocketAddr>, String> {
+    let host = host.to_string();
+    let resolved = with_timeout(timeout, move || {
+        (host.as_str(), port).to_socket_addrs().map(|addrs| addrs.collect::<Vec<_>>())
+    });
+    match resolved {
+        Some(Ok(addrs)) if addrs.is_empty() => Err("DNS FAIL (no address)".to_string()),
+        Some(Ok(addrs)) => Ok(addrs),
+        Some(Err(e)) => Err(format!("DNS FAIL ({})", e)),
+        None => Err("DNS TIMEOUT".to_string()),
+    }
+}
+
+fn check_tcp(alias: &str, address: String, route: String, host: &str, port: u16, banner: bool, timeout: Duration) -> CheckResult {
+    let mut result = CheckResult {
+        alias: alias.to_string(),
+        address,
+        route,
+        ok: false,
+        status: String::new(),
+        latency: None,
+        banner: None,
+    };
+
+    let addrs = match resolve_addrs(host, port, timeout) {
+        Ok(addrs) => addrs,
+        Err(status) => {
+            result.status = status;
+            return result;
+        }
+    };
+
+    let mut last_error = None;
+    for addr in addrs {
+        let start = Instant::now();
+        match TcpStream::connect_timeout(&addr, timeout) {
+            Ok(stream) => {
+                result.latency = Some(start.elapsed());
+                result.ok = true;
+                result.status = "OK".to_string();
+                if banner {
+                    let _ = stream.set_read_timeout(Some(timeout));
+                    match read_banner(&stream) {
+                        Ok(Some(banner)) => result.banner = Some(banner),
+                        Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {
+                            result.ok = false;
+                            result.status = "TIMEOUT".to_string();
+                        }
+                        _ => {
+                            result.ok = false;
+                            result.status = "NO BANNER".to_string();
+                        }
+                    }
+                }
+                return result;
+            }
+            Err(e) => last_error = Some(e),
+        }
+    }
+
+    result.status = match last_error {
+        Some(e) if e.kind() == io::ErrorKind::TimedOut => "TIMEOUT".to_string(),
+        Some(e) => format!("TCP FAIL ({})", e),
+        None => "TCP FAIL".to_string(),
+    };
+    result
+}
+
+/// ProxyCommand を実行し、標準出力に SSH バナーが返ってくるかで疎通を確認する
+fn check_proxy_command(target: &Target, command: &str, timeout: Duration) -> CheckResult {
+    let expanded = command
+        .replace("%%", "\u{0}")
+        .replace("%h", &target.hostname)
+        .replace("%p", &target.port.to_string())
+        .replace("%r", target.user.as_deref().unwrap_or(""))
+        .replace("%n", &target.alias)
+        .replace('\u{0}', "%");
+
+    let mut result = CheckResult {
+        alias: target.alias.clone(),
+        address: host_port(&target.hostname, target.port),
+        route: "ProxyCommand".to_string(),
+        ok: false,
+        status: String::new(),
+        latency: None,
+        banner: None,
+    };
+
+    let start = Instant::now();
+    let mut child = match Command::new("sh")
+        .arg("-c")
+        .arg(&expanded)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(e) => {
+            result.status = format!("PROXY FAIL ({})", e);
+            return result;
+        }
+    };
+
+    let stdout = child.stdout.take().unwrap();
+    match with_timeout(timeout, move || read_banner(stdout).ok().flatten()) {
+        Some(Some(banner)) => {
+            result.latency = Some(start.elapsed());
+            result.ok = true;
+            result.status = "OK".to_string();
+            result.banner = Some(banner);
+        }
+        Some(None) => result.status = "PROXY FAIL".to_string(),
+        None => result.status = "TIMEOUT".to_string(),
+    }
+
+    let _ = child.kill();
+    let _ = child.wait();
+    result
+}
+
+fn check_target(target: &Target, banner: bool, timeout: Duration) -> CheckResult {
+    match &target.route {
+        Route::Direct => check_tcp(
+            &target.alias,
+            host_port(&target.hostname, target.port),
+            "direct".to_string(),
+            &target.hostname,
+            target.port,
+            banner,
+            timeout,
+        ),
+        Route::Jump(jump) => {
+            // 踏み台経由の場合は最初のホップまでの疎通を確認する
+            let (hop_host, hop_port) = resolve_first_hop(jump);
+            check_tcp(
+                &target.alias,
+                host_port(&target.hostname, target.port),
+                jump_route(jump, &hop_host, hop_port),
+                &hop_host,
+                hop_port,
+                banner,
+                timeout,
+            )
+        }
+        Route::Command(command) => check_proxy_command(target, command, timeout),
+    }
+}
+
+/// `items` の各要素に `f` を最大 MAX_WORKERS 個のスレッドで並列に適用し、元の順に結果を返す
+fn run_pool<T: Sync, R: Send>(items: &[T], f: impl Fn(&T) -> R + Sync) -> Vec<R> {
+    let jobs = Mutex::new(items.iter().enumerate());
+    let (tx, rx) = mpsc::channel();
+    thread::scope(|scope| {
+        for _ in 0..MAX_WORKERS.min(items.len()) {
+            let tx = tx.clone();
+            let (jobs, f) = (&jobs, &f);
+            scope.spawn(move || loop {
+                let Some((index, item)) = jobs.lock().unwrap().next() else {
+                    break;
+                };
+                let _ = tx.send((index, f(item)));
+            });
+        }
+    });
+    drop(tx);
+
+    // 終わった順に届くので、元の並びに戻す
+    let mut results: Vec<(usize, R)> = rx.into_iter().collect();
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+/// 複数ホストの疎通確認を並列に実行する。
+/// ホストが多くてもスレッドと ProxyCommand の子プロセスが増えすぎないよう、同時に確認するのは MAX_WORKERS 件まで
+pub fn check_hosts(configs: &[SshConfig], banner: bool, timeout: Duration) -> Vec<CheckResult> {
+    run_pool(configs, |config| {
+        let target = resolve_target(config);
+        check_target(&target, banner, timeout)
+    })
+}
+
+pub fn print_results(results: &[CheckResult]) {
+    let headers = ["HOST", "ADDRESS", "ROUTE", "STATUS", "LATENCY", "BANNER"];
+    let rows: Vec<Vec<String>> = results
+        .iter()
+        .map(|r| {
+            vec![
+                r.alias.clone(),
+                r.address.clone(),
+                r.route.clone(),
+                r.status.clone(),
+                r.latency
+                    .map(|d| format!("{:.1}ms", d.as_secs_f64() * 1000.0))
+                    .unwrap_or_else(|| "-".to_string()),
+                r.banner.clone().unwrap_or_else(|| "-".to_string()),
+            ]
+        })
+        .collect();
+
+    print_table(&headers, &rows);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::net::TcpListener;
+
+    const TIMEOUT: Duration = Duration::from_millis(500);
+
+    fn check_local(port: u16, banner: bool) -> CheckResult {
+        let address = format!("127.0.0.1:{}", port);
+        check_tcp("test", address, "direct".to_string(), "127.0.0.1", port, banner, TIMEOUT)
+    }
+
+    #[test]
+    fn reports_ok_with_latency_and_banner() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.write_all(b"SSH-2.0-OpenSSH_9.6\r\n").unwrap();
+        });
+
+        let result = check_local(port, true);
+        server.join().unwrap();
+        assert!(result.ok, "{}", result.status);
+        assert_eq!(result.status, "OK");
+        assert!(result.latency.is_some());
+        assert!(result.banner.as_deref().unwrap().starts_with("SSH-2.0-"));
+    }
+
+    #[test]
+    fn skips_lines_before_banner() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            stream.write_all(b"welcome\r\nSSH-2.0-dropbear\r\n").unwrap();
+        });
+
+        let result = check_local(port, true);
+        server.join().unwrap();
+        assert_eq!(result.banner.as_deref(), Some("SSH-2.0-dropbear"));
+    }
+
+    #[test]
+    fn reports_closed_port() {
+        // 一度 bind して空いているポート番号を得てから閉じる
+        let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+
+        let result = check_local(port, false);
+        assert!(!result.ok);
+        assert!(result.status.starts_with("TCP FAIL"), "{}", result.status);
+        assert!(result.latency.is_none());
+    }
+
+    #[test]
+    fn reports_timeout_when_no_banner_is_sent() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (done_tx, done_rx) = mpsc::channel::<()>();
+        let server = thread::spawn(move || {
+            let (_stream, _) = listener.accept().unwrap();
+            // 何も送らずに接続を保持する
+            let _ = done_rx.recv();
+        });
+
+        let result = check_local(port, true);
+        drop(done_tx);
+        server.join().unwrap();
+        assert!(!result.ok);
+        assert_eq!(result.status, "TIMEOUT");
+        assert!(result.banner.is_none());
+    }
+
+    #[test]
+    fn connects_without_reading_banner() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let result = check_local(port, false);
+        drop(listener);
+        assert!(result.ok);
+        assert!(result.banner.is_none());
+    }
+
+    #[test]
+    fn pool_limits_concurrency_and_keeps_order() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let running = AtomicUsize::new(0);
+        let peak = AtomicUsize::new(0);
+        let items: Vec<usize> = (0..MAX_WORKERS * 4).collect();
+        let results = run_pool(&items, |&i| {
+            let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+            peak.fetch_max(now, Ordering::SeqCst);
+            thread::sleep(Duration::from_millis(10));
+            running.fetch_sub(1, Ordering::SeqCst);
+            i * 2
+        });
+
+        assert_eq!(results, items.iter().map(|i| i * 2).collect::<Vec<_>>());
+        assert!(peak.load(Ordering::SeqCst) <= MAX_WORKERS);
+        assert!(run_pool(&[] as &[usize], |&i| i).is_empty());
+    }
+
+    #[test]
+    fn gives_up_on_slow_work() {
+        assert_eq!(with_timeout(TIMEOUT, || 42), Some(42));
+        let slow = with_timeout(Duration::from_millis(50), || thread::sleep(Duration::from_secs(2)));
+        assert_eq!(slow, None);
+    }
+
+    #[test]
+    fn resolves_local_addresses() {
+        let addrs = resolve_addrs("127.0.0.1", 22, TIMEOUT).unwrap();
+        assert_eq!(addrs, [SocketAddr::from(([127, 0, 0, 1], 22))]);
+    }
+
+    #[test]
+    fn reports_unresolvable_host() {
+        let result = check_tcp("test", String::new(), String::new(), "no-such-host.invalid", 22, false, TIMEOUT);
+        assert!(!result.ok);
+        assert!(result.status.starts_with("DNS "), "{}", result.status);
+    }
+
+    #[test]
+    fn resolves_first_hop_with_user_and_port() {
+        assert_eq!(resolve_first_hop("admin@127.0.0.1:2222"), ("127.0.0.1".to_string(), 2222));
+        assert_eq!(
+            resolve_first_hop("admin@127.0.0.1:2200,other@10.0.0.1"),
+            ("127.0.0.1".to_string(), 2200)
+        );
+    }
+
+    #[test]
+    fn parses_jump_hop_forms() {
+        let cases = [
+            ("bastion", ("bastion", None)),
+            ("admin@bastion:2222", ("bastion", Some(2222))),
+            (" bastion.example.com ", ("bastion.example.com", None)),
+            ("[::1]:2222", ("::1", Some(2222))),
+            ("admin@[2001:db8::1]", ("2001:db8::1", None)),
+            ("2001:db8::1", ("2001:db8::1", None)),
+            ("ssh://admin@bastion:2022", ("bastion", Some(2022))),
+            ("ssh://bastion/", ("bastion", None)),
+            ("ssh://admin@[::1]:2200", ("::1", Some(2200))),
+            ("a@b@bastion:22", ("bastion", Some(22))),
+        ];
+        for (hop, expected) in cases {
+            assert_eq!(parse_jump_hop(hop), expected, "{}", hop);
+        }
+    }
+
+    #[test]
+    fn resolves_bracketed_and_url_hops() {
+        assert_eq!(resolve_first_hop("[::1]:2222,next"), ("::1".to_string(), 2222));
+        assert_eq!(resolve_first_hop("ssh://admin@127.0.0.1:2022"), ("127.0.0.1".to_string(), 2022));
+    }
+
+    #[test]
+    fn notes_unchecked_later_hops() {
+        assert_eq!(jump_route("bastion", "10.0.0.1", 22), "via bastion (10.0.0.1:22)");
+        assert_eq!(
+            jump_route("[::1]:2222,inner", "::1", 2222),
+            "via [::1]:2222,inner ([::1]:2222; later hops not checked)"
+        );
+    }
+
+    #[test]
+    fn resolves_first_hop_without_port() {
+        let (host, port) = resolve_first_hop("127.0.0.1");
+        assert_eq!(host, "127.0.0.1");
+        assert_eq!(port, 22);
+    }
+}
@@ -1,7 +1,398 @@
 use regex::Regex;
+use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::io::{self, Write};
 use std::path::PathBuf;
+use std::process::Command;
+
+use crate::template::is_valid_name;
+use crate::utils::home_dir;
+
+/// プロンプトに対する応答の種類
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuleResponse {
+    /// 空行（Enter キー）を送る
+    Enter,
+    /// 文字列をそのまま送る
+    Send(String),
+    /// ホストの #pass を送る（エイリアス指定時はそのホストのもの）
+    Secret(Option<String>),
+    /// ホストの #totp から計算したコードを送る
+    Totp,
+    /// 利用者に入力してもらう
+    Ask,
+}
+
+impl RuleResponse {
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        let (kind, arg) = match value.split_once(char::is_whitespace) {
+            Some((kind, arg)) => (kind, Some(arg.trim())),
+            None => (value, None),
+        };
+        match (kind.to_lowercase().as_str(), arg) {
+            ("enter", None) => Some(Self::Enter),
+            ("send", Some(text)) => Some(Self::Send(text.to_string())),
+            ("secret", alias) => Some(Self::Secret(alias.map(|a| a.to_string()))),
+            ("totp", None) => Some(Self::Totp),
+            ("ask", None) => Some(Self::Ask),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for RuleResponse {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Enter => write!(f, "enter"),
+            Self::Send(text) => write!(f, "send {}", text),
+            Self::Secret(None) => write!(f, "secret"),
+            Self::Secret(Some(alias)) => write!(f, "secret {}", alias),
+            Self::Totp => write!(f, "totp"),
+            Self::Ask => write!(f, "ask"),
+        }
+    }
+}
+
+/// `#rule <regex> => <response>` で保存する、ホストごとのプロンプト応答ルール。
+/// `#rule-after-login` はログイン後のシェル上のプロンプト（sudo など）に使う
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PromptRuleSpec {
+    pub after_login: bool,
+    pub pattern: String,
+    pub response: RuleResponse,
+}
+
+impl PromptRuleSpec {
+    /// `#rule` / `#rule-after-login` に続く `<regex> => <response>` を解釈する
+    pub fn parse(after_login: bool, value: &str) -> Option<Self> {
+        let (pattern, response) = value.rsplit_once(" => ")?;
+        Some(Self {
+            after_login,
+            pattern: pattern.trim().to_string(),
+            response: RuleResponse::parse(response)?,
+        })
+    }
+
+    pub fn to_config_line(&self) -> String {
+        let keyword = if self.after_login { "#rule-after-login" } else { "#rule" };
+        format!("{} {} => {}", keyword, self.pattern, self.response)
+    }
+}
+
+/// `#agent lifetime=<秒> confirm` で指定する、ssh-agent に鍵を登録するときの制約
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AgentOptions {
+    /// 鍵を自動的に削除するまでの秒数
+    pub lifetime: Option<u32>,
+    /// 鍵を使うたびに確認を求める
+    pub confirm: bool,
+}
+
+impl AgentOptions {
+    /// `lifetime=<期間>` と `confirm` を空白区切りで解釈する。期間には s/m/h/d の単位を付けられる
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut options = Self::default();
+        for item in value.split_whitespace() {
+            if item.eq_ignore_ascii_case("confirm") {
+                options.confirm = true;
+            } else if let Some(lifetime) = item.strip_prefix("lifetime=") {
+                options.lifetime = Some(parse_duration_secs(lifetime)?);
+            } else {
+                return None;
+            }
+        }
+        Some(options)
+    }
+
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl std::fmt::Display for AgentOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let mut items = Vec::new();
+        if let Some(lifetime) = self.lifetime {
+            items.push(format!("lifetime={}", lifetime));
+        }
+        if self.confirm {
+            items.push("confirm".to_string());
+        }
+        write!(f, "{}", items.join(" "))
+    }
+}
+
+/// `90`、`30m`、`8h` のような期間を秒数に変換する
+pub fn parse_duration_secs(value: &str) -> Option<u32> {
+    let value = value.trim();
+    let (number, unit) = match value.char_indices().last()? {
+        (i, c) if c.is_ascii_alphabetic() => (&value[..i], c.to_ascii_lowercase()),
+        _ => (value, 's'),
+    };
+    let multiplier = match unit {
+        's' => 1,
+        'm' => 60,
+        'h' => 60 * 60,
+        'd' => 24 * 60 * 60,
+        _ => return None,
+    };
+    number.parse::<u32>().ok()?.checked_mul(multiplier).filter(|secs| *secs > 0)
+}
+
+/// `#env <ラベル> [bg=<色>]` で指定する、接続先の環境（prod など）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvironmentLabel {
+    pub name: String,
+    /// セッション中に設定する端末の背景色（`#3a0000` など）
+    pub background: Option<String>,
+}
+
+impl EnvironmentLabel {
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut items = value.split_whitespace();
+        let name = items.next()?.to_string();
+        let mut background = None;
+        for item in items {
+            background = Some(item.strip_prefix("bg=")?.to_string());
+        }
+        Some(Self { name, background })
+    }
+
+    /// 接続前に確認を求める本番環境か
+    pub fn is_production(&self) -> bool {
+        matches!(self.name.to_lowercase().as_str(), "prod" | "production" | "prd")
+    }
+}
+
+impl std::fmt::Display for EnvironmentLabel {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.name)?;
+        if let Some(background) = &self.background {
+            write!(f, " bg={}", background)?;
+        }
+        Ok(())
+    }
+}
+
+/// `#tunnel <名前> L 5432:db.internal:5432 [R ...] [D 1080]` で保存する、名前付きのポート転送
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TunnelProfile {
+    pub name: String,
+    /// 転送の種類と、ssh の `-L` / `-R` / `-D` にそのまま渡す指定
+    pub forwards: Vec<(ForwardKind, String)>,
+}
+
+impl TunnelProfile {
+    /// `#tunnel` に続く `<名前> <L|R|D> <指定> ...` を解釈する（名前の後ろの `:` は省略できる）。
+    /// 名前は状態ファイルと制御ソケットのファイル名になるので、テンプレート名と同じ文字だけを許す
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut items = value.split_whitespace();
+        let name = items.next()?.trim_end_matches(':').to_string();
+        if !is_valid_name(&name) {
+            return None;
+        }
+        let mut forwards = Vec::new();
+        while let Some(kind) = items.next() {
+            let kind = ForwardKind::parse(kind)?;
+            forwards.push((kind, items.next()?.to_string()));
+        }
+        if forwards.is_empty() {
+            return None;
+        }
+        Some(Self { name, forwards })
+    }
+
+    pub fn to_config_line(&self) -> String {
+        format!("#tunnel {} {}", self.name, self.forwards_string())
+    }
+
+    /// `L 5432:db.internal:5432 D 1080` の形式
+    pub fn forwards_string(&self) -> String {
+        self.forwards
+            .iter()
+            .map(|(kind, spec)| format!("{} {}", kind.letter(), spec))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// ssh に渡す `-L <指定>` などの引数
+    pub fn ssh_args(&self) -> Vec<String> {
+        self.forwards
+            .iter()
+            .flat_map(|(kind, spec)| [format!("-{}", kind.letter()), spec.clone()])
+            .collect()
+    }
+
+    /// 待ち受けるポート。ローカル側は `5432`、リモート側は `remote:9000` のように返す
+    pub fn listen_ports(&self) -> Vec<String> {
+        self.forwards
+            .iter()
+            .filter_map(|(kind, spec)| {
+                let fields = split_forward_spec(spec);
+                // `[bind_address:]port[:host:hostport]`
+                let port = match (kind, fields.len()) {
+                    (ForwardKind::Dynamic, n) => fields[n - 1],
+                    (_, 3) => fields[0],
+                    (_, 4) => fields[1],
+                    _ => return None,
+                };
+                Some(match kind {
+                    ForwardKind::Remote => format!("remote:{}", port),
+                    _ => port.to_string(),
+                })
+            })
+            .collect()
+    }
+}
+
+/// 転送の指定を `:` で分ける。`[::1]` のように角括弧で囲んだ IPv6 アドレスの中では分けない
+fn split_forward_spec(spec: &str) -> Vec<&str> {
+    let mut fields = Vec::new();
+    let mut start = 0;
+    let mut in_brackets = false;
+    for (i, c) in spec.char_indices() {
+        match c {
+            '[' => in_brackets = true,
+            ']' => in_brackets = false,
+            ':' if !in_brackets => {
+                fields.push(&spec[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    fields.push(&spec[start..]);
+    fields
+}
+
+/// よく使うディレクティブの正式な表記と、複数回書けるか（書くと値が累積するか）
+const COMMON_DIRECTIVES: &[(&str, bool)] = &[
+    ("LocalForward", true),
+    ("RemoteForward", true),
+    ("DynamicForward", true),
+    ("ForwardAgent", false),
+    ("ForwardX11", false),
+    ("ServerAliveInterval", false),
+    ("ServerAliveCountMax", false),
+    ("IdentitiesOnly", false),
+    ("StrictHostKeyChecking", false),
+    ("UserKnownHostsFile", false),
+    ("ControlMaster", false),
+    ("ControlPath", false),
+    ("ControlPersist", false),
+    ("SetEnv", true),
+    ("SendEnv", true),
+    ("RequestTTY", false),
+    ("RemoteCommand", false),
+    ("CertificateFile", true),
+    ("AddKeysToAgent", false),
+    ("Compression", false),
+    ("ConnectTimeout", false),
+    ("LogLevel", false),
+];
+
+/// キーワードを正式な表記にそろえる（知らないキーワードはそのまま）
+pub fn canonical_keyword(keyword: &str) -> String {
+    COMMON_DIRECTIVES
+        .iter()
+        .find(|(known, _)| known.eq_ignore_ascii_case(keyword))
+        .map_or_else(|| keyword.to_string(), |(known, _)| known.to_string())
+}
+
+/// 複数回指定すると値が累積するキーワードか（それ以外は最初の値だけが使われる）
+pub fn is_repeatable(keyword: &str) -> bool {
+    ["IdentityFile", "Include"].iter().any(|k| k.eq_ignore_ascii_case(keyword))
+        || COMMON_DIRECTIVES
+            .iter()
+            .any(|(known, repeatable)| *repeatable && known.eq_ignore_ascii_case(keyword))
+}
+
+/// `Keyword value` / `Keyword=value` 形式の行をキーワードと値に分割する
+pub fn split_directive(line: &str) -> Option<(String, String)> {
+    let trimmed = line.trim();
+    if trimmed.is_empty() || trimmed.starts_with('#') {
+        return None;
+    }
+    let end = trimmed
+        .find(|c: char| c.is_whitespace() || c == '=')
+        .unwrap_or(trimmed.len());
+    let keyword = trimmed[..end].to_string();
+    let rest = trimmed[end..].trim_start();
+    let value = rest.strip_prefix('=').unwrap_or(rest).trim().to_string();
+    Some((keyword, value))
+}
+
+/// SshConfig が専用のフィールドで扱うキーワードか（Host 行もここで扱う）
+pub fn is_typed_keyword(keyword: &str) -> bool {
+    ["Host", "HostName", "User", "Port", "IdentityFile", "ProxyCommand", "ProxyJump"]
+        .iter()
+        .any(|k| k.eq_ignore_ascii_case(keyword))
+}
+
+/// HostName などの専用フィールド以外のディレクティブ（1 行分）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Directive {
+    pub keyword: String,
+    pub value: String,
+}
+
+impl Directive {
+    pub fn new(keyword: &str, value: &str) -> Self {
+        Self {
+            keyword: canonical_keyword(keyword),
+            value: value.trim().to_string(),
+        }
+    }
+
+    pub fn is(&self, keyword: &str) -> bool {
+        self.keyword.eq_ignore_ascii_case(keyword)
+    }
+}
+
+/// ポート転送の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ForwardKind {
+    Local,
+    Remote,
+    Dynamic,
+}
+
+impl ForwardKind {
+    /// `L` / `R` / `D`（ssh のオプション名と同じ）
+    pub fn parse(letter: &str) -> Option<Self> {
+        match letter.to_uppercase().as_str() {
+            "L" => Some(ForwardKind::Local),
+            "R" => Some(ForwardKind::Remote),
+            "D" => Some(ForwardKind::Dynamic),
+            _ => None,
+        }
+    }
+
+    pub fn letter(self) -> char {
+        match self {
+            ForwardKind::Local => 'L',
+            ForwardKind::Remote => 'R',
+            ForwardKind::Dynamic => 'D',
+        }
+    }
+
+    pub fn keyword(self) -> &'static str {
+        match self {
+            ForwardKind::Local => "LocalForward",
+            ForwardKind::Remote => "RemoteForward",
+            ForwardKind::Dynamic => "DynamicForward",
+        }
+    }
+}
+
+fn parse_yes_no(value: &str) -> Option<bool> {
+    match value.to_lowercase().as_str() {
+        "yes" | "true" => Some(true),
+        "no" | "false" => Some(false),
+        _ => None,
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct SshConfig {
@@ -9,9 +400,23 @@ pub struct SshConfig {
     pub hostname: Option<String>,
     pub user: Option<String>,
     pub port: Option<String>,
-    pub identity_file: Option<String>,
+    /// IdentityFile は複数書ける。ssh は書かれている順に試す
+    pub identity_files: Vec<String>,
     pub password: Option<String>,
+    pub passphrase: Option<String>,
+    pub agent_options: AgentOptions,
+    pub totp_secret: Option<String>,
+    pub totp_prompt: Option<String>,
     pub proxy_command: Option<String>,
+    pub proxy_jump: Option<String>,
+    pub tags: Vec<String>,
+    pub environment: Option<EnvironmentLabel>,
+    pub auto_sudo: bool,
+    pub record: bool,
+    pub prompt_rules: Vec<PromptRuleSpec>,
+    pub tunnels: Vec<TunnelProfile>,
+    /// 上記以外のディレクティブ（ファイルに書かれている順）
+    pub directives: Vec<Directive>,
 }
 
 impl SshConfig {
@@ -21,51 +426,412 @@ impl SshConfig {
             hostname: None,
             user: None,
             port: None,
-            identity_file: None,
+            identity_files: Vec::new(),
             password: None,
+            passphrase: None,
+            agent_options: AgentOptions::default(),
+            totp_secret: None,
+            totp_prompt: None,
             proxy_command: None,
+            proxy_jump: None,
+            tags: Vec::new(),
+            environment: None,
+            auto_sudo: false,
+            record: false,
+            prompt_rules: Vec::new(),
+            tunnels: Vec::new(),
+            directives: Vec::new(),
         }
     }
 
     pub fn to_config_string(&self) -> String {
         let mut config = format!("Host {}\n", self.host);
-        
-        if let Some(hostname) = &self.hostname {
-            config.push_str(&format!("  HostName {}\n", hostname));
+        for field in BLOCK_FIELDS {
+            for line in self.field_lines(field) {
+                config.push_str(&line);
+                config.push('\n');
+            }
         }
-        if let Some(user) = &self.user {
-            config.push_str(&format!("  User {}\n", user));
+        config
+    }
+
+    /// #pass / #passphrase / #totp の値を伏せた to_config_string（画面に表示する用）
+    pub fn to_config_string_redacted(&self) -> String {
+        let mask = |value: &Option<String>| value.as_ref().map(|_| "********".to_string());
+        let mut redacted = self.clone();
+        redacted.password = mask(&self.password);
+        redacted.passphrase = mask(&self.passphrase);
+        redacted.totp_secret = mask(&self.totp_secret);
+        redacted.to_config_string()
+    }
+
+    /// ブロックの 1 項目分の行（値がなければ空）。複数行になる項目はまとめて返す
+    fn field_lines(&self, field: &str) -> Vec<String> {
+        let line = |keyword: &str, value: &dyn std::fmt::Display| format!("  {} {}", keyword, value);
+        let single = |keyword: &str, value: &Option<String>| value.iter().map(|v| line(keyword, v)).collect();
+        match field {
+            "hostname" => single("HostName", &self.hostname),
+            "user" => single("User", &self.user),
+            "port" => single("Port", &self.port),
+            "identityfile" => self.identity_files.iter().map(|v| line("IdentityFile", v)).collect(),
+            "proxycommand" => single("ProxyCommand", &self.proxy_command),
+            "proxyjump" => single("ProxyJump", &self.proxy_jump),
+            "directives" => self.directives.iter().map(|d| line(&d.keyword, &d.value)).collect(),
+            "#pass" => single("#pass", &self.password),
+            "#passphrase" => single("#passphrase", &self.passphrase),
+            "#agent" if !self.agent_options.is_default() => vec![line("#agent", &self.agent_options)],
+            "#totp" => single("#totp", &self.totp_secret),
+            "#totp-prompt" => single("#totp-prompt", &self.totp_prompt),
+            "#tags" if !self.tags.is_empty() => vec![line("#tags", &self.tags.join(","))],
+            "#env" => self.environment.iter().map(|e| line("#env", e)).collect(),
+            "#sudo" if self.auto_sudo => vec!["  #sudo".to_string()],
+            "#record" if self.record => vec!["  #record".to_string()],
+            "#rule" => self.prompt_rules.iter().map(|r| format!("  {}", r.to_config_line())).collect(),
+            "#tunnel" => self.tunnels.iter().map(|t| format!("  {}", t.to_config_line())).collect(),
+            _ => Vec::new(),
         }
-        if let Some(port) = &self.port {
-            config.push_str(&format!("  Port {}\n", port));
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+
+    /// 最初に試される鍵（#passphrase はこの鍵のパスフレーズ）
+    pub fn identity_file(&self) -> Option<&str> {
+        self.identity_files.first().map(String::as_str)
+    }
+
+    /// 最初に試される鍵を置き換える（ほかの鍵はそのまま残す）
+    pub fn set_identity_file(&mut self, identity_file: String) {
+        match self.identity_files.first_mut() {
+            Some(first) => *first = identity_file,
+            None => self.identity_files.push(identity_file),
         }
-        if let Some(identity_file) = &self.identity_file {
-            config.push_str(&format!("  IdentityFile {}\n", identity_file));
+    }
+
+    /// ディレクティブの値（複数ある場合は ssh と同じく最初のもの）
+    pub fn directive(&self, keyword: &str) -> Option<&str> {
+        self.directives.iter().find(|d| d.is(keyword)).map(|d| d.value.as_str())
+    }
+
+    /// 同じキーワードのディレクティブの値をすべて返す
+    pub fn directive_values(&self, keyword: &str) -> Vec<&str> {
+        self.directives
+            .iter()
+            .filter(|d| d.is(keyword))
+            .map(|d| d.value.as_str())
+            .collect()
+    }
+
+    /// ディレクティブを追加する。複数回書けないキーワードは既存の値を置き換える
+    pub fn add_directive(&mut self, keyword: &str, value: &str) {
+        if is_repeatable(keyword) {
+            self.directives.push(Directive::new(keyword, value));
+        } else {
+            self.set_directive(keyword, Some(value));
         }
-        if let Some(proxy_command) = &self.proxy_command {
-            config.push_str(&format!("  ProxyCommand {}\n", proxy_command));
+    }
+
+    /// 複数回書けないディレクティブの値を設定する（None なら削除する）
+    pub fn set_directive(&mut self, keyword: &str, value: Option<&str>) {
+        let position = self.directives.iter().position(|d| d.is(keyword));
+        self.directives.retain(|d| !d.is(keyword));
+        if let Some(value) = value {
+            let directive = Directive::new(keyword, value);
+            match position {
+                Some(position) => self.directives.insert(position, directive),
+                None => self.directives.push(directive),
+            }
         }
-        if let Some(password) = &self.password {
-            config.push_str(&format!("  #pass {}\n", password));
+    }
+
+    fn directive_flag(&self, keyword: &str) -> Option<bool> {
+        self.directive(keyword).and_then(parse_yes_no)
+    }
+
+    /// LocalForward / RemoteForward / DynamicForward を書かれている順に返す
+    pub fn forwards(&self) -> Vec<(ForwardKind, &str)> {
+        self.directives
+            .iter()
+            .filter_map(|d| {
+                let kind = [ForwardKind::Local, ForwardKind::Remote, ForwardKind::Dynamic]
+                    .into_iter()
+                    .find(|k| d.is(k.keyword()))?;
+                Some((kind, d.value.as_str()))
+            })
+            .collect()
+    }
+
+    pub fn forward_agent(&self) -> Option<bool> {
+        self.directive_flag("ForwardAgent")
+    }
+
+    pub fn identities_only(&self) -> Option<bool> {
+        self.directive_flag("IdentitiesOnly")
+    }
+
+    pub fn server_alive_interval(&self) -> Option<u32> {
+        self.directive("ServerAliveInterval").and_then(|v| v.parse().ok())
+    }
+
+    pub fn strict_host_key_checking(&self) -> Option<&str> {
+        self.directive("StrictHostKeyChecking")
+    }
+
+    pub fn control_master(&self) -> Option<&str> {
+        self.directive("ControlMaster")
+    }
+
+    pub fn control_path(&self) -> Option<&str> {
+        self.directive("ControlPath")
+    }
+
+    pub fn control_persist(&self) -> Option<&str> {
+        self.directive("ControlPersist")
+    }
+
+    /// SetEnv で渡す環境変数（`NAME=value` の組）
+    pub fn set_env(&self) -> Vec<(&str, &str)> {
+        // SetEnv は 1 行に複数の組を書ける
+        self.directive_values("SetEnv")
+            .into_iter()
+            .flat_map(str::split_whitespace)
+            .filter_map(|item| item.split_once('='))
+            .collect()
+    }
+
+    pub fn request_tty(&self) -> Option<&str> {
+        self.directive("RequestTTY")
+    }
+
+    /// config から読んだ 1 行を反映する（`HostName=example.com` のような形式も専用のフィールドに入れる）
+    fn set_parsed_directive(&mut self, keyword: &str, value: String) {
+        let field = match keyword.to_lowercase().as_str() {
+            "hostname" => &mut self.hostname,
+            "user" => &mut self.user,
+            "port" => &mut self.port,
+            "identityfile" => {
+                self.identity_files.push(value);
+                return;
+            }
+            "proxycommand" => &mut self.proxy_command,
+            "proxyjump" => &mut self.proxy_jump,
+            // インデントされた Host 行は前の処理でブロックの区切りとして扱っていない
+            "host" => return,
+            _ => {
+                self.directives.push(Directive::new(keyword, &value));
+                return;
+            }
+        };
+        *field = Some(value);
+    }
+
+    /// ProxyJump に指定された踏み台のホスト名（`user@` と `:port` を除いたもの）
+    pub fn jump_hosts(&self) -> Vec<String> {
+        self.proxy_jump
+            .as_deref()
+            .filter(|j| !j.eq_ignore_ascii_case("none"))
+            .map(|j| j.split(',').map(jump_host_name).collect())
+            .unwrap_or_default()
+    }
+}
+
+/// `[user@]host[:port]` 形式のホップからホスト名部分を取り出す
+pub fn jump_host_name(hop: &str) -> String {
+    let hop = hop.trim();
+    let hop = hop.rsplit_once('@').map(|(_, h)| h).unwrap_or(hop);
+    let hop = match hop.rsplit_once(':') {
+        Some((h, p)) if p.chars().all(|c| c.is_ascii_digit()) => h,
+        _ => hop,
+    };
+    hop.to_string()
+}
+
+/// 踏み台チェーンを接続順（最初に接続するホップが先頭）に展開する。
+/// 循環している場合は循環経路をエラーとして返す
+pub fn resolve_jump_chain(config: &SshConfig, configs: &[SshConfig]) -> Result<Vec<SshConfig>, Vec<String>> {
+    let mut chain = Vec::new();
+    let mut stack = vec![config.host.clone()];
+    collect_jump_chain(config, configs, &mut stack, &mut chain)?;
+    Ok(chain)
+}
+
+fn collect_jump_chain(
+    config: &SshConfig,
+    configs: &[SshConfig],
+    stack: &mut Vec<String>,
+    chain: &mut Vec<SshConfig>,
+) -> Result<(), Vec<String>> {
+    for hop in config.jump_hosts() {
+        if stack.contains(&hop) {
+            let mut cycle = stack.clone();
+            cycle.push(hop);
+            return Err(cycle);
         }
-        
-        config
+        let hop_config = configs
+            .iter()
+            .find(|c| c.host == hop)
+            .cloned()
+            .unwrap_or_else(|| SshConfig::new(hop.clone()));
+
+        stack.push(hop);
+        collect_jump_chain(&hop_config, configs, stack, chain)?;
+        stack.pop();
+        chain.push(hop_config);
+    }
+    Ok(())
+}
+
+/// `#tags` の値（カンマ区切り）をタグのリストに分解する
+pub fn parse_tags(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+        .collect()
+}
+
+pub fn get_ssh_config_path() -> io::Result<PathBuf> {
+    Ok(home_dir()?.join(".ssh").join("config"))
+}
+
+/// sshct が書き込む Host ブロックを囲む目印。この範囲の外は手書きの設定として扱う
+pub const MANAGED_BEGIN: &str = "# BEGIN ssh_supporter";
+pub const MANAGED_END: &str = "# END ssh_supporter";
+
+/// 管理領域の BEGIN 行と END 行の位置（END がない場合はファイル末尾まで）
+fn managed_region<S: AsRef<str>>(lines: &[S]) -> Option<(usize, usize)> {
+    let begin = lines.iter().position(|l| l.as_ref().trim() == MANAGED_BEGIN)?;
+    let end = lines[begin..]
+        .iter()
+        .position(|l| l.as_ref().trim() == MANAGED_END)
+        .map_or(lines.len(), |i| begin + i);
+    Some((begin, end))
+}
+
+/// 管理領域の中で定義されているホスト
+pub fn managed_hosts() -> io::Result<Vec<String>> {
+    let config_path = get_ssh_config_path()?;
+    let content = match fs::read_to_string(&config_path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let lines: Vec<&str> = content.lines().collect();
+    let Some((begin, end)) = managed_region(&lines) else {
+        return Ok(Vec::new());
+    };
+
+    let host_re = Regex::new(r"(?i)^Host\s+(.+)$").unwrap();
+    Ok(lines[begin..end]
+        .iter()
+        .filter_map(|line| host_re.captures(line).map(|caps| caps[1].trim().to_string()))
+        .collect())
+}
+
+pub fn is_managed_host(host: &str) -> io::Result<bool> {
+    Ok(managed_hosts()?.iter().any(|h| h == host))
+}
+
+/// `ssh -G` の出力から、ワイルドカードや Include を反映した有効な設定値を読み取る。
+/// `-F` を付けると /etc/ssh/ssh_config が読まれなくなるので、実際の `ssh <alias>` と同じく付けない
+pub fn ssh_effective_config(alias: &str) -> Option<Vec<(String, String)>> {
+    let output = Command::new("ssh").arg("-G").arg(alias).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|line| {
+                let (key, value) = line.split_once(' ')?;
+                Some((key.to_lowercase(), value.trim().to_string()))
+            })
+            .collect(),
+    )
+}
+
+pub fn effective_value<'a>(values: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    values
+        .iter()
+        .find(|(k, _)| k == key)
+        .map(|(_, v)| v.as_str())
+        .filter(|v| *v != "none")
+}
+
+/// `ssh -G` の結果をエイリアスごとに 1 回だけ取得して使い回す。
+/// ssh -G は config 全体を読み直し `Match exec` のコマンドも実行するので、1 回の接続の中で何度も呼ばない
+#[derive(Debug, Default)]
+pub struct EffectiveConfigs {
+    values: HashMap<String, Vec<(String, String)>>,
+}
+
+impl EffectiveConfigs {
+    /// エイリアスの有効な設定値（ssh -G が失敗した場合は空）
+    pub fn get(&mut self, alias: &str) -> &[(String, String)] {
+        self.values
+            .entry(alias.to_string())
+            .or_insert_with(|| ssh_effective_config(alias).unwrap_or_default())
+    }
+
+    pub fn value(&mut self, alias: &str, key: &str) -> Option<&str> {
+        effective_value(self.get(alias), key)
     }
 }
 
-pub fn get_ssh_config_path() -> PathBuf {
-    let home = std::env::var("HOME").expect("HOME environment variable not set");
-    PathBuf::from(home).join(".ssh").join("config")
+/// `*` と `?` を含む Host パターンが単一のホスト名にマッチするか判定する
+pub fn pattern_matches(pattern: &str, text: &str) -> bool {
+    let p: Vec<char> = pattern.to_lowercase().chars().collect();
+    let t: Vec<char> = text.to_lowercase().chars().collect();
+    let (mut pi, mut ti) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+
+    while ti < t.len() {
+        if pi < p.len() && (p[pi] == '?' || p[pi] == t[ti]) {
+            pi += 1;
+            ti += 1;
+        } else if pi < p.len() && p[pi] == '*' {
+            star = Some((pi, ti));
+            pi += 1;
+        } else if let Some((sp, st)) = star {
+            pi = sp + 1;
+            ti = st + 1;
+            star = Some((sp, st + 1));
+        } else {
+            return false;
+        }
+    }
+    p[pi..].iter().all(|&c| c == '*')
+}
+
+/// `Host` 行のパターンリスト（`!` による否定を含む）がホスト名にマッチするか判定する
+pub fn host_patterns_match(patterns: &str, host: &str) -> bool {
+    let mut matched = false;
+    for pattern in patterns.split_whitespace() {
+        if let Some(negated) = pattern.strip_prefix('!') {
+            if pattern_matches(negated, host) {
+                return false;
+            }
+        } else if pattern_matches(pattern, host) {
+            matched = true;
+        }
+    }
+    matched
 }
 
 pub fn parse_ssh_config() -> io::Result<Vec<SshConfig>> {
-    let config_path = get_ssh_config_path();
+    let config_path = get_ssh_config_path()?;
     
     if !config_path.exists() {
         return Ok(Vec::new());
     }
     
     let content = fs::read_to_string(&config_path)?;
+    Ok(parse_config_content(&content))
+}
+
+/// ssh_config の内容から Host ブロックを読み取る
+fn parse_config_content(content: &str) -> Vec<SshConfig> {
     let mut configs = Vec::new();
     let mut current_config: Option<SshConfig> = None;
     
@@ -76,7 +842,19 @@ pub fn parse_ssh_config() -> io::Result<Vec<SshConfig>> {
     let port_re = Regex::new(r"(?i)^\s*Port\s+(.+)$").unwrap();
     let identity_re = Regex::new(r"(?i)^\s*IdentityFile\s+(.+)$").unwrap();
     let proxy_re = Regex::new(r"(?i)^\s*ProxyCommand\s+(.+)$").unwrap();
+    let jump_re = Regex::new(r"(?i)^\s*ProxyJump\s+(.+)$").unwrap();
     let pass_re = Regex::new(r"(?i)^\s*#pass\s+(.+)$").unwrap();
+    let passphrase_re = Regex::new(r"(?i)^\s*#passphrase\s+(.+)$").unwrap();
+    let agent_re = Regex::new(r"(?i)^\s*#agent\s+(.+)$").unwrap();
+    let totp_re = Regex::new(r"(?i)^\s*#totp\s+(.+)$").unwrap();
+    let totp_prompt_re = Regex::new(r"(?i)^\s*#totp-prompt\s+(.+)$").unwrap();
+    let tags_re = Regex::new(r"(?i)^\s*#tags\s+(.+)$").unwrap();
+    let env_re = Regex::new(r"(?i)^\s*#env\s+(.+)$").unwrap();
+    let sudo_re = Regex::new(r"(?i)^\s*#sudo\s*$").unwrap();
+    let record_re = Regex::new(r"(?i)^\s*#record\s*$").unwrap();
+    let rule_re = Regex::new(r"(?i)^\s*#rule(-after-login)?\s+(.+)$").unwrap();
+    let tunnel_re = Regex::new(r"(?i)^\s*#tunnel\s+(.+)$").unwrap();
+    let match_re = Regex::new(r"(?i)^\s*Match(\s|=|$)").unwrap();
     
     for line in content.lines() {
         if let Some(caps) = host_re.captures(line) {
@@ -84,6 +862,11 @@ pub fn parse_ssh_config() -> io::Result<Vec<SshConfig>> {
                 configs.push(config);
             }
             current_config = Some(SshConfig::new(caps[1].trim().to_string()));
+        } else if match_re.is_match(line) {
+            // Match ブロックはホストの設定として扱わない
+            if let Some(config) = current_config.take() {
+                configs.push(config);
+            }
         } else if let Some(config) = current_config.as_mut() {
             if let Some(caps) = hostname_re.captures(line) {
                 config.hostname = Some(caps[1].trim().to_string());
@@ -92,11 +875,41 @@ pub fn parse_ssh_config() -> io::Result<Vec<SshConfig>> {
             } else if let Some(caps) = port_re.captures(line) {
                 config.port = Some(caps[1].trim().to_string());
             } else if let Some(caps) = identity_re.captures(line) {
-                config.identity_file = Some(caps[1].trim().to_string());
+                config.identity_files.push(caps[1].trim().to_string());
             } else if let Some(caps) = proxy_re.captures(line) {
                 config.proxy_command = Some(caps[1].trim().to_string());
+            } else if let Some(caps) = jump_re.captures(line) {
+                config.proxy_jump = Some(caps[1].trim().to_string());
             } else if let Some(caps) = pass_re.captures(line) {
                 config.password = Some(caps[1].trim().to_string());
+            } else if let Some(caps) = passphrase_re.captures(line) {
+                config.passphrase = Some(caps[1].trim().to_string());
+            } else if let Some(caps) = agent_re.captures(line) {
+                if let Some(options) = AgentOptions::parse(&caps[1]) {
+                    config.agent_options = options;
+                }
+            } else if let Some(caps) = totp_re.captures(line) {
+                config.totp_secret = Some(caps[1].trim().to_string());
+            } else if let Some(caps) = totp_prompt_re.captures(line) {
+                config.totp_prompt = Some(caps[1].trim().to_string());
+            } else if let Some(caps) = tags_re.captures(line) {
+                config.tags = parse_tags(&caps[1]);
+            } else if let Some(caps) = env_re.captures(line) {
+                config.environment = EnvironmentLabel::parse(&caps[1]);
+            } else if sudo_re.is_match(line) {
+                config.auto_sudo = true;
+            } else if record_re.is_match(line) {
+                config.record = true;
+            } else if let Some(caps) = rule_re.captures(line) {
+                if let Some(rule) = PromptRuleSpec::parse(caps.get(1).is_some(), &caps[2]) {
+                    config.prompt_rules.push(rule);
+                }
+            } else if let Some(caps) = tunnel_re.captures(line) {
+                if let Some(tunnel) = TunnelProfile::parse(&caps[1]) {
+                    config.tunnels.push(tunnel);
+                }
+            } else if let Some((keyword, value)) = split_directive(line) {
+                config.set_parsed_directive(&keyword, value);
             }
         }
     }
@@ -105,187 +918,555 @@ pub fn parse_ssh_config() -> io::Result<Vec<SshConfig>> {
         configs.push(config);
     }
     
-    Ok(configs)
+    configs
 }
 
+/// ホストの設定を探す。同名のブロックが複数ある場合は update_ssh_config が書き換えるもの
+/// （管理領域の中のもの、なければ最初のもの）を返す
 pub fn find_config_by_host(host: &str) -> io::Result<Option<SshConfig>> {
-    let configs = parse_ssh_config()?;
-    Ok(configs.into_iter().find(|c| c.host == host))
+    let config_path = get_ssh_config_path()?;
+    let content = match fs::read_to_string(&config_path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    Ok(find_config_in(&content, host))
+}
+
+fn find_config_in(content: &str, host: &str) -> Option<SshConfig> {
+    let lines: Vec<&str> = content.lines().collect();
+    if let Some((begin, end)) = managed_region(&lines) {
+        let managed = parse_config_content(&lines[begin..end].join("\n"));
+        if let Some(config) = managed.into_iter().find(|c| c.host == host) {
+            return Some(config);
+        }
+    }
+    parse_config_content(content).into_iter().find(|c| c.host == host)
 }
 
 // 既存のwrite_ssh_configは削除し、より安全な編集ロジックを使用する
 
 pub fn add_ssh_config(new_config: SshConfig) -> io::Result<()> {
-    let config_path = get_ssh_config_path();
+    let config_path = get_ssh_config_path()?;
     
     // .sshディレクトリが存在しない場合は作成
     if let Some(parent) = config_path.parent() {
         fs::create_dir_all(parent)?;
     }
     
-    let mut file = fs::OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open(&config_path)?;
+    let content = match fs::read_to_string(&config_path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e),
+    };
+    let mut lines: Vec<String> = content.lines().map(|s| s.to_string()).collect();
+    let block: Vec<String> = new_config.to_config_string().lines().map(|s| s.to_string()).collect();
     
-    // ファイルが空でない場合は改行を追加
-    if file.metadata()?.len() > 0 {
-        writeln!(file)?;
+    match managed_region(&lines) {
+        // 管理領域があれば END の直前に追加する
+        Some((begin, end)) => {
+            let mut insert = Vec::new();
+            if end > begin + 1 && !lines[end - 1].trim().is_empty() {
+                insert.push(String::new());
+            }
+            insert.extend(block);
+            if end == lines.len() {
+                insert.push(MANAGED_END.to_string());
+            }
+            lines.splice(end..end, insert);
+        }
+        // なければファイル末尾に管理領域を作る
+        None => {
+            if lines.last().is_some_and(|l| !l.trim().is_empty()) {
+                lines.push(String::new());
+            }
+            lines.push(MANAGED_BEGIN.to_string());
+            lines.extend(block);
+            lines.push(MANAGED_END.to_string());
+        }
     }
     
-    write!(file, "{}", new_config.to_config_string())?;
+    let mut file = fs::File::create(&config_path)?;
+    for line in lines {
+        writeln!(file, "{}", line)?;
+    }
     
     Ok(())
 }
 
-pub fn update_ssh_config(host: &str, updated_config: SshConfig) -> io::Result<bool> {
-    let config_path = get_ssh_config_path();
+/// Host ブロックに書き出す項目（書き出す順）。`directives` は専用フィールド以外のディレクティブ
+const BLOCK_FIELDS: [&str; 18] = [
+    "hostname",
+    "user",
+    "port",
+    "identityfile",
+    "proxycommand",
+    "proxyjump",
+    "directives",
+    "#pass",
+    "#passphrase",
+    "#agent",
+    "#totp",
+    "#totp-prompt",
+    "#tags",
+    "#env",
+    "#sudo",
+    "#record",
+    "#rule",
+    "#tunnel",
+];
+
+/// Host ブロック内の行がどの項目のものか（コメントや Host 行など、項目でなければ None）
+fn block_field(line: &str) -> Option<&'static str> {
+    let lower = line.trim().to_lowercase();
+    if lower.starts_with('#') {
+        let (word, value) = lower.split_once(char::is_whitespace).unwrap_or((&lower, ""));
+        let has_value = !value.trim().is_empty();
+        return match word {
+            "#rule" | "#rule-after-login" if has_value => Some("#rule"),
+            "#sudo" | "#record" if !has_value => BLOCK_FIELDS.iter().find(|f| **f == word).copied(),
+            "#sudo" | "#record" => None,
+            _ if has_value => BLOCK_FIELDS.iter().find(|f| **f == word).copied(),
+            _ => None,
+        };
+    }
+    // `HostName=example.com` のように = で区切った書き方もある
+    let (keyword, _) = split_directive(&lower)?;
+    match BLOCK_FIELDS.iter().find(|f| **f == keyword) {
+        Some(field) => Some(field),
+        None if !is_typed_keyword(&keyword) => Some("directives"),
+        None => None,
+    }
+}
+
+/// ホストの設定を書き換える。
+///
+/// 書き換えるのは管理領域（`# BEGIN ssh_supporter` ～ `# END ssh_supporter`）の中のブロックだけで、
+/// 領域の外にしかない場合はエラーを返す。`force` を指定すると領域の外のブロックも書き換える。
+/// 同じホストのブロックが複数ある場合は 1 つだけ（管理領域の中のもの、なければ最初のもの）を書き換える。
+pub fn update_ssh_config(host: &str, updated_config: SshConfig, force: bool) -> io::Result<bool> {
+    let config_path = get_ssh_config_path()?;
     if !config_path.exists() {
         return Ok(false);
     }
     
     let content = fs::read_to_string(&config_path)?;
-    let mut lines: Vec<String> = content.lines().map(|s| s.to_string()).collect();
+    let Some(new_lines) = rewrite_host_block(&content, host, &updated_config, force)? else {
+        return Ok(false);
+    };
+    let mut file = fs::File::create(&config_path)?;
+    for line in new_lines {
+        writeln!(file, "{}", line)?;
+    }
+    Ok(true)
+}
+
+/// config の内容のうち host のブロックを書き換えた行を返す（対象のブロックがなければ None）。
+/// 項目ごとに最初の行の位置へ新しい値をまとめて書き出し、残りの行は削除する。
+/// コメントなど項目でない行はそのまま残し、元のブロックになかった項目はブロックの末尾に追加する
+fn rewrite_host_block(content: &str, host: &str, updated_config: &SshConfig, force: bool) -> io::Result<Option<Vec<String>>> {
+    let lines: Vec<String> = content.lines().map(|s| s.to_string()).collect();
     let mut new_lines = Vec::new();
+    let region = managed_region(&lines);
+    let in_region = |index: usize| region.is_some_and(|(begin, end)| index > begin && index < end);
     
     let host_re = Regex::new(r"(?i)^Host\s+(.+)$").unwrap();
+    let match_re = Regex::new(r"(?i)^\s*Match(\s|=|$)").unwrap();
+    // 書き換えるブロックの Host 行。ほかの同名のブロックには触れない
+    let blocks: Vec<usize> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| host_re.captures(line).is_some_and(|caps| caps[1].trim() == host))
+        .map(|(index, _)| index)
+        .collect();
+    let target = match blocks.iter().find(|&&index| in_region(index)) {
+        Some(&index) => index,
+        None if force && !blocks.is_empty() => blocks[0],
+        None if blocks.is_empty() => return Ok(None),
+        None => {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("'{}' is outside the ssh_supporter managed region; use --force to edit it", host),
+            ))
+        }
+    };
     let mut in_target_host = false;
-    let mut host_found = false;
-    
-    // 更新対象のフィールドを追跡
-    let mut updated_hostname = false;
-    let mut updated_user = false;
-    let mut updated_port = false;
-    let mut updated_identity = false;
-    let mut updated_proxy = false;
-    let mut updated_password = false;
+    // 書き出し済みの項目
+    let mut written: HashSet<&'static str> = HashSet::new();
     
-    for line in lines.iter() {
-        if let Some(caps) = host_re.captures(line) {
-            let current_host = caps[1].trim();
-            if current_host == host {
-                in_target_host = true;
-                host_found = true;
-                new_lines.push(line.clone());
-                continue;
-            } else {
-                // ターゲットホストのブロックが終わった場合
-                if in_target_host {
-                    // まだ追加されていないフィールドがあれば追加
-                    append_missing_fields(&mut new_lines, &updated_config, 
-                        updated_hostname, updated_user, updated_port, 
-                        updated_identity, updated_proxy, updated_password);
-                }
-                in_target_host = false;
-            }
+    for (index, line) in lines.iter().enumerate() {
+        // 管理領域の目印もブロックの終わりとして扱い、目印の外に項目を追加しないようにする
+        // Match 行も同様にブロックの終わりになる
+        let is_boundary = line.trim() == MANAGED_BEGIN
+            || line.trim() == MANAGED_END
+            || match_re.is_match(line)
+            || host_re.is_match(line);
+        if is_boundary && in_target_host {
+            append_missing_fields(&mut new_lines, updated_config, &written);
+            in_target_host = false;
         }
         
-        if in_target_host {
-            // フィールドの更新ロジック
-            // 各行がどのフィールドか判定し、更新対象なら値を書き換える
-            // 更新対象でない（コメントや未知のフィールド）ならそのまま残す
-            
-            let lower_line = line.to_lowercase();
-            let trimmed = lower_line.trim_start();
-            
-            if trimmed.starts_with("hostname ") {
-                if let Some(val) = &updated_config.hostname {
-                    new_lines.push(format!("  HostName {}", val));
-                    updated_hostname = true;
-                } else {
-                    // 値がNoneなら行を削除（追加しない）
-                }
-            } else if trimmed.starts_with("user ") {
-                if let Some(val) = &updated_config.user {
-                    new_lines.push(format!("  User {}", val));
-                    updated_user = true;
-                }
-            } else if trimmed.starts_with("port ") {
-                if let Some(val) = &updated_config.port {
-                    new_lines.push(format!("  Port {}", val));
-                    updated_port = true;
-                }
-            } else if trimmed.starts_with("identityfile ") {
-                if let Some(val) = &updated_config.identity_file {
-                    new_lines.push(format!("  IdentityFile {}", val));
-                    updated_identity = true;
-                }
-            } else if trimmed.starts_with("proxycommand ") {
-                if let Some(val) = &updated_config.proxy_command {
-                    new_lines.push(format!("  ProxyCommand {}", val));
-                    updated_proxy = true;
-                }
-            } else if trimmed.starts_with("#pass ") {
-                if let Some(val) = &updated_config.password {
-                    new_lines.push(format!("  #pass {}", val));
-                    updated_password = true;
+        if host_re.is_match(line) {
+            in_target_host = index == target;
+            new_lines.push(line.clone());
+            continue;
+        }
+        
+        if !in_target_host {
+            new_lines.push(line.clone());
+            continue;
+        }
+        match block_field(line) {
+            Some(field) => {
+                if written.insert(field) {
+                    new_lines.extend(updated_config.field_lines(field));
                 }
-            } else {
-                // その他の行（コメント、未知の設定など）はそのまま保持
-                new_lines.push(line.clone());
             }
-        } else {
-            // ターゲットホスト以外の行はそのまま保持
-            new_lines.push(line.clone());
+            // その他の行（コメントなど）はそのまま保持
+            None => new_lines.push(line.clone()),
         }
     }
     
     // ファイル末尾がターゲットホストだった場合の処理
     if in_target_host {
-        append_missing_fields(&mut new_lines, &updated_config, 
-            updated_hostname, updated_user, updated_port, 
-            updated_identity, updated_proxy, updated_password);
+        append_missing_fields(&mut new_lines, updated_config, &written);
     }
     
-    if host_found {
-        let mut file = fs::File::create(&config_path)?;
-        for line in new_lines {
-            writeln!(file, "{}", line)?;
+    Ok(Some(new_lines))
+}
+
+/// まだ書き出していない項目をブロックの末尾に追加する
+fn append_missing_fields(lines: &mut Vec<String>, config: &SshConfig, written: &HashSet<&'static str>) {
+    // ブロック末尾の空行より前に追加する（次のブロックとの区切りを保つ）
+    let blank_start = lines
+        .iter()
+        .rposition(|l| !l.trim().is_empty())
+        .map_or(0, |i| i + 1);
+    let trailing_blanks = lines.split_off(blank_start);
+    
+    for field in BLOCK_FIELDS {
+        if !written.contains(field) {
+            lines.extend(config.field_lines(field));
         }
-        Ok(true)
-    } else {
-        Ok(false)
     }
+    
+    lines.extend(trailing_blanks);
 }
 
-fn append_missing_fields(
-    lines: &mut Vec<String>, 
-    config: &SshConfig,
-    has_hostname: bool,
-    has_user: bool,
-    has_port: bool,
-    has_identity: bool,
-    has_proxy: bool,
-    has_password: bool
-) {
-    if !has_hostname {
-        if let Some(val) = &config.hostname {
-            lines.push(format!("  HostName {}", val));
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rewrite(content: &str, config: &SshConfig, force: bool) -> String {
+        let lines = rewrite_host_block(content, &config.host, config, force).unwrap().unwrap();
+        lines.join("\n") + "\n"
     }
-    if !has_user {
-        if let Some(val) = &config.user {
-            lines.push(format!("  User {}", val));
-        }
+
+    fn parse_one(content: &str, host: &str) -> SshConfig {
+        parse_config_content(content).into_iter().find(|c| c.host == host).unwrap()
     }
-    if !has_port {
-        if let Some(val) = &config.port {
-            lines.push(format!("  Port {}", val));
-        }
+
+    #[test]
+    fn writes_block_fields_in_order() {
+        let mut config = SshConfig::new("web".to_string());
+        config.hostname = Some("10.0.0.1".to_string());
+        config.port = Some("2222".to_string());
+        config.identity_files = vec!["~/.ssh/a".to_string(), "~/.ssh/b".to_string()];
+        config.add_directive("ForwardAgent", "yes");
+        config.password = Some("s3cret".to_string());
+        config.tags = vec!["prod".to_string()];
+        config.auto_sudo = true;
+        assert_eq!(
+            config.to_config_string(),
+            "Host web\n  HostName 10.0.0.1\n  Port 2222\n  IdentityFile ~/.ssh/a\n  IdentityFile ~/.ssh/b\n  \
+             ForwardAgent yes\n  #pass s3cret\n  #tags prod\n  #sudo\n"
+        );
     }
-    if !has_identity {
-        if let Some(val) = &config.identity_file {
-            lines.push(format!("  IdentityFile {}", val));
-        }
+
+    #[test]
+    fn rewrites_values_in_place_and_appends_new_fields() {
+        let content = "\
+# BEGIN ssh_supporter
+Host web
+  # primary web server
+  HostName=10.0.0.1
+  User deploy
+  #pass old
+
+Host db
+  HostName 10.0.0.2
+# END ssh_supporter
+";
+        let mut config = parse_one(content, "web");
+        config.hostname = Some("10.0.0.9".to_string());
+        config.user = None;
+        config.password = Some("new".to_string());
+        config.port = Some("2222".to_string());
+
+        assert_eq!(
+            rewrite(content, &config, false),
+            "\
+# BEGIN ssh_supporter
+Host web
+  # primary web server
+  HostName 10.0.0.9
+  #pass new
+  Port 2222
+
+Host db
+  HostName 10.0.0.2
+# END ssh_supporter
+"
+        );
     }
-    if !has_proxy {
-        if let Some(val) = &config.proxy_command {
-            lines.push(format!("  ProxyCommand {}", val));
-        }
+
+    #[test]
+    fn refuses_blocks_outside_managed_region() {
+        let content = "Host web\n  HostName 10.0.0.1\n";
+        let config = parse_one(content, "web");
+        let error = rewrite_host_block(content, "web", &config, false).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::PermissionDenied);
+        assert!(rewrite_host_block(content, "other", &config, false).unwrap().is_none());
+        assert_eq!(rewrite(content, &config, true), content);
     }
-    if !has_password {
-        if let Some(val) = &config.password {
-            lines.push(format!("  #pass {}", val));
+
+    #[test]
+    fn rewrites_only_one_of_duplicate_blocks() {
+        let content = "\
+Host web
+  HostName old.example.com
+  User hand
+
+# BEGIN ssh_supporter
+Host web
+  HostName 10.0.0.1
+  #pass s3cret
+# END ssh_supporter
+
+Host web
+  Port 2200
+";
+        let mut config = find_config_in(content, "web").unwrap();
+        assert_eq!(config.password.as_deref(), Some("s3cret"));
+        config.hostname = Some("10.0.0.9".to_string());
+        config.port = Some("2222".to_string());
+
+        // 管理領域の中のブロックだけを書き換え、--force でも外のブロックには触れない
+        for force in [false, true] {
+            let rewritten = rewrite(content, &config, force);
+            assert_eq!(
+                rewritten,
+                "\
+Host web
+  HostName old.example.com
+  User hand
+
+# BEGIN ssh_supporter
+Host web
+  HostName 10.0.0.9
+  #pass s3cret
+  Port 2222
+# END ssh_supporter
+
+Host web
+  Port 2200
+"
+            );
+            // 書き換えた結果を読み直しても同じになる
+            assert_eq!(find_config_in(&rewritten, "web").unwrap().to_config_string(), config.to_config_string());
+            assert_eq!(rewrite(&rewritten, &config, force), rewritten);
         }
     }
+
+    #[test]
+    fn force_rewrites_first_block_when_none_is_managed() {
+        let content = "Host web\n  HostName a\n\nHost web\n  HostName b\n";
+        let mut config = SshConfig::new("web".to_string());
+        config.hostname = Some("c".to_string());
+        assert_eq!(rewrite(content, &config, true), "Host web\n  HostName c\n\nHost web\n  HostName b\n");
+    }
+
+    const DIRECTIVES_FIXTURE: &str = "\
+Include ~/.ssh/conf.d/*
+
+Host *
+  ServerAliveInterval 30
+
+# BEGIN ssh_supporter
+Host app
+  # deployed by ansible
+  HostName app.internal
+  User deploy
+  ForwardAgent yes
+  SetEnv LANG=C.UTF-8
+  SetEnv TZ=UTC
+  LocalForward 5432 localhost:5432
+  FutureOption something new
+  #pass s3cret
+  #tags web,prod
+
+Match host app exec \"test -f /tmp/vpn\"
+  ProxyJump bastion
+
+Host other
+  HostName other.internal
+# END ssh_supporter
+";
+
+    #[test]
+    fn round_trips_unknown_directives() {
+        let config = parse_one(DIRECTIVES_FIXTURE, "app");
+        assert_eq!(config.directive_values("SetEnv"), ["LANG=C.UTF-8", "TZ=UTC"]);
+        assert_eq!(config.directive("FutureOption"), Some("something new"));
+        assert_eq!(config.forward_agent(), Some(true));
+        // Match ブロックの ProxyJump は app の設定に含めない
+        assert!(config.proxy_jump.is_none());
+
+        let reparsed = parse_one(&config.to_config_string(), "app");
+        assert_eq!(reparsed.directives, config.directives);
+        assert_eq!(reparsed.to_config_string(), config.to_config_string());
+    }
+
+    #[test]
+    fn unchanged_update_keeps_file_intact() {
+        let config = parse_one(DIRECTIVES_FIXTURE, "app");
+        assert_eq!(rewrite(DIRECTIVES_FIXTURE, &config, false), DIRECTIVES_FIXTURE);
+    }
+
+    #[test]
+    fn editing_one_directive_keeps_the_rest() {
+        let mut config = parse_one(DIRECTIVES_FIXTURE, "app");
+        config.set_directive("ForwardAgent", Some("no"));
+        config.add_directive("SetEnv", "EDITOR=vim");
+        config.set_directive("FutureOption", None);
+        config.user = Some("admin".to_string());
+
+        let rewritten = rewrite(DIRECTIVES_FIXTURE, &config, false);
+        let expected = DIRECTIVES_FIXTURE
+            .replace("  User deploy\n", "  User admin\n")
+            .replace("  ForwardAgent yes\n", "  ForwardAgent no\n")
+            .replace("  FutureOption something new\n", "  SetEnv EDITOR=vim\n");
+        assert_eq!(rewritten, expected);
+
+        let reparsed = parse_one(&rewritten, "app");
+        assert_eq!(reparsed.directive_values("SetEnv"), ["LANG=C.UTF-8", "TZ=UTC", "EDITOR=vim"]);
+        assert_eq!(reparsed.to_config_string(), config.to_config_string());
+        assert_eq!(parse_one(&rewritten, "other").hostname.as_deref(), Some("other.internal"));
+    }
+
+    #[test]
+    fn new_fields_are_added_before_match_block() {
+        let mut config = parse_one(DIRECTIVES_FIXTURE, "other");
+        config.add_directive("Compression", "yes");
+        config.record = true;
+        let content = DIRECTIVES_FIXTURE.replace("Host other\n  HostName other.internal\n", "");
+        let content = content.replace("Host app\n", "Host other\n  HostName other.internal\n\nHost app\n");
+
+        let rewritten = rewrite(&content, &config, false);
+        assert!(rewritten.contains("Host other\n  HostName other.internal\n  Compression yes\n  #record\n\nHost app\n"));
+        assert!(rewritten.contains("Match host app exec \"test -f /tmp/vpn\"\n  ProxyJump bastion\n"));
+    }
+
+    const IDENTITY_FIXTURE: &str = "\
+# BEGIN ssh_supporter
+Host git
+  HostName git.example.com
+  IdentityFile ~/.ssh/id_ed25519
+  IdentitiesOnly yes
+  IdentityFile ~/.ssh/id_rsa
+  identityfile=~/.ssh/legacy
+  #passphrase hunter2
+# END ssh_supporter
+";
+
+    #[test]
+    fn keeps_every_identity_file() {
+        let config = parse_one(IDENTITY_FIXTURE, "git");
+        assert_eq!(config.identity_files, ["~/.ssh/id_ed25519", "~/.ssh/id_rsa", "~/.ssh/legacy"]);
+        assert_eq!(config.identity_file(), Some("~/.ssh/id_ed25519"));
+        assert_eq!(parse_one(&config.to_config_string(), "git").identity_files, config.identity_files);
+
+        // 書き換えると最初の IdentityFile の位置にまとめて書き出す
+        let rewritten = rewrite(IDENTITY_FIXTURE, &config, false);
+        assert_eq!(
+            rewritten,
+            "\
+# BEGIN ssh_supporter
+Host git
+  HostName git.example.com
+  IdentityFile ~/.ssh/id_ed25519
+  IdentityFile ~/.ssh/id_rsa
+  IdentityFile ~/.ssh/legacy
+  IdentitiesOnly yes
+  #passphrase hunter2
+# END ssh_supporter
+"
+        );
+        assert_eq!(rewrite(&rewritten, &config, false), rewritten);
+    }
+
+    #[test]
+    fn replaces_and_reorders_identity_files() {
+        let mut config = parse_one(IDENTITY_FIXTURE, "git");
+        config.set_identity_file("~/.ssh/new".to_string());
+        config.identity_files.swap(1, 2);
+        let rewritten = rewrite(IDENTITY_FIXTURE, &config, false);
+        assert_eq!(
+            parse_one(&rewritten, "git").identity_files,
+            ["~/.ssh/new", "~/.ssh/legacy", "~/.ssh/id_rsa"]
+        );
+
+        config.identity_files.clear();
+        let rewritten = rewrite(&rewritten, &config, false);
+        assert!(!rewritten.contains("IdentityFile"));
+        assert!(rewritten.contains("  IdentitiesOnly yes\n  #passphrase hunter2\n"));
+    }
+
+    #[test]
+    fn parses_tunnel_profiles() {
+        let tunnel = TunnelProfile::parse("db: L 5432:db.internal:5432 d 1080").unwrap();
+        assert_eq!(tunnel.name, "db");
+        assert_eq!(
+            tunnel.forwards,
+            [
+                (ForwardKind::Local, "5432:db.internal:5432".to_string()),
+                (ForwardKind::Dynamic, "1080".to_string())
+            ]
+        );
+        assert_eq!(tunnel.forwards_string(), "L 5432:db.internal:5432 D 1080");
+        assert_eq!(tunnel.to_config_line(), "#tunnel db L 5432:db.internal:5432 D 1080");
+        assert_eq!(tunnel.ssh_args(), ["-L", "5432:db.internal:5432", "-D", "1080"]);
+
+        assert_eq!(TunnelProfile::parse("db"), None);
+        assert_eq!(TunnelProfile::parse("db L"), None);
+        assert_eq!(TunnelProfile::parse("db X 1:a:1"), None);
+        assert_eq!(TunnelProfile::parse("../../x L 1:a:1"), None);
+        assert_eq!(TunnelProfile::parse("a/b L 1:a:1"), None);
+        assert_eq!(TunnelProfile::parse(".hidden L 1:a:1"), None);
+    }
+
+    #[test]
+    fn lists_tunnel_listen_ports() {
+        let ports = |value: &str| TunnelProfile::parse(value).unwrap().listen_ports();
+        assert_eq!(ports("t L 5432:db:5432 R 9000:localhost:3000 D 1080"), ["5432", "remote:9000", "1080"]);
+        assert_eq!(ports("t L 127.0.0.1:5432:db:5432 D *:1080"), ["5432", "1080"]);
+        assert_eq!(ports("t L [::1]:5432:db:5432 D [::1]:1080"), ["5432", "1080"]);
+        assert_eq!(ports("t L 5432:[2001:db8::1]:5432"), ["5432"]);
+        assert_eq!(ports("t L [::1]:6000:[fe80::1]:22"), ["6000"]);
+        assert!(ports("t L 5432").is_empty());
+    }
+
+    #[test]
+    fn recognizes_block_fields() {
+        assert_eq!(block_field("  HostName example.com"), Some("hostname"));
+        assert_eq!(block_field("  identityfile=~/.ssh/id"), Some("identityfile"));
+        assert_eq!(block_field("  ForwardAgent yes"), Some("directives"));
+        assert_eq!(block_field("  #passphrase secret"), Some("#passphrase"));
+        assert_eq!(block_field("  #pass secret"), Some("#pass"));
+        assert_eq!(block_field("  #rule-after-login $ whoami"), Some("#rule"));
+        assert_eq!(block_field("  #sudo"), Some("#sudo"));
+        assert_eq!(block_field("  #passwords are in the vault"), None);
+        assert_eq!(block_field("  # a comment"), None);
+        assert_eq!(block_field(""), None);
+    }
 }
@@ -0,0 +1,376 @@
+use crate::config::SshConfig;
+use crate::utils::home_dir;
+use std::collections::{BTreeSet, HashSet};
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// known_hosts やシェル履歴で見つかった、config に未登録の接続先
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub alias: String,
+    pub hostname: String,
+    pub user: Option<String>,
+    pub port: Option<String>,
+    /// 履歴に現れた回数
+    pub uses: usize,
+    pub sources: BTreeSet<&'static str>,
+}
+
+impl Candidate {
+    pub fn destination(&self) -> String {
+        let user = self.user.as_ref().map(|u| format!("{}@", u)).unwrap_or_default();
+        let port = self.port.as_ref().map(|p| format!(":{}", p)).unwrap_or_default();
+        format!("{}{}{}", user, self.hostname, port)
+    }
+
+    pub fn to_ssh_config(&self) -> SshConfig {
+        let mut config = SshConfig::new(self.alias.clone());
+        config.hostname = Some(self.hostname.clone());
+        config.user = self.user.clone();
+        config.port = self.port.clone();
+        config
+    }
+}
+
+/// ssh の接続先（ユーザー、ホスト、ポート）
+type Destination = (Option<String>, String, Option<String>);
+
+/// 値を取るオプション（`ssh -p 22` の `22` を接続先と取り違えないため）
+const OPTIONS_WITH_ARG: &str = "BbcDEeFIiJLlmOoPpQRSWw";
+
+/// ssh コマンドの引数から接続先を取り出す
+fn parse_ssh_args(args: &[String]) -> Option<Destination> {
+    let mut user = None;
+    let mut port = None;
+    let mut destination: Option<&str> = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let Some(flags) = arg.strip_prefix('-').filter(|f| !f.is_empty()) else {
+            // 接続先の後ろにもオプションを書けるが、それ以外はリモートで実行するコマンド
+            if destination.is_some() {
+                break;
+            }
+            destination = Some(arg);
+            continue;
+        };
+        // `-p2222` のように値がつながっていることもある
+        for (i, flag) in flags.char_indices() {
+            if OPTIONS_WITH_ARG.contains(flag) {
+                let inline = &flags[i + flag.len_utf8()..];
+                let value = if inline.is_empty() { iter.next()?.clone() } else { inline.to_string() };
+                match flag {
+                    'p' => port = Some(value),
+                    'l' => user = Some(value),
+                    'o' => match value.split_once(['=', ' ']) {
+                        Some((key, v)) if key.eq_ignore_ascii_case("port") => port = Some(v.trim().to_string()),
+                        Some((key, v)) if key.eq_ignore_ascii_case("user") => user = Some(v.trim().to_string()),
+                        _ => {}
+                    },
+                    _ => {}
+                }
+                break;
+            }
+        }
+    }
+
+    let destination = destination?;
+    let url = destination.strip_prefix("ssh://");
+    let (dest_user, rest) = match url.unwrap_or(destination).rsplit_once('@') {
+        Some((u, rest)) => (Some(u.to_string()), rest),
+        None => (None, url.unwrap_or(destination)),
+    };
+    let rest = rest.trim_end_matches('/');
+    let (host, dest_port) = match rest.rsplit_once(':') {
+        Some((h, p)) if url.is_some() => (h.to_string(), Some(p.to_string())),
+        _ => (rest.to_string(), None),
+    };
+    Some((dest_user.or(user), host, dest_port.or(port)))
+}
+
+/// 簡易的にシェルの単語へ分割する（クォートは外すが、展開は行わない）
+fn split_words(command: &str) -> Vec<Vec<String>> {
+    let mut commands = vec![Vec::new()];
+    let mut word = String::new();
+    let mut quote: Option<char> = None;
+    let mut has_word = false;
+    let mut chars = command.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => word.push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                has_word = true;
+            }
+            (None, '\\') => word.extend(chars.next()),
+            (None, c) if c.is_whitespace() || ";|&".contains(c) => {
+                if has_word || !word.is_empty() {
+                    commands.last_mut().unwrap().push(std::mem::take(&mut word));
+                    has_word = false;
+                }
+                if ";|&".contains(c) {
+                    commands.push(Vec::new());
+                }
+            }
+            (None, c) => word.push(c),
+        }
+    }
+    if has_word || !word.is_empty() {
+        commands.last_mut().unwrap().push(word);
+    }
+    commands
+}
+
+/// 履歴の 1 行からコマンド部分を取り出す（zsh の拡張形式と fish の形式に対応）
+fn history_command(line: &str) -> &str {
+    if let Some(rest) = line.strip_prefix(": ") {
+        if let Some((_, command)) = rest.split_once(';') {
+            return command;
+        }
+    }
+    line.strip_prefix("- cmd: ").unwrap_or(line)
+}
+
+/// 履歴の 1 行に含まれる ssh コマンドの接続先
+fn history_destinations(line: &str) -> Vec<Destination> {
+    split_words(history_command(line))
+        .into_iter()
+        .filter_map(|words| {
+            let position = words.iter().position(|w| w == "ssh" || w.ends_with("/ssh"))?;
+            parse_ssh_args(&words[position + 1..])
+        })
+        .collect()
+}
+
+/// known_hosts の 1 行のホスト名とポート。ハッシュ化された行やマーカー付きの行は None
+fn known_hosts_entry(line: &str) -> Option<(String, Option<String>)> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') || line.starts_with('@') || line.starts_with('|') {
+        return None;
+    }
+    let hosts = line.split_whitespace().next()?;
+    // 同じ行の名前と IP アドレスは同じホストなので、最初の名前だけを使う
+    let host = hosts.split(',').find(|h| !h.contains(['*', '?', '!']))?;
+    Some(match host.strip_prefix('[').and_then(|h| h.split_once("]:")) {
+        Some((host, port)) => (host.to_string(), Some(port.to_string())),
+        None => (host.to_string(), None),
+    })
+}
+
+fn history_files() -> io::Result<Vec<PathBuf>> {
+    let home = home_dir()?;
+    let mut files = vec![
+        home.join(".bash_history"),
+        home.join(".zsh_history"),
+        home.join(".zhistory"),
+        home.join(".local/share/fish/fish_history"),
+    ];
+    if let Some(histfile) = env::var_os("HISTFILE") {
+        files.insert(0, PathBuf::from(histfile));
+    }
+    let mut seen = HashSet::new();
+    files.retain(|f| f.is_file() && seen.insert(f.clone()));
+    Ok(files)
+}
+
+fn is_local(host: &str) -> bool {
+    matches!(host, "localhost" | "127.0.0.1" | "::1") || host.is_empty()
+}
+
+fn add_candidate(
+    candidates: &mut Vec<Candidate>,
+    user: Option<String>,
+    hostname: String,
+    port: Option<String>,
+    source: &'static str,
+) {
+    let port = port.filter(|p| p != "22" && p.parse::<u16>().is_ok());
+    let existing = candidates.iter_mut().find(|c| {
+        c.hostname == hostname && c.port == port && (user.is_none() || c.user.is_none() || c.user == user)
+    });
+    match existing {
+        Some(candidate) => {
+            if candidate.user.is_none() {
+                candidate.user = user;
+            }
+            if source == "history" {
+                candidate.uses += 1;
+            }
+            candidate.sources.insert(source);
+        }
+        None => candidates.push(Candidate {
+            alias: String::new(),
+            hostname,
+            user,
+            port,
+            uses: usize::from(source == "history"),
+            sources: BTreeSet::from([source]),
+        }),
+    }
+}
+
+fn candidates_from_history(candidates: &mut Vec<Candidate>, user: Option<String>, host: String, port: Option<String>) {
+    // 変数やコマンド置換を含むものは実際の接続先が分からない
+    if is_local(&host) || host.contains(['$', '`', '*', '(', '<', '>']) || host.starts_with('-') {
+        return;
+    }
+    add_candidate(candidates, user, host, port, "history");
+}
+
+/// 接続先から短いエイリアスを作る（`web1.example.com` → `web1`、IP アドレスは `host-10-0-0-1`）
+fn propose_alias(candidate: &Candidate, taken: &HashSet<String>) -> String {
+    let hostname = candidate.hostname.as_str();
+    let is_ip = hostname.parse::<std::net::IpAddr>().is_ok();
+    let full = hostname.replace([':', '.'], "-");
+    let mut options = Vec::new();
+    if is_ip {
+        options.push(format!("host-{}", full));
+    } else {
+        options.push(hostname.split('.').next().unwrap_or(hostname).to_string());
+        options.push(full);
+    }
+    if let Some(user) = &candidate.user {
+        options.push(format!("{}-{}", options[0], user));
+    }
+    if let Some(alias) = options.iter().find(|a| !taken.contains(*a)) {
+        return alias.clone();
+    }
+    (2..).map(|n| format!("{}-{}", options[0], n)).find(|a| !taken.contains(a)).unwrap()
+}
+
+/// known_hosts（ハッシュ化されていない行）とシェル履歴から、config にない接続先を集める
+pub fn discover(configs: &[SshConfig]) -> io::Result<Vec<Candidate>> {
+    let mut candidates = Vec::new();
+
+    for file in history_files()? {
+        // 履歴ファイルには UTF-8 でないバイトが混ざることがある
+        let content = String::from_utf8_lossy(&fs::read(&file)?).into_owned();
+        for line in content.lines() {
+            for (user, host, port) in history_destinations(line) {
+                candidates_from_history(&mut candidates, user, host, port);
+            }
+        }
+    }
+
+    let known_hosts = home_dir()?.join(".ssh").join("known_hosts");
+    if let Ok(content) = fs::read_to_string(&known_hosts) {
+        for (host, port) in content.lines().filter_map(known_hosts_entry) {
+            if !is_local(&host) {
+                add_candidate(&mut candidates, None, host, port, "known_hosts");
+            }
+        }
+    }
+
+    // config のエイリアスや HostName として登録済みのものは除く
+    candidates.retain(|c| {
+        !configs.iter().any(|config| {
+            config.host.split_whitespace().any(|h| h == c.hostname)
+                || config.hostname.as_deref() == Some(c.hostname.as_str())
+        })
+    });
+    candidates.sort_by(|a, b| b.uses.cmp(&a.uses).then(a.hostname.cmp(&b.hostname)));
+
+    let mut taken: HashSet<String> = configs
+        .iter()
+        .flat_map(|c| c.host.split_whitespace().map(str::to_string))
+        .collect();
+    for candidate in &mut candidates {
+        candidate.alias = propose_alias(candidate, &taken);
+        taken.insert(candidate.alias.clone());
+    }
+    Ok(candidates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Expected<'a> = (Option<&'a str>, &'a str, Option<&'a str>);
+    type ExpectedKnownHost<'a> = Option<(&'a str, Option<&'a str>)>;
+
+    fn destination(user: Option<&str>, host: &str, port: Option<&str>) -> Destination {
+        (user.map(str::to_string), host.to_string(), port.map(str::to_string))
+    }
+
+    #[test]
+    fn parses_ssh_commands_in_history() {
+        let cases: &[(&str, &[Expected])] = &[
+            ("ssh web1.example.com", &[(None, "web1.example.com", None)]),
+            ("ssh user@host -p 2222", &[(Some("user"), "host", Some("2222"))]),
+            ("ssh -p2222 -l admin host uptime", &[(Some("admin"), "host", Some("2222"))]),
+            ("ssh -i ~/.ssh/key -o Port=2200 -o User=ops host", &[(Some("ops"), "host", Some("2200"))]),
+            ("ssh -A -J bastion deploy@10.0.0.5 'tail -f log'", &[(Some("deploy"), "10.0.0.5", None)]),
+            ("ssh ssh://root@db.internal:2022/", &[(Some("root"), "db.internal", Some("2022"))]),
+            ("/usr/bin/ssh -v web", &[(None, "web", None)]),
+            (": 1700000000:0;ssh zsh-host", &[(None, "zsh-host", None)]),
+            ("- cmd: ssh fish-host", &[(None, "fish-host", None)]),
+            ("cd /srv && ssh a; ssh b | cat", &[(None, "a", None), (None, "b", None)]),
+            ("ssh -p", &[]),
+            ("git push origin main", &[]),
+            ("sshr web", &[]),
+        ];
+        for (line, expected) in cases {
+            let expected: Vec<_> = expected.iter().map(|(u, h, p)| destination(*u, h, *p)).collect();
+            assert_eq!(history_destinations(line), expected, "{}", line);
+        }
+    }
+
+    #[test]
+    fn parses_known_hosts_lines() {
+        let cases: &[(&str, ExpectedKnownHost)] = &[
+            ("web1.example.com,10.0.0.1 ssh-ed25519 AAAA", Some(("web1.example.com", None))),
+            ("[git.example.com]:2222 ssh-rsa AAAA", Some(("git.example.com", Some("2222")))),
+            ("[10.0.0.9]:2200,[web9]:2200 ecdsa-sha2-nistp256 AAAA", Some(("10.0.0.9", Some("2200")))),
+            ("*.example.com,web2 ssh-ed25519 AAAA", Some(("web2", None))),
+            ("|1|F1E1KeoE/eEWhi10WpGv4OdiO6Y=|3988QV0VE8wmZL7suNrYQLITLCg= ssh-rsa AAAA", None),
+            ("@cert-authority *.example.com ssh-rsa AAAA", None),
+            ("# comment", None),
+            ("   ", None),
+            ("*.example.com ssh-rsa AAAA", None),
+        ];
+        for (line, expected) in cases {
+            let expected = expected.map(|(h, p)| (h.to_string(), p.map(str::to_string)));
+            assert_eq!(known_hosts_entry(line), expected, "{}", line);
+        }
+    }
+
+    #[test]
+    fn skips_local_and_unresolvable_hosts() {
+        let mut candidates = Vec::new();
+        for host in ["localhost", "127.0.0.1", "$HOST", "`hostname`", "web*", "-x"] {
+            candidates_from_history(&mut candidates, None, host.to_string(), None);
+        }
+        assert!(candidates.is_empty());
+
+        candidates_from_history(&mut candidates, None, "web".to_string(), Some("22".to_string()));
+        candidates_from_history(&mut candidates, Some("deploy".to_string()), "web".to_string(), None);
+        add_candidate(&mut candidates, None, "web".to_string(), None, "known_hosts");
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(candidates[0].user.as_deref(), Some("deploy"));
+        assert_eq!(candidates[0].port, None);
+        assert_eq!(candidates[0].uses, 2);
+        assert_eq!(candidates[0].destination(), "deploy@web");
+    }
+
+    #[test]
+    fn proposes_short_unique_aliases() {
+        let candidate = |hostname: &str, user: Option<&str>| Candidate {
+            alias: String::new(),
+            hostname: hostname.to_string(),
+            user: user.map(str::to_string),
+            port: None,
+            uses: 0,
+            sources: BTreeSet::new(),
+        };
+        let mut taken = HashSet::new();
+        assert_eq!(propose_alias(&candidate("web1.example.com", None), &taken), "web1");
+        assert_eq!(propose_alias(&candidate("10.0.0.1", None), &taken), "host-10-0-0-1");
+        taken.insert("web1".to_string());
+        assert_eq!(propose_alias(&candidate("web1.example.com", None), &taken), "web1-example-com");
+        taken.insert("web1-example-com".to_string());
+        assert_eq!(propose_alias(&candidate("web1.example.com", Some("ops")), &taken), "web1-ops");
+        assert_eq!(propose_alias(&candidate("web1.example.com", None), &taken), "web1-2");
+    }
+}
@@ -0,0 +1,308 @@
+use crate::config::{get_ssh_config_path, parse_ssh_config};
+use crate::utils::home_dir;
+use std::env;
+use std::fs;
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+use std::path::Path;
+use std::process::Command;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Pass,
+    Warn,
+    Fail,
+}
+
+pub struct Check {
+    pub name: &'static str,
+    pub status: Status,
+    pub detail: String,
+    pub hint: Option<String>,
+}
+
+impl Check {
+    fn pass(name: &'static str, detail: String) -> Self {
+        Self { name, status: Status::Pass, detail, hint: None }
+    }
+
+    fn warn(name: &'static str, detail: String, hint: &str) -> Self {
+        Self { name, status: Status::Warn, detail, hint: Some(hint.to_string()) }
+    }
+
+    fn fail(name: &'static str, detail: String, hint: &str) -> Self {
+        Self { name, status: Status::Fail, detail, hint: Some(hint.to_string()) }
+    }
+}
+
+/// コマンドを実行し、stdout と stderr をまとめた最初の行を返す
+fn command_first_line(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    let text = format!(
+        "{}{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    Some(text.lines().next().unwrap_or("").trim().to_string())
+}
+
+fn check_home() -> Check {
+    match home_dir() {
+        Ok(home) if home.is_dir() => Check::pass("HOME", home.display().to_string()),
+        Ok(home) => Check::fail(
+            "HOME",
+            format!("{} is not a directory", home.display()),
+            "set HOME to your home directory",
+        ),
+        Err(_) => Check::fail(
+            "HOME",
+            "HOME environment variable not set".to_string(),
+            "export HOME=/path/to/your/home",
+        ),
+    }
+}
+
+fn check_ssh() -> Check {
+    // ssh -V はバージョンを stderr に出力する
+    match command_first_line("ssh", &["-V"]) {
+        Some(version) => Check::pass("ssh", version),
+        None => Check::fail(
+            "ssh",
+            "ssh command not found".to_string(),
+            "install the OpenSSH client (e.g. apt-get install openssh-client)",
+        ),
+    }
+}
+
+fn check_agent() -> Check {
+    let Some(sock) = env::var_os("SSH_AUTH_SOCK") else {
+        return Check::warn(
+            "ssh-agent",
+            "SSH_AUTH_SOCK is not set".to_string(),
+            "start an agent with: eval \"$(ssh-agent -s)\"",
+        );
+    };
+
+    let is_socket = fs::metadata(&sock)
+        .map(|m| m.file_type().is_socket())
+        .unwrap_or(false);
+    if !is_socket {
+        return Check::fail(
+            "ssh-agent",
+            format!("SSH_AUTH_SOCK {} is not a socket", Path::new(&sock).display()),
+            "the agent has exited; restart it with: eval \"$(ssh-agent -s)\"",
+        );
+    }
+
+    // ssh-add -l の終了コード: 0=鍵あり, 1=鍵なし, 2=接続不可
+    match Command::new("ssh-add").arg("-l").output() {
+        Ok(output) => match output.status.code() {
+            Some(0) => {
+                let keys = String::from_utf8_lossy(&output.stdout).lines().count();
+                Check::pass("ssh-agent", format!("{} key(s) loaded", keys))
+            }
+            Some(1) => Check::warn(
+                "ssh-agent",
+                "agent is running but has no keys loaded".to_string(),
+                "load a key with: ssh-add ~/.ssh/id_ed25519",
+            ),
+            _ => Check::fail(
+                "ssh-agent",
+                "cannot connect to the agent".to_string(),
+                "restart the agent with: eval \"$(ssh-agent -s)\"",
+            ),
+        },
+        Err(_) => Check::warn(
+            "ssh-agent",
+            "ssh-add command not found".to_string(),
+            "install the OpenSSH client tools",
+        ),
+    }
+}
+
+fn check_ssh_dir() -> Check {
+    let Ok(home) = home_dir() else {
+        return Check::fail("~/.ssh", "HOME is not set".to_string(), "fix HOME first");
+    };
+    check_ssh_dir_at(&home.join(".ssh"))
+}
+
+fn check_ssh_dir_at(dir: &Path) -> Check {
+    let Ok(metadata) = fs::metadata(dir) else {
+        return Check::warn(
+            "~/.ssh",
+            format!("{} does not exist", dir.display()),
+            "it will be created by `sshct new`; or run: mkdir -m 700 ~/.ssh",
+        );
+    };
+
+    let mode = metadata.permissions().mode() & 0o777;
+    if mode & 0o022 != 0 {
+        Check::fail(
+            "~/.ssh",
+            format!("{} is writable by group/others (mode {:o})", dir.display(), mode),
+            "run: chmod 700 ~/.ssh",
+        )
+    } else if mode & 0o077 != 0 {
+        Check::warn(
+            "~/.ssh",
+            format!("{} is readable by group/others (mode {:o})", dir.display(), mode),
+            "run: chmod 700 ~/.ssh",
+        )
+    } else {
+        Check::pass("~/.ssh", format!("mode {:o}", mode))
+    }
+}
+
+fn check_config(secret_hosts: usize) -> Check {
+    let Ok(path) = get_ssh_config_path() else {
+        return Check::fail("config", "HOME is not set".to_string(), "fix HOME first");
+    };
+    check_config_at(&path, secret_hosts)
+}
+
+fn check_config_at(path: &Path, secret_hosts: usize) -> Check {
+    let Ok(metadata) = fs::metadata(path) else {
+        return Check::warn(
+            "config",
+            format!("{} does not exist", path.display()),
+            "add a host with: sshct new <name>",
+        );
+    };
+
+    let mode = metadata.permissions().mode() & 0o777;
+    if mode & 0o022 != 0 {
+        Check::fail(
+            "config",
+            format!("{} is writable by group/others (mode {:o}); ssh will refuse it", path.display(), mode),
+            "run: chmod 600 ~/.ssh/config",
+        )
+    } else if mode & 0o044 != 0 && secret_hosts > 0 {
+        Check::fail(
+            "config",
+            format!("{} contains #pass/#passphrase/#totp secrets and is readable by others (mode {:o})", path.display(), mode),
+            "run: chmod 600 ~/.ssh/config",
+        )
+    } else if mode & 0o044 != 0 {
+        Check::warn(
+            "config",
+            format!("{} is readable by others (mode {:o})", path.display(), mode),
+            "run: chmod 600 ~/.ssh/config",
+        )
+    } else {
+        Check::pass("config", format!("mode {:o}", mode))
+    }
+}
+
+fn check_temp_dir() -> Check {
+    let dir = env::temp_dir();
+    let probe = dir.join(format!("ssh_supporter_doctor_{}", std::process::id()));
+    match fs::write(&probe, b"") {
+        Ok(()) => {
+            let _ = fs::remove_file(&probe);
+            Check::pass("temp dir", dir.display().to_string())
+        }
+        Err(e) => Check::fail(
+            "temp dir",
+            format!("cannot write to {}: {}", dir.display(), e),
+            "set TMPDIR to a writable directory",
+        ),
+    }
+}
+
+/// sshr が依存する実行環境を検査する
+pub fn run_checks() -> Vec<Check> {
+    let secret_hosts = parse_ssh_config()
+        .map(|configs| {
+            configs
+                .iter()
+                .filter(|c| c.password.is_some() || c.passphrase.is_some() || c.totp_secret.is_some())
+                .count()
+        })
+        .unwrap_or(0);
+
+    vec![
+        check_home(),
+        check_ssh(),
+        check_agent(),
+        check_ssh_dir(),
+        check_config(secret_hosts),
+        check_temp_dir(),
+    ]
+}
+
+pub fn print_checks(checks: &[Check]) {
+    for check in checks {
+        let label = match check.status {
+            Status::Pass => "PASS",
+            Status::Warn => "WARN",
+            Status::Fail => "FAIL",
+        };
+        println!("[{}] {:<10} {}", label, check.name, check.detail);
+        if let Some(hint) = &check.hint {
+            println!("       {:<10} hint: {}", "", hint);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_mode(path: &Path, mode: u32) -> &Path {
+        fs::set_permissions(path, fs::Permissions::from_mode(mode)).unwrap();
+        path
+    }
+
+    #[test]
+    fn classifies_ssh_dir_permissions() {
+        let home = tempfile::tempdir().unwrap();
+        let dir = home.path().join(".ssh");
+        assert_eq!(check_ssh_dir_at(&dir).status, Status::Warn);
+
+        fs::create_dir(&dir).unwrap();
+        let cases = [
+            (0o700, Status::Pass),
+            (0o750, Status::Warn),
+            (0o755, Status::Warn),
+            (0o770, Status::Fail),
+            (0o777, Status::Fail),
+        ];
+        for (mode, status) in cases {
+            let check = check_ssh_dir_at(with_mode(&dir, mode));
+            assert_eq!(check.status, status, "mode {:o}: {}", mode, check.detail);
+            assert_eq!(check.hint.is_some(), status != Status::Pass);
+        }
+    }
+
+    #[test]
+    fn classifies_config_permissions() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config");
+        let missing = check_config_at(&path, 0);
+        assert_eq!(missing.status, Status::Warn);
+        assert!(missing.detail.contains("does not exist"));
+
+        fs::write(&path, "Host web\n").unwrap();
+        let cases = [
+            (0o600, 0, Status::Pass),
+            (0o600, 2, Status::Pass),
+            (0o644, 0, Status::Warn),
+            (0o640, 1, Status::Fail),
+            (0o644, 1, Status::Fail),
+            (0o620, 0, Status::Fail),
+            (0o666, 0, Status::Fail),
+        ];
+        for (mode, secret_hosts, status) in cases {
+            let check = check_config_at(with_mode(&path, mode), secret_hosts);
+            assert_eq!(check.status, status, "mode {:o}, {} secret hosts: {}", mode, secret_hosts, check.detail);
+        }
+        assert!(check_config_at(with_mode(&path, 0o644), 1).detail.contains("secrets"));
+    }
+
+    #[test]
+    fn reports_missing_commands() {
+        assert_eq!(command_first_line("ssh-supporter-no-such-command", &[]), None);
+        assert_eq!(command_first_line("sh", &["-c", "echo first; echo second"]).as_deref(), Some("first"));
+        assert_eq!(command_first_line("sh", &["-c", "echo on-stderr >&2"]).as_deref(), Some("on-stderr"));
+    }
+}
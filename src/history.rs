@@ -0,0 +1,203 @@
+use crate::utils::data_dir;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+/// sshr による接続 1 回分の記録（history.jsonl に 1 行ずつ保存する）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub alias: String,
+    pub hostname: String,
+    /// 接続開始時刻（UNIX 時刻）
+    pub started: u64,
+    /// 接続していた秒数
+    pub duration: f64,
+    pub exit_status: i32,
+}
+
+/// ホストごとの集計
+#[derive(Debug, Clone)]
+pub struct HostStats {
+    pub alias: String,
+    pub connections: usize,
+    pub failures: usize,
+    pub total_duration: f64,
+    pub last_used: u64,
+}
+
+fn history_path() -> io::Result<PathBuf> {
+    Ok(data_dir()?.join("history.jsonl"))
+}
+
+pub fn append(entry: &Entry) -> io::Result<()> {
+    append_to(&history_path()?, entry)
+}
+
+/// どのホストにいつ接続したかの記録なので、自分だけが読めるように 0700 のディレクトリに 0600 で作る
+fn append_to(path: &Path, entry: &Entry) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::DirBuilder::new().recursive(true).mode(0o700).create(parent)?;
+    }
+    let line = serde_json::to_string(entry).map_err(io::Error::other)?;
+    let mut file = fs::OpenOptions::new().create(true).append(true).mode(0o600).open(path)?;
+    // 以前のバージョンが umask のまま作ったファイルも絞る
+    if file.metadata()?.permissions().mode() & 0o077 != 0 {
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    }
+    writeln!(file, "{}", line)
+}
+
+/// 履歴を古い順に読み込む。壊れた行は読み飛ばす
+pub fn load() -> io::Result<Vec<Entry>> {
+    load_from(&history_path()?)
+}
+
+fn load_from(path: &Path) -> io::Result<Vec<Entry>> {
+    let file = match fs::File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    let mut entries = Vec::new();
+    for line in BufReader::new(file).lines() {
+        if let Ok(entry) = serde_json::from_str(&line?) {
+            entries.push(entry);
+        }
+    }
+    Ok(entries)
+}
+
+/// ホストごとに集計し、接続回数の多い順に並べる
+pub fn host_stats(entries: &[Entry]) -> Vec<HostStats> {
+    let mut by_host: HashMap<&str, HostStats> = HashMap::new();
+    for entry in entries {
+        let stats = by_host.entry(&entry.alias).or_insert_with(|| HostStats {
+            alias: entry.alias.clone(),
+            connections: 0,
+            failures: 0,
+            total_duration: 0.0,
+            last_used: 0,
+        });
+        stats.connections += 1;
+        if entry.exit_status != 0 {
+            stats.failures += 1;
+        }
+        stats.total_duration += entry.duration;
+        stats.last_used = stats.last_used.max(entry.started);
+    }
+
+    let mut stats: Vec<HostStats> = by_host.into_values().collect();
+    stats.sort_by(|a, b| {
+        b.connections
+            .cmp(&a.connections)
+            .then(b.last_used.cmp(&a.last_used))
+            .then(a.alias.cmp(&b.alias))
+    });
+    stats
+}
+
+/// ホストごとの最終接続時刻
+pub fn last_used(entries: &[Entry]) -> HashMap<String, u64> {
+    let mut last = HashMap::new();
+    for entry in entries {
+        let time = last.entry(entry.alias.clone()).or_insert(0);
+        *time = entry.started.max(*time);
+    }
+    last
+}
+
+/// 秒数を `1h02m` のような短い表記にする
+pub fn format_duration(secs: f64) -> String {
+    let secs = secs.round() as u64;
+    if secs < 60 {
+        format!("{}s", secs)
+    } else if secs < 3600 {
+        format!("{}m{:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{}h{:02}m", secs / 3600, secs % 3600 / 60)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(alias: &str, started: u64, duration: f64, exit_status: i32) -> Entry {
+        Entry {
+            alias: alias.to_string(),
+            hostname: format!("{}.example.com", alias),
+            started,
+            duration,
+            exit_status,
+        }
+    }
+
+    #[test]
+    fn creates_private_history_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ssh_supporter").join("history.jsonl");
+        append_to(&path, &entry("web", 100, 1.5, 0)).unwrap();
+        append_to(&path, &entry("db", 200, 2.0, 255)).unwrap();
+
+        let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(&path), 0o600);
+        assert_eq!(mode(path.parent().unwrap()), 0o700);
+        let aliases: Vec<String> = load_from(&path).unwrap().into_iter().map(|e| e.alias).collect();
+        assert_eq!(aliases, ["web", "db"]);
+
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        append_to(&path, &entry("web", 300, 1.0, 0)).unwrap();
+        assert_eq!(mode(&path), 0o600);
+    }
+
+    #[test]
+    fn skips_corrupt_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.jsonl");
+        let good = serde_json::to_string(&entry("web", 100, 1.0, 0)).unwrap();
+        let content = format!("{}\n{{\"alias\":\"trunc\n\nnot json\n{{\"alias\":\"db\"}}\n{}\n", good, good);
+        fs::write(&path, content).unwrap();
+
+        let entries = load_from(&path).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert!(entries.iter().all(|e| e.alias == "web"));
+        assert!(load_from(&dir.path().join("missing.jsonl")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn aggregates_stats_per_host() {
+        let entries = [
+            entry("web", 100, 10.0, 0),
+            entry("db", 500, 3.0, 1),
+            entry("web", 300, 20.0, 255),
+            entry("cache", 400, 1.0, 0),
+            entry("db", 200, 4.0, 0),
+        ];
+        let stats = host_stats(&entries);
+        let summary: Vec<_> = stats
+            .iter()
+            .map(|s| (s.alias.as_str(), s.connections, s.failures, s.total_duration, s.last_used))
+            .collect();
+        assert_eq!(
+            summary,
+            [("db", 2, 1, 7.0, 500), ("web", 2, 1, 30.0, 300), ("cache", 1, 0, 1.0, 400)]
+        );
+
+        let last = last_used(&entries);
+        assert_eq!(last["web"], 300);
+        assert_eq!(last["db"], 500);
+        assert!(host_stats(&[]).is_empty());
+    }
+
+    #[test]
+    fn formats_durations() {
+        assert_eq!(format_duration(0.4), "0s");
+        assert_eq!(format_duration(59.6), "1m00s");
+        assert_eq!(format_duration(125.0), "2m05s");
+        assert_eq!(format_duration(3720.0), "1h02m");
+    }
+}
@@ -0,0 +1,603 @@
+use crate::inventory::{Imported, InventoryHost};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// セッション名を Host に使える名前にする（空白は `-`、使えない文字は取り除く）
+fn alias_from_name(name: &str) -> String {
+    name.trim()
+        .chars()
+        .filter_map(|c| match c {
+            c if c.is_whitespace() => Some('-'),
+            c if c.is_alphanumeric() || "-_.@".contains(c) => Some(c),
+            _ => None,
+        })
+        .collect()
+}
+
+/// `user@host:port` 形式を分解する
+fn split_destination(value: &str) -> (Option<String>, String, Option<String>) {
+    let (user, rest) = match value.rsplit_once('@') {
+        Some((user, rest)) => (Some(user.to_string()).filter(|u| !u.is_empty()), rest),
+        None => (None, value),
+    };
+    // IPv6 アドレスは `[...]:port` の形でなければポートとみなさない
+    let (host, port) = match rest.rsplit_once(':') {
+        Some((host, port)) if !host.contains(':') && port.parse::<u16>().is_ok() => {
+            (host.to_string(), Some(port.to_string()))
+        }
+        Some((host, port)) if host.starts_with('[') && host.ends_with(']') && port.parse::<u16>().is_ok() => {
+            (host[1..host.len() - 1].to_string(), Some(port.to_string()))
+        }
+        _ => (rest.to_string(), None),
+    };
+    (user, host, port)
+}
+
+fn non_default_port(port: Option<String>) -> Option<String> {
+    port.filter(|p| p != "22")
+}
+
+// ---------------------------------------------------------------------------
+// PuTTY（`reg export HKCU\Software\SimonTatham\PuTTY\Sessions` の出力）
+// ---------------------------------------------------------------------------
+
+enum RegValue {
+    Text(String),
+    Dword(u32),
+}
+
+/// .reg ファイルは UTF-16LE（BOM 付き）で書き出されることが多い
+fn decode_reg(bytes: &[u8]) -> io::Result<String> {
+    if let Some(rest) = bytes.strip_prefix(&[0xff, 0xfe]) {
+        let units: Vec<u16> = rest.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect();
+        String::from_utf16(&units).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    } else {
+        let bytes = bytes.strip_prefix(&[0xef, 0xbb, 0xbf]).unwrap_or(bytes);
+        String::from_utf8(bytes.to_vec()).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+            if let Some(byte) = hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                out.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// `"Key"="value"` または `"Key"=dword:0000001a` の行を読む
+fn parse_reg_value(line: &str) -> Option<(String, RegValue)> {
+    let rest = line.strip_prefix('"')?;
+    let (key, value) = rest.split_once("\"=")?;
+    if let Some(hex) = value.strip_prefix("dword:") {
+        return Some((key.to_string(), RegValue::Dword(u32::from_str_radix(hex.trim(), 16).ok()?)));
+    }
+    let quoted = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+    let mut text = String::new();
+    let mut chars = quoted.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            text.extend(chars.next());
+        } else {
+            text.push(c);
+        }
+    }
+    Some((key.to_string(), RegValue::Text(text)))
+}
+
+pub fn read_putty_reg(path: &Path) -> io::Result<Imported> {
+    let content = decode_reg(&fs::read(path)?)?;
+    let mut sessions: Vec<(String, HashMap<String, RegValue>)> = Vec::new();
+    for line in content.lines() {
+        let line = line.trim();
+        if let Some(section) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            if let Some((_, name)) = section.split_once("\\Sessions\\") {
+                sessions.push((percent_decode(name), HashMap::new()));
+            }
+        } else if let (Some((_, values)), Some((key, value))) = (sessions.last_mut(), parse_reg_value(line)) {
+            values.insert(key, value);
+        }
+    }
+
+    let mut imported = Imported::default();
+    for (name, values) in sessions {
+        if name == "Default Settings" {
+            continue;
+        }
+        let text = |key: &str| match values.get(key) {
+            Some(RegValue::Text(t)) if !t.is_empty() => Some(t.clone()),
+            _ => None,
+        };
+        let dword = |key: &str| match values.get(key) {
+            Some(RegValue::Dword(n)) => Some(*n),
+            _ => None,
+        };
+
+        let protocol = text("Protocol").unwrap_or_else(|| "ssh".to_string());
+        if protocol != "ssh" {
+            imported.notes.push(format!("'{}': skipped ({} session)", name, protocol));
+            continue;
+        }
+        let Some(destination) = text("HostName") else {
+            imported.notes.push(format!("'{}': skipped (no HostName)", name));
+            continue;
+        };
+
+        let (user, hostname, _) = split_destination(&destination);
+        let mut host = InventoryHost {
+            alias: alias_from_name(&name),
+            hostname: Some(hostname),
+            user: text("UserName").or(user),
+            port: non_default_port(dword("PortNumber").map(|p| p.to_string())),
+            ..Default::default()
+        };
+        let mut unmapped = Vec::new();
+
+        if let Some(key_file) = text("PublicKeyFile") {
+            if key_file.to_lowercase().ends_with(".ppk") {
+                unmapped.push(format!(
+                    "PublicKeyFile {} is a PuTTY key (convert it with `puttygen <key>.ppk -O private-openssh -o <key>`)",
+                    key_file
+                ));
+            } else {
+                host.identity_files.push(key_file);
+            }
+        }
+
+        // ProxyMethod: 1=SOCKS4 2=SOCKS5 3=HTTP 4=Telnet 5=ローカルコマンド 6=SSH
+        let proxy_host = text("ProxyHost");
+        let proxy_port = dword("ProxyPort").unwrap_or(0);
+        match (dword("ProxyMethod").unwrap_or(0), &proxy_host) {
+            (0, _) => {}
+            (6, Some(proxy)) => {
+                let user = text("ProxyUsername").map(|u| format!("{}@", u)).unwrap_or_default();
+                let port = if proxy_port == 0 || proxy_port == 22 { String::new() } else { format!(":{}", proxy_port) };
+                host.proxy_jump = Some(format!("{}{}{}", user, proxy, port));
+            }
+            (method @ 1..=3, Some(proxy)) => {
+                let kind = match method {
+                    1 => "4",
+                    2 => "5",
+                    _ => "connect",
+                };
+                host.proxy_command = Some(format!("nc -X {} -x {}:{} %h %p", kind, proxy, proxy_port));
+                if text("ProxyUsername").is_some() {
+                    unmapped.push("ProxyUsername (proxy authentication)".to_string());
+                }
+            }
+            (5, _) => match text("ProxyTelnetCommand") {
+                Some(command) => {
+                    let command = command.replace("%host", "%h").replace("%port", "%p");
+                    host.proxy_command = Some(command.trim_end_matches("\\n").trim().to_string());
+                }
+                None => unmapped.push("ProxyMethod (local command without ProxyTelnetCommand)".to_string()),
+            },
+            (method, _) => unmapped.push(format!("ProxyMethod {}", method)),
+        }
+
+        for key in ["PortForwardings", "RemoteCommand"] {
+            if text(key).is_some() {
+                unmapped.push(key.to_string());
+            }
+        }
+        for key in ["AgentFwd", "X11Forward", "Compression"] {
+            if dword(key).unwrap_or(0) != 0 {
+                unmapped.push(key.to_string());
+            }
+        }
+        if !unmapped.is_empty() {
+            imported.notes.push(format!("'{}': not mapped: {}", host.alias, unmapped.join(", ")));
+        }
+        imported.hosts.push(host);
+    }
+    Ok(imported)
+}
+
+// ---------------------------------------------------------------------------
+// Remmina（`~/.local/share/remmina/*.remmina`）
+// ---------------------------------------------------------------------------
+
+fn parse_remmina(content: &str) -> HashMap<String, String> {
+    let mut values = HashMap::new();
+    let mut in_section = false;
+    for line in content.lines() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            in_section = line == "[remmina]";
+        } else if let (true, Some((key, value))) = (in_section, line.split_once('=')) {
+            if !value.trim().is_empty() {
+                values.insert(key.trim().to_string(), value.trim().to_string());
+            }
+        }
+    }
+    values
+}
+
+fn remmina_host(file_name: &str, values: &HashMap<String, String>, imported: &mut Imported) {
+    let name = values.get("name").map(String::as_str).unwrap_or(file_name);
+    let protocol = values.get("protocol").map(String::as_str).unwrap_or("");
+    if !protocol.eq_ignore_ascii_case("ssh") && !protocol.eq_ignore_ascii_case("sftp") {
+        imported.notes.push(format!("'{}': skipped ({} connection)", name, protocol));
+        return;
+    }
+    let Some(server) = values.get("server") else {
+        imported.notes.push(format!("'{}': skipped (no server)", name));
+        return;
+    };
+
+    let (user, hostname, port) = split_destination(server);
+    let mut host = InventoryHost {
+        alias: alias_from_name(name),
+        hostname: Some(hostname),
+        user: values.get("ssh_username").or(values.get("username")).cloned().or(user),
+        port: non_default_port(port),
+        identity_files: values.get("ssh_privatekey").cloned().into_iter().collect(),
+        proxy_command: values.get("ssh_proxycommand").cloned(),
+        ..Default::default()
+    };
+    if let Some(group) = values.get("group") {
+        host.tags = vec![alias_from_name(group)];
+    }
+
+    let mut unmapped = Vec::new();
+    if values.get("ssh_tunnel_enabled").is_some_and(|v| v == "1") {
+        match values.get("ssh_tunnel_server") {
+            Some(server) => {
+                let user = values.get("ssh_tunnel_username").map(|u| format!("{}@", u)).unwrap_or_default();
+                host.proxy_jump = Some(format!("{}{}", user, server));
+            }
+            None => unmapped.push("ssh_tunnel_enabled (no tunnel server)".to_string()),
+        }
+    }
+    // Remmina のパスワードは Remmina の鍵で暗号化されているので取り込めない
+    if values.contains_key("password") || values.contains_key("ssh_password") {
+        unmapped.push("password (stored encrypted by Remmina; set it with sshct edit)".to_string());
+    }
+    for key in ["exec", "ssh_stricthostkeycheck", "ssh_compression"] {
+        if values.get(key).is_some_and(|v| v != "0") {
+            unmapped.push(key.to_string());
+        }
+    }
+    if !unmapped.is_empty() {
+        imported.notes.push(format!("'{}': not mapped: {}", host.alias, unmapped.join(", ")));
+    }
+    imported.hosts.push(host);
+}
+
+/// .remmina ファイル、または .remmina ファイルを含むディレクトリを読み込む
+pub fn read_remmina(path: &Path) -> io::Result<Imported> {
+    let mut files = Vec::new();
+    if path.is_dir() {
+        for entry in fs::read_dir(path)? {
+            let file = entry?.path();
+            if file.extension().is_some_and(|e| e == "remmina") {
+                files.push(file);
+            }
+        }
+        files.sort();
+    } else {
+        files.push(path.to_path_buf());
+    }
+
+    let mut imported = Imported::default();
+    for file in files {
+        let values = parse_remmina(&fs::read_to_string(&file)?);
+        let file_name = file.file_stem().and_then(|s| s.to_str()).unwrap_or_default();
+        remmina_host(file_name, &values, &mut imported);
+    }
+    Ok(imported)
+}
+
+// ---------------------------------------------------------------------------
+// Termius（JSON エクスポート）
+// ---------------------------------------------------------------------------
+
+fn json_str(value: &serde_json::Value, key: &str) -> Option<String> {
+    match value.get(key)? {
+        serde_json::Value::String(s) if !s.is_empty() => Some(s.clone()),
+        serde_json::Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+/// グループやタグは文字列か `{"label": ...}` のどちらでも書かれる
+fn json_label(value: &serde_json::Value) -> Option<String> {
+    match value {
+        serde_json::Value::String(s) if !s.is_empty() => Some(s.clone()),
+        serde_json::Value::Object(_) => json_str(value, "label"),
+        _ => None,
+    }
+}
+
+pub fn read_termius(path: &Path) -> io::Result<Imported> {
+    let content = fs::read_to_string(path)?;
+    let document: serde_json::Value =
+        serde_json::from_str(&content).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let entries = match document.get("hosts").unwrap_or(&document) {
+        serde_json::Value::Array(entries) => entries.clone(),
+        _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "no hosts array found")),
+    };
+
+    let mut imported = Imported::default();
+    for entry in &entries {
+        let label = json_str(entry, "label");
+        let Some(address) = json_str(entry, "address") else {
+            imported.notes.push(format!("'{}': skipped (no address)", label.unwrap_or_default()));
+            continue;
+        };
+        let name = label.unwrap_or_else(|| address.clone());
+
+        // 接続設定は ssh_config の下にまとめられていることもある
+        let ssh = entry.get("ssh_config").unwrap_or(entry);
+        let identity = ssh.get("identity").unwrap_or(ssh);
+        let mut host = InventoryHost {
+            alias: alias_from_name(&name),
+            hostname: Some(address),
+            user: json_str(identity, "username"),
+            port: non_default_port(json_str(ssh, "port")),
+            password_ref: json_str(identity, "password"),
+            ..Default::default()
+        };
+
+        let mut tags: Vec<String> = entry
+            .get("tags")
+            .and_then(|t| t.as_array())
+            .map(|tags| tags.iter().filter_map(json_label).collect())
+            .unwrap_or_default();
+        if let Some(group) = entry.get("group").and_then(json_label) {
+            tags.insert(0, group);
+        }
+        host.tags = tags.iter().map(|t| alias_from_name(t)).filter(|t| !t.is_empty()).collect();
+
+        let mut unmapped = Vec::new();
+        match identity.get("ssh_key") {
+            Some(key) if key.is_object() => match json_str(key, "path") {
+                Some(path) => host.identity_files.push(path),
+                None => unmapped.push(format!(
+                    "ssh_key '{}' (stored in Termius; save it to a file and set IdentityFile)",
+                    json_str(key, "label").unwrap_or_default()
+                )),
+            },
+            _ => {}
+        }
+        // 踏み台は host_chain に順に並んでいる
+        if let Some(chain) = ssh.get("host_chain").and_then(|c| c.as_array()) {
+            let hops: Vec<String> = chain
+                .iter()
+                .filter_map(|hop| json_str(hop, "address").or_else(|| json_label(hop)))
+                .collect();
+            if !hops.is_empty() {
+                host.proxy_jump = Some(hops.join(","));
+            }
+        }
+        for key in ["startup_snippet", "port_forwarding", "proxy"] {
+            if ssh.get(key).is_some_and(|v| !v.is_null()) {
+                unmapped.push(key.to_string());
+            }
+        }
+        if !unmapped.is_empty() {
+            imported.notes.push(format!("'{}': not mapped: {}", host.alias, unmapped.join(", ")));
+        }
+        imported.hosts.push(host);
+    }
+    Ok(imported)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write(dir: &TempDir, name: &str, bytes: &[u8]) -> std::path::PathBuf {
+        let path = dir.path().join(name);
+        fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    /// `reg export` と同じく BOM 付き UTF-16LE にする
+    fn utf16le(text: &str) -> Vec<u8> {
+        let mut bytes = vec![0xff, 0xfe];
+        for unit in text.encode_utf16() {
+            bytes.extend_from_slice(&unit.to_le_bytes());
+        }
+        bytes
+    }
+
+    const PUTTY_REG: &str = r#"Windows Registry Editor Version 5.00
+
+[HKEY_CURRENT_USER\Software\SimonTatham\PuTTY\Sessions\Default%20Settings]
+"HostName"=""
+
+[HKEY_CURRENT_USER\Software\SimonTatham\PuTTY\Sessions\web%20prod]
+"HostName"="admin@web.example.com"
+"Protocol"="ssh"
+"PortNumber"=dword:000008ae
+"UserName"="deploy"
+"PublicKeyFile"="/home/me/.ssh/id_ed25519"
+"ProxyMethod"=dword:00000006
+"ProxyHost"="bastion.example.com"
+"ProxyPort"=dword:00000898
+"ProxyUsername"="jump"
+"AgentFwd"=dword:00000001
+
+[HKEY_CURRENT_USER\Software\SimonTatham\PuTTY\Sessions\db]
+"HostName"="db.example.com"
+"Protocol"="ssh"
+"PortNumber"=dword:00000016
+"PublicKeyFile"="C:\\keys\\db.ppk"
+"ProxyMethod"=dword:00000002
+"ProxyHost"="socks.local"
+"ProxyPort"=dword:00000438
+
+[HKEY_CURRENT_USER\Software\SimonTatham\PuTTY\Sessions\router]
+"HostName"="192.168.0.1"
+"Protocol"="telnet"
+"#;
+
+    #[test]
+    fn reads_utf16_putty_registry_export() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write(&dir, "putty.reg", &utf16le(&PUTTY_REG.replace('\n', "\r\n")));
+        let imported = read_putty_reg(&path).unwrap();
+
+        assert_eq!(imported.hosts.len(), 2);
+        let web = &imported.hosts[0];
+        assert_eq!(web.alias, "web-prod");
+        assert_eq!(web.hostname.as_deref(), Some("web.example.com"));
+        assert_eq!(web.port.as_deref(), Some("2222"));
+        // UserName は HostName の user@ より優先する
+        assert_eq!(web.user.as_deref(), Some("deploy"));
+        assert_eq!(web.identity_files, ["/home/me/.ssh/id_ed25519"]);
+        assert_eq!(web.proxy_jump.as_deref(), Some("jump@bastion.example.com:2200"));
+        assert!(web.proxy_command.is_none());
+
+        let db = &imported.hosts[1];
+        assert_eq!(db.alias, "db");
+        assert_eq!(db.port, None);
+        assert!(db.identity_files.is_empty());
+        assert_eq!(db.proxy_command.as_deref(), Some("nc -X 5 -x socks.local:1080 %h %p"));
+
+        assert_eq!(imported.notes.len(), 3);
+        assert_eq!(imported.notes[0], "'web-prod': not mapped: AgentFwd");
+        assert!(imported.notes[1].starts_with(r"'db': not mapped: PublicKeyFile C:\keys\db.ppk is a PuTTY key"));
+        assert_eq!(imported.notes[2], "'router': skipped (telnet session)");
+    }
+
+    #[test]
+    fn reads_utf8_putty_registry_export() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write(&dir, "putty.reg", PUTTY_REG.as_bytes());
+        let imported = read_putty_reg(&path).unwrap();
+        assert_eq!(imported.hosts[0].alias, "web-prod");
+    }
+
+    #[test]
+    fn decodes_percent_escapes() {
+        assert_eq!(percent_decode("web%20prod"), "web prod");
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%E6%9C%AC%E7%95%AA"), "本番");
+    }
+
+    #[test]
+    fn reads_remmina_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        write(
+            &dir,
+            "app.remmina",
+            b"[remmina]\n\
+              name=App Server\n\
+              protocol=SSH\n\
+              server=deploy@app.example.com:2022\n\
+              username=\n\
+              ssh_privatekey=/home/me/.ssh/app\n\
+              group=Web\n\
+              ssh_tunnel_enabled=1\n\
+              ssh_tunnel_server=bastion.example.com:22\n\
+              ssh_tunnel_username=jump\n\
+              password=c2VjcmV0\n\
+              exec=uptime\n",
+        );
+        write(&dir, "desktop.remmina", b"[remmina]\nname=Desktop\nprotocol=RDP\nserver=10.0.0.9\n");
+        write(&dir, "notes.txt", b"ignored");
+        let imported = read_remmina(dir.path()).unwrap();
+
+        assert_eq!(imported.hosts.len(), 1);
+        let app = &imported.hosts[0];
+        assert_eq!(app.alias, "App-Server");
+        assert_eq!(app.hostname.as_deref(), Some("app.example.com"));
+        assert_eq!(app.port.as_deref(), Some("2022"));
+        assert_eq!(app.user.as_deref(), Some("deploy"));
+        assert_eq!(app.identity_files, ["/home/me/.ssh/app"]);
+        assert_eq!(app.proxy_jump.as_deref(), Some("jump@bastion.example.com:22"));
+        assert_eq!(app.tags, ["Web"]);
+        assert!(app.password_ref.is_none());
+
+        assert_eq!(
+            imported.notes,
+            [
+                "'App-Server': not mapped: password (stored encrypted by Remmina; set it with sshct edit), exec",
+                "'Desktop': skipped (RDP connection)",
+            ]
+        );
+    }
+
+    const TERMIUS_JSON: &str = r#"{
+      "hosts": [
+        {
+          "label": "api prod",
+          "address": "10.0.0.5",
+          "group": {"label": "prod"},
+          "tags": ["api", {"label": "critical"}],
+          "ssh_config": {
+            "port": 2222,
+            "identity": {"username": "ubuntu", "password": "hunter2", "ssh_key": {"label": "laptop"}},
+            "host_chain": [{"address": "bastion.example.com"}, {"label": "jump2"}],
+            "startup_snippet": {"script": "uptime"}
+          }
+        },
+        {
+          "label": "worker",
+          "address": "worker.example.com",
+          "ssh_config": {
+            "port": 22,
+            "identity": {"username": "ops", "ssh_key": {"label": "ops", "path": "~/.ssh/ops"}}
+          }
+        },
+        {"label": "broken"}
+      ]
+    }"#;
+
+    #[test]
+    fn reads_termius_export() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write(&dir, "termius.json", TERMIUS_JSON.as_bytes());
+        let imported = read_termius(&path).unwrap();
+
+        assert_eq!(imported.hosts.len(), 2);
+        let api = &imported.hosts[0];
+        assert_eq!(api.alias, "api-prod");
+        assert_eq!(api.hostname.as_deref(), Some("10.0.0.5"));
+        assert_eq!(api.port.as_deref(), Some("2222"));
+        assert_eq!(api.user.as_deref(), Some("ubuntu"));
+        assert_eq!(api.password_ref.as_deref(), Some("hunter2"));
+        assert_eq!(api.tags, ["prod", "api", "critical"]);
+        assert_eq!(api.proxy_jump.as_deref(), Some("bastion.example.com,jump2"));
+        assert!(api.identity_files.is_empty());
+
+        let worker = &imported.hosts[1];
+        assert_eq!(worker.port, None);
+        assert_eq!(worker.identity_files, ["~/.ssh/ops"]);
+        assert!(worker.proxy_jump.is_none());
+
+        assert_eq!(
+            imported.notes,
+            [
+                "'api-prod': not mapped: ssh_key 'laptop' (stored in Termius; save it to a file and set IdentityFile), startup_snippet",
+                "'broken': skipped (no address)",
+            ]
+        );
+    }
+
+    #[test]
+    fn splits_destinations() {
+        assert_eq!(
+            split_destination("root@host:2200"),
+            (Some("root".to_string()), "host".to_string(), Some("2200".to_string()))
+        );
+        assert_eq!(split_destination("fe80::1"), (None, "fe80::1".to_string(), None));
+        assert_eq!(split_destination("[fe80::1]:22"), (None, "fe80::1".to_string(), Some("22".to_string())));
+    }
+}
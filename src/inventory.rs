@@ -0,0 +1,651 @@
+use crate::config::{parse_tags, SshConfig};
+use crate::importers;
+use crate::utils::expand_tilde;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Csv,
+    Json,
+    Yaml,
+    Putty,
+    Remmina,
+    Termius,
+}
+
+impl Format {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "csv" => Some(Self::Csv),
+            "json" => Some(Self::Json),
+            "yaml" | "yml" => Some(Self::Yaml),
+            "putty" | "reg" => Some(Self::Putty),
+            "remmina" => Some(Self::Remmina),
+            "termius" => Some(Self::Termius),
+            _ => None,
+        }
+    }
+
+    /// 拡張子から形式を判定する。ディレクトリは Remmina のデータディレクトリとみなす
+    pub fn from_path(path: &Path) -> Option<Self> {
+        if path.is_dir() {
+            return Some(Self::Remmina);
+        }
+        Self::parse(path.extension()?.to_str()?)
+    }
+}
+
+/// `sshct export` の出力形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Json,
+    Csv,
+    Yaml,
+    AnsibleIni,
+    AnsibleYaml,
+}
+
+impl ExportFormat {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "json" => Some(Self::Json),
+            "csv" => Some(Self::Csv),
+            "yaml" | "yml" => Some(Self::Yaml),
+            "ansible-ini" => Some(Self::AnsibleIni),
+            "ansible-yaml" => Some(Self::AnsibleYaml),
+            _ => None,
+        }
+    }
+}
+
+/// CSV では数値や文字列、JSON/YAML ではリストとしても書ける値
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Flexible {
+    Text(String),
+    Number(u64),
+    List(Vec<String>),
+}
+
+fn flexible_string<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    Ok(match Option::<Flexible>::deserialize(deserializer)? {
+        Some(Flexible::Text(text)) => Some(text.trim().to_string()).filter(|t| !t.is_empty()),
+        Some(Flexible::Number(n)) => Some(n.to_string()),
+        Some(Flexible::List(items)) => Some(items.join(",")),
+        None => None,
+    })
+}
+
+fn flexible_list<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    Ok(match Option::<Flexible>::deserialize(deserializer)? {
+        // CSV では `web,prod` や `web;prod` のように 1 セルにまとめて書く
+        Some(Flexible::Text(text)) => parse_tags(&text.replace(';', ",")),
+        Some(Flexible::Number(n)) => vec![n.to_string()],
+        Some(Flexible::List(items)) => items.into_iter().filter(|t| !t.is_empty()).collect(),
+        None => Vec::new(),
+    })
+}
+
+/// インベントリの 1 ホスト分。列名・キー名の表記ゆれは alias で吸収する
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct InventoryHost {
+    #[serde(alias = "host", alias = "name")]
+    pub alias: String,
+    #[serde(default, alias = "host_name", alias = "address", alias = "ip", deserialize_with = "flexible_string")]
+    pub hostname: Option<String>,
+    #[serde(default, alias = "username", deserialize_with = "flexible_string")]
+    pub user: Option<String>,
+    #[serde(default, deserialize_with = "flexible_string")]
+    pub port: Option<String>,
+    /// CSV では `~/.ssh/a;~/.ssh/b` のように 1 セルにまとめて書く
+    #[serde(
+        default,
+        rename = "identity_file",
+        alias = "identity_files",
+        alias = "identity",
+        alias = "key",
+        deserialize_with = "flexible_list"
+    )]
+    pub identity_files: Vec<String>,
+    #[serde(default, alias = "jump", alias = "bastion", deserialize_with = "flexible_string")]
+    pub proxy_jump: Option<String>,
+    #[serde(default, deserialize_with = "flexible_string")]
+    pub proxy_command: Option<String>,
+    #[serde(default, deserialize_with = "flexible_list")]
+    pub tags: Vec<String>,
+    /// `env:<変数名>`、`file:<パス>`、またはパスワードそのもの
+    #[serde(default, alias = "password", alias = "password_reference", deserialize_with = "flexible_string")]
+    pub password_ref: Option<String>,
+}
+
+/// JSON/YAML は配列か、`hosts` キーの下の配列を受け付ける
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Document {
+    List(Vec<InventoryHost>),
+    Wrapped { hosts: Vec<InventoryHost> },
+}
+
+fn invalid_data(e: impl std::fmt::Display) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+}
+
+/// 読み込んだホストと、取り込めなかった設定についての注意書き
+#[derive(Debug, Default)]
+pub struct Imported {
+    pub hosts: Vec<InventoryHost>,
+    pub notes: Vec<String>,
+}
+
+/// インベントリファイル（または他のクライアントのエクスポート）を読み込む
+pub fn read_inventory(path: &Path, format: Format) -> io::Result<Imported> {
+    let document = match format {
+        Format::Csv => {
+            let content = fs::read_to_string(path)?;
+            let mut reader = csv::ReaderBuilder::new()
+                .trim(csv::Trim::All)
+                .from_reader(content.as_bytes());
+            let mut hosts = Vec::new();
+            for (index, row) in reader.deserialize().enumerate() {
+                // ヘッダーが 1 行目なので、データ行は 2 行目から
+                let host: InventoryHost = row.map_err(|e| invalid_data(format!("row {}: {}", index + 2, e)))?;
+                hosts.push(host);
+            }
+            Document::List(hosts)
+        }
+        Format::Json => serde_json::from_str(&fs::read_to_string(path)?).map_err(invalid_data)?,
+        Format::Yaml => serde_yaml::from_str(&fs::read_to_string(path)?).map_err(invalid_data)?,
+        Format::Putty => return importers::read_putty_reg(path),
+        Format::Remmina => return importers::read_remmina(path),
+        Format::Termius => return importers::read_termius(path),
+    };
+
+    let hosts = match document {
+        Document::List(hosts) | Document::Wrapped { hosts } => hosts,
+    };
+    Ok(Imported { hosts, notes: Vec::new() })
+}
+
+/// パスワードの参照を解決する
+fn resolve_password(reference: &str) -> Result<String, String> {
+    if let Some(name) = reference.strip_prefix("env:") {
+        env::var(name).map_err(|_| format!("environment variable {} is not set", name))
+    } else if let Some(path) = reference.strip_prefix("file:") {
+        let path = expand_tilde(path).map_err(|e| e.to_string())?;
+        fs::read_to_string(&path)
+            .map(|content| content.trim_end_matches(['\r', '\n']).to_string())
+            .map_err(|e| format!("cannot read {}: {}", path.display(), e))
+    } else {
+        Ok(reference.to_string())
+    }
+}
+
+impl InventoryHost {
+    /// SshConfig に変換する。値が不正な場合は理由を返す
+    pub fn to_ssh_config(&self) -> Result<SshConfig, String> {
+        let alias = self.alias.trim();
+        if alias.is_empty() || alias.contains(char::is_whitespace) {
+            return Err(format!("invalid alias '{}'", self.alias));
+        }
+        if self.hostname.is_none() {
+            return Err("hostname is missing".to_string());
+        }
+        if let Some(port) = &self.port {
+            if port.parse::<u16>().is_err() {
+                return Err(format!("invalid port '{}'", port));
+            }
+        }
+
+        let mut config = SshConfig::new(alias.to_string());
+        config.hostname = self.hostname.clone();
+        config.user = self.user.clone();
+        config.port = self.port.clone();
+        config.identity_files = self.identity_files.clone();
+        config.proxy_jump = self.proxy_jump.clone();
+        config.proxy_command = self.proxy_command.clone();
+        config.tags = self.tags.clone();
+        if let Some(reference) = &self.password_ref {
+            config.password = Some(resolve_password(reference)?);
+        }
+        Ok(config)
+    }
+}
+
+/// 取り込む前に表示する一覧。秘密情報は伏せる
+pub fn import_preview(additions: &[SshConfig], overwrites: &[SshConfig]) -> String {
+    let mut preview = String::new();
+    for (label, configs) in [("New", additions), ("Overwrite", overwrites)] {
+        for config in configs {
+            preview.push_str(&format!("\n# {}: {}\n", label, config.host));
+            preview.push_str(&config.to_config_string_redacted());
+        }
+    }
+    preview
+}
+
+/// 既存のホストをインベントリの値で上書きする。
+/// インベントリに書かれていない項目（#pass や #tags、#env や #rule など）は既存の設定を残す
+pub fn overwrite_existing(existing: &SshConfig, imported: SshConfig) -> SshConfig {
+    let mut merged = existing.clone();
+    if imported.hostname.is_some() {
+        merged.hostname = imported.hostname;
+    }
+    if imported.user.is_some() {
+        merged.user = imported.user;
+    }
+    if imported.port.is_some() {
+        merged.port = imported.port;
+    }
+    if !imported.identity_files.is_empty() {
+        merged.identity_files = imported.identity_files;
+    }
+    if imported.proxy_jump.is_some() {
+        merged.proxy_jump = imported.proxy_jump;
+    }
+    if imported.proxy_command.is_some() {
+        merged.proxy_command = imported.proxy_command;
+    }
+    if !imported.tags.is_empty() {
+        merged.tags = imported.tags;
+    }
+    if imported.password.is_some() {
+        merged.password = imported.password;
+    }
+    merged
+}
+
+/// 書き出す 1 ホスト分。キー名は import で読み戻せるものにそろえる
+#[derive(Serialize)]
+struct ExportHost<'a> {
+    alias: &'a str,
+    hostname: Option<&'a str>,
+    user: Option<&'a str>,
+    port: Option<u16>,
+    identity_files: &'a [String],
+    proxy_jump: Option<&'a str>,
+    proxy_command: Option<&'a str>,
+    tags: &'a [String],
+    environment: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    password: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    passphrase: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    totp_secret: Option<&'a str>,
+}
+
+impl<'a> ExportHost<'a> {
+    fn new(config: &'a SshConfig, include_secrets: bool) -> Self {
+        let secret = |value: &'a Option<String>| value.as_deref().filter(|_| include_secrets);
+        Self {
+            alias: &config.host,
+            hostname: config.hostname.as_deref(),
+            user: config.user.as_deref(),
+            port: config.port.as_deref().and_then(|p| p.parse().ok()),
+            identity_files: &config.identity_files,
+            proxy_jump: config.proxy_jump.as_deref(),
+            proxy_command: config.proxy_command.as_deref(),
+            tags: &config.tags,
+            environment: config.environment.as_ref().map(|e| e.name.as_str()),
+            password: secret(&config.password),
+            passphrase: secret(&config.passphrase),
+            totp_secret: secret(&config.totp_secret),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ExportDocument<'a> {
+    hosts: Vec<ExportHost<'a>>,
+}
+
+/// ホスト一覧を指定の形式の文字列にする
+pub fn export_hosts(configs: &[SshConfig], format: ExportFormat, include_secrets: bool) -> io::Result<String> {
+    let hosts: Vec<ExportHost> = configs.iter().map(|c| ExportHost::new(c, include_secrets)).collect();
+    match format {
+        ExportFormat::Json => {
+            let mut json = serde_json::to_string_pretty(&ExportDocument { hosts }).map_err(io::Error::other)?;
+            json.push('\n');
+            Ok(json)
+        }
+        ExportFormat::Yaml => serde_yaml::to_string(&ExportDocument { hosts }).map_err(io::Error::other),
+        ExportFormat::Csv => export_csv(&hosts, include_secrets),
+        ExportFormat::AnsibleIni => Ok(export_ansible_ini(configs, include_secrets)),
+        ExportFormat::AnsibleYaml => serde_yaml::to_string(&ansible_inventory(configs, include_secrets)).map_err(io::Error::other),
+    }
+}
+
+fn export_csv(hosts: &[ExportHost], include_secrets: bool) -> io::Result<String> {
+    let mut headers = vec![
+        "alias", "hostname", "user", "port", "identity_files", "proxy_jump", "proxy_command", "tags", "environment",
+    ];
+    if include_secrets {
+        headers.extend(["password", "passphrase", "totp_secret"]);
+    }
+
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record(&headers)?;
+    for host in hosts {
+        let text = |value: Option<&str>| value.unwrap_or_default().to_string();
+        let mut record = vec![
+            host.alias.to_string(),
+            text(host.hostname),
+            text(host.user),
+            host.port.map(|p| p.to_string()).unwrap_or_default(),
+            host.identity_files.join(";"),
+            text(host.proxy_jump),
+            text(host.proxy_command),
+            host.tags.join(","),
+            text(host.environment),
+        ];
+        if include_secrets {
+            record.extend([text(host.password), text(host.passphrase), text(host.totp_secret)]);
+        }
+        writer.write_record(&record)?;
+    }
+    let bytes = writer.into_inner().map_err(|e| io::Error::other(e.to_string()))?;
+    String::from_utf8(bytes).map_err(invalid_data)
+}
+
+/// Ansible のグループ名に使えない文字を `_` に置き換える
+fn ansible_group_name(tag: &str) -> String {
+    tag.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' { c } else { '_' })
+        .collect()
+}
+
+/// ホストごとの Ansible 変数。ProxyJump は ssh の引数として渡す
+fn ansible_host_vars(config: &SshConfig, include_secrets: bool) -> Vec<(&'static str, String)> {
+    let mut vars = Vec::new();
+    if let Some(hostname) = &config.hostname {
+        vars.push(("ansible_host", hostname.clone()));
+    }
+    if let Some(user) = &config.user {
+        vars.push(("ansible_user", user.clone()));
+    }
+    if let Some(port) = config.port.as_deref().and_then(|p| p.parse::<u16>().ok()) {
+        vars.push(("ansible_port", port.to_string()));
+    }
+    // Ansible に渡せる鍵は 1 つだけなので、最初に試される鍵を使う
+    if let Some(identity_file) = config.identity_file() {
+        vars.push(("ansible_ssh_private_key_file", identity_file.to_string()));
+    }
+    if let Some(proxy_jump) = &config.proxy_jump {
+        vars.push(("ansible_ssh_common_args", format!("-o ProxyJump={}", proxy_jump)));
+    } else if let Some(proxy_command) = &config.proxy_command {
+        vars.push(("ansible_ssh_common_args", format!("-o ProxyCommand=\"{}\"", proxy_command)));
+    }
+    if include_secrets {
+        if let Some(password) = &config.password {
+            vars.push(("ansible_password", password.clone()));
+        }
+    }
+    vars
+}
+
+/// タグごとのグループに所属するホスト名（グループ名順）
+fn ansible_groups(configs: &[SshConfig]) -> BTreeMap<String, Vec<&str>> {
+    let mut groups: BTreeMap<String, Vec<&str>> = BTreeMap::new();
+    for config in configs {
+        for tag in &config.tags {
+            groups.entry(ansible_group_name(tag)).or_default().push(&config.host);
+        }
+    }
+    groups
+}
+
+fn export_ansible_ini(configs: &[SshConfig], include_secrets: bool) -> String {
+    // INI の値は空白で区切られるので、空白を含む値はクォートする
+    let quote = |value: &str| {
+        if value.contains(|c: char| c.is_whitespace() || "'\"\\".contains(c)) {
+            format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+        } else {
+            value.to_string()
+        }
+    };
+
+    let mut out = String::new();
+    for config in configs {
+        out.push_str(&config.host);
+        for (key, value) in ansible_host_vars(config, include_secrets) {
+            out.push_str(&format!(" {}={}", key, quote(&value)));
+        }
+        out.push('\n');
+    }
+    for (group, hosts) in ansible_groups(configs) {
+        out.push_str(&format!("\n[{}]\n", group));
+        for host in hosts {
+            out.push_str(host);
+            out.push('\n');
+        }
+    }
+    out
+}
+
+fn ansible_inventory(configs: &[SshConfig], include_secrets: bool) -> serde_yaml::Value {
+    use serde_yaml::{Mapping, Value};
+
+    let mut hosts = Mapping::new();
+    for config in configs {
+        let mut vars = Mapping::new();
+        for (key, value) in ansible_host_vars(config, include_secrets) {
+            let value = match key {
+                "ansible_port" => Value::from(value.parse::<u16>().unwrap_or_default()),
+                _ => Value::from(value),
+            };
+            vars.insert(key.into(), value);
+        }
+        hosts.insert(config.host.clone().into(), Value::Mapping(vars));
+    }
+
+    let mut children = Mapping::new();
+    for (group, members) in ansible_groups(configs) {
+        let members: Mapping = members
+            .into_iter()
+            .map(|host| (Value::from(host), Value::Mapping(Mapping::new())))
+            .collect();
+        let mut group_body = Mapping::new();
+        group_body.insert("hosts".into(), Value::Mapping(members));
+        children.insert(group.into(), Value::Mapping(group_body));
+    }
+
+    let mut all = Mapping::new();
+    all.insert("hosts".into(), Value::Mapping(hosts));
+    if !children.is_empty() {
+        all.insert("children".into(), Value::Mapping(children));
+    }
+    let mut root = Mapping::new();
+    root.insert("all".into(), Value::Mapping(all));
+    Value::Mapping(root)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::EnvironmentLabel;
+
+    fn existing_host() -> SshConfig {
+        let mut config = SshConfig::new("web".to_string());
+        config.hostname = Some("10.0.0.1".to_string());
+        config.user = Some("deploy".to_string());
+        config.identity_files = vec!["~/.ssh/web".to_string(), "~/.ssh/backup".to_string()];
+        config.password = Some("s3cret".to_string());
+        config.tags = vec!["prod".to_string(), "web".to_string()];
+        config.environment = EnvironmentLabel::parse("production");
+        config
+    }
+
+    fn read_csv(content: &str) -> Vec<SshConfig> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("hosts.csv");
+        fs::write(&path, content).unwrap();
+        read_inventory(&path, Format::Csv)
+            .unwrap()
+            .hosts
+            .iter()
+            .map(|host| host.to_ssh_config().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn overwrite_keeps_fields_missing_from_inventory() {
+        let imported = read_csv("alias,hostname,user,port,identity_file,tags,password_ref\nweb,10.0.0.2,,2222,,,\n");
+        let merged = overwrite_existing(&existing_host(), imported.into_iter().next().unwrap());
+
+        assert_eq!(merged.hostname.as_deref(), Some("10.0.0.2"));
+        assert_eq!(merged.port.as_deref(), Some("2222"));
+        assert_eq!(merged.user.as_deref(), Some("deploy"));
+        assert_eq!(merged.password.as_deref(), Some("s3cret"));
+        assert_eq!(merged.tags, ["prod", "web"]);
+        assert_eq!(merged.identity_files, ["~/.ssh/web", "~/.ssh/backup"]);
+        assert!(merged.environment.is_some());
+
+        let text = merged.to_config_string();
+        assert!(text.contains("  #pass s3cret\n"));
+        assert!(text.contains("  #tags prod,web\n"));
+    }
+
+    #[test]
+    fn overwrite_replaces_supplied_fields() {
+        let imported = read_csv("alias,hostname,user,identity_file,tags,password_ref\nweb,10.0.0.2,admin,~/.ssh/new,staging,n3w\n");
+        let merged = overwrite_existing(&existing_host(), imported.into_iter().next().unwrap());
+
+        assert_eq!(merged.user.as_deref(), Some("admin"));
+        assert_eq!(merged.identity_files, ["~/.ssh/new"]);
+        assert_eq!(merged.tags, ["staging"]);
+        assert_eq!(merged.password.as_deref(), Some("n3w"));
+    }
+
+    #[test]
+    fn preview_hides_resolved_secrets() {
+        let dir = tempfile::tempdir().unwrap();
+        let secret = dir.path().join("secret");
+        fs::write(&secret, "fr0m-file\n").unwrap();
+        let csv = format!("alias,hostname,password_ref\nweb,10.0.0.2,file:{}\n", secret.display());
+        let additions = read_csv(&csv);
+        assert_eq!(additions[0].password.as_deref(), Some("fr0m-file"));
+
+        let mut overwrite = existing_host();
+        overwrite.passphrase = Some("k3y-pass".to_string());
+        overwrite.totp_secret = Some("JBSWY3DPEHPK3PXP".to_string());
+        let preview = import_preview(&additions, &[overwrite]);
+
+        for secret in ["fr0m-file", "s3cret", "k3y-pass", "JBSWY3DPEHPK3PXP"] {
+            assert!(!preview.contains(secret), "{} in {}", secret, preview);
+        }
+        assert!(preview.contains("# New: web\nHost web\n"));
+        assert!(preview.contains("  #pass ********\n"));
+        assert!(preview.contains("  #passphrase ********\n"));
+    }
+
+    fn exported_hosts() -> Vec<SshConfig> {
+        let mut jump = SshConfig::new("db".to_string());
+        jump.hostname = Some("10.0.0.5".to_string());
+        jump.proxy_jump = Some("bastion".to_string());
+        jump.tags = vec!["db".to_string(), "prod-eu".to_string()];
+        let mut command = SshConfig::new("legacy".to_string());
+        command.hostname = Some("legacy.example.com".to_string());
+        command.proxy_command = Some("nc -X 5 -x proxy:1080 %h %p".to_string());
+        vec![existing_host(), jump, command]
+    }
+
+    fn round_trip(format: ExportFormat, import_format: Format, include_secrets: bool) -> Vec<SshConfig> {
+        let text = export_hosts(&exported_hosts(), format, include_secrets).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("hosts");
+        fs::write(&path, text).unwrap();
+        read_inventory(&path, import_format)
+            .unwrap()
+            .hosts
+            .iter()
+            .map(|host| host.to_ssh_config().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn export_round_trips_through_import() {
+        let cases = [
+            (ExportFormat::Json, Format::Json),
+            (ExportFormat::Csv, Format::Csv),
+            (ExportFormat::Yaml, Format::Yaml),
+        ];
+        for (export_format, import_format) in cases {
+            for include_secrets in [false, true] {
+                let imported = round_trip(export_format, import_format, include_secrets);
+                let original = exported_hosts();
+                assert_eq!(imported.len(), original.len(), "{:?}", export_format);
+                for (imported, original) in imported.iter().zip(&original) {
+                    assert_eq!(imported.host, original.host);
+                    assert_eq!(imported.hostname, original.hostname);
+                    assert_eq!(imported.user, original.user);
+                    assert_eq!(imported.port, original.port);
+                    assert_eq!(imported.identity_files, original.identity_files);
+                    assert_eq!(imported.proxy_jump, original.proxy_jump);
+                    assert_eq!(imported.proxy_command, original.proxy_command);
+                    assert_eq!(imported.tags, original.tags);
+                    let password = original.password.as_ref().filter(|_| include_secrets);
+                    assert_eq!(imported.password.as_ref(), password, "{:?}", export_format);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn exports_hide_secrets_by_default() {
+        let mut hosts = exported_hosts();
+        hosts[0].passphrase = Some("k3y-pass".to_string());
+        hosts[0].totp_secret = Some("JBSWY3DPEHPK3PXP".to_string());
+        let formats = [
+            ExportFormat::Json,
+            ExportFormat::Csv,
+            ExportFormat::Yaml,
+            ExportFormat::AnsibleIni,
+            ExportFormat::AnsibleYaml,
+        ];
+        for format in formats {
+            let text = export_hosts(&hosts, format, false).unwrap();
+            for secret in ["s3cret", "k3y-pass", "JBSWY3DPEHPK3PXP"] {
+                assert!(!text.contains(secret), "{:?}: {}", format, text);
+            }
+        }
+        let csv = export_hosts(&hosts, ExportFormat::Csv, true).unwrap();
+        assert!(csv.lines().next().unwrap().ends_with(",password,passphrase,totp_secret"));
+        assert!(csv.contains(",s3cret,k3y-pass,JBSWY3DPEHPK3PXP"));
+    }
+
+    #[test]
+    fn exports_ansible_ini() {
+        let text = export_hosts(&exported_hosts(), ExportFormat::AnsibleIni, true).unwrap();
+        assert_eq!(
+            text,
+            "web ansible_host=10.0.0.1 ansible_user=deploy ansible_ssh_private_key_file=~/.ssh/web ansible_password=s3cret\n\
+             db ansible_host=10.0.0.5 ansible_ssh_common_args=\"-o ProxyJump=bastion\"\n\
+             legacy ansible_host=legacy.example.com ansible_ssh_common_args=\"-o ProxyCommand=\\\"nc -X 5 -x proxy:1080 %h %p\\\"\"\n\
+             \n[db]\ndb\n\n[prod]\nweb\n\n[prod_eu]\ndb\n\n[web]\nweb\n"
+        );
+    }
+
+    #[test]
+    fn exports_ansible_yaml() {
+        let text = export_hosts(&exported_hosts(), ExportFormat::AnsibleYaml, false).unwrap();
+        let inventory: serde_yaml::Value = serde_yaml::from_str(&text).unwrap();
+        let all = &inventory["all"];
+        assert_eq!(all["hosts"]["web"]["ansible_host"], "10.0.0.1");
+        assert_eq!(all["hosts"]["web"]["ansible_user"], "deploy");
+        assert!(all["hosts"]["web"].get("ansible_password").is_none());
+        assert_eq!(all["hosts"]["db"]["ansible_ssh_common_args"], "-o ProxyJump=bastion");
+        assert!(all["children"]["prod_eu"]["hosts"].get("db").is_some());
+        assert!(all["children"]["web"]["hosts"].get("web").is_some());
+
+        let mut with_port = exported_hosts();
+        with_port[0].port = Some("2222".to_string());
+        let text = export_hosts(&with_port, ExportFormat::AnsibleYaml, false).unwrap();
+        let inventory: serde_yaml::Value = serde_yaml::from_str(&text).unwrap();
+        assert_eq!(inventory["all"]["hosts"]["web"]["ansible_port"], 2222);
+    }
+}
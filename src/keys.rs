@@ -0,0 +1,225 @@
+use crate::utils::home_dir;
+use ssh_key::rand_core::OsRng;
+use ssh_key::{Algorithm, LineEnding, PrivateKey, PublicKey};
+use std::fs;
+use std::io::{self, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+
+/// 鍵だけでログインさせる（パスワード認証と、config 以外の鍵を使わない）ssh のオプション。
+/// 多重化のマスター接続を使うと認証を確かめたことにならないので、使わせない
+pub const KEY_ONLY_OPTIONS: &[&str] = &[
+    "-o", "PasswordAuthentication=no",
+    "-o", "KbdInteractiveAuthentication=no",
+    "-o", "IdentitiesOnly=yes",
+    "-o", "ControlPath=none",
+];
+
+fn key_error(e: ssh_key::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+}
+
+/// ホスト専用の鍵のパス（`~/.ssh/id_ed25519_<host><suffix>`）。
+/// config に書く `~` 表記と、実際のパスの組を返す
+pub fn host_key_path(host: &str, suffix: &str) -> io::Result<(String, PathBuf)> {
+    let name: String = host
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || "-_.".contains(c) { c } else { '_' })
+        .collect();
+    let file_name = format!("id_ed25519_{}{}", name, suffix);
+    Ok((
+        format!("~/.ssh/{}", file_name),
+        home_dir()?.join(".ssh").join(file_name),
+    ))
+}
+
+/// ed25519 の鍵ペアを生成して書き出し、公開鍵を返す。既存のファイルは上書きしない
+pub fn generate_key(path: &Path, comment: &str, passphrase: Option<&str>) -> io::Result<PublicKey> {
+    let mut key = PrivateKey::random(&mut OsRng, Algorithm::Ed25519).map_err(key_error)?;
+    key.set_comment(comment);
+    // 暗号化すると公開鍵側のコメントが消えるので、先に取り出しておく
+    let public = key.public_key().clone();
+    if let Some(passphrase) = passphrase {
+        key = key.encrypt(&mut OsRng, passphrase).map_err(key_error)?;
+    }
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let pem = key.to_openssh(LineEnding::LF).map_err(key_error)?;
+    fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)?
+        .write_all(pem.as_bytes())?;
+
+    let public_line = public.to_openssh().map_err(key_error)?;
+    fs::write(public_path(path), format!("{}\n", public_line))?;
+    Ok(public)
+}
+
+/// 秘密鍵と公開鍵のファイルを削除する
+pub fn remove_key_files(path: &Path) -> io::Result<()> {
+    fs::remove_file(path)?;
+    match fs::remove_file(public_path(path)) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+fn public_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".pub");
+    PathBuf::from(name)
+}
+
+/// 鍵に対応する公開鍵を読む。`.pub` がなければ秘密鍵から取り出す（暗号化されていても読める）
+pub fn read_public_key(path: &Path) -> io::Result<PublicKey> {
+    if let Ok(line) = fs::read_to_string(public_path(path)) {
+        return PublicKey::from_openssh(line.trim()).map_err(key_error);
+    }
+    let pem = fs::read_to_string(path)?;
+    let key = PrivateKey::from_openssh(&pem).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} is not an OpenSSH private key ({})", path.display(), e),
+        )
+    })?;
+    Ok(key.public_key().clone())
+}
+
+/// シェルの単一引用符で囲む
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// リモートで sh に実行させるコマンドを組み立てる（ログインシェルの種類に依存しないように）
+fn remote_sh(script: &str) -> String {
+    format!("exec sh -c {}", shell_quote(script))
+}
+
+/// 公開鍵の `<種類> <base64>` 部分（コメントを除いたもの）
+fn key_text(key: &PublicKey) -> io::Result<String> {
+    let line = key.to_openssh().map_err(key_error)?;
+    Ok(line.split_whitespace().take(2).collect::<Vec<_>>().join(" "))
+}
+
+/// ssh-copy-id と同様に、まだ登録されていなければ authorized_keys に公開鍵を追記するコマンド
+pub fn install_key_command(key: &PublicKey) -> io::Result<String> {
+    let line = shell_quote(&key.to_openssh().map_err(key_error)?);
+    let needle = shell_quote(&key_text(key)?);
+    let script = format!(
+        "umask 077 && mkdir -p ~/.ssh && touch ~/.ssh/authorized_keys && \
+         {{ grep -qF {needle} ~/.ssh/authorized_keys || {{ \
+         if [ -s ~/.ssh/authorized_keys ] && [ -n \"$(tail -c 1 ~/.ssh/authorized_keys)\" ]; then echo >> ~/.ssh/authorized_keys; fi; \
+         printf '%s\\n' {line} >> ~/.ssh/authorized_keys; }}; }}"
+    );
+    Ok(remote_sh(&script))
+}
+
+/// authorized_keys から `remove` の公開鍵を取り除くコマンド。
+/// 締め出されないよう、`keep` の公開鍵が登録されている場合だけ書き換える
+pub fn remove_key_command(remove: &PublicKey, keep: &PublicKey) -> io::Result<String> {
+    let remove = shell_quote(&key_text(remove)?);
+    let keep = shell_quote(&key_text(keep)?);
+    // cp -p で所有者とパーミッションを引き継いだ一時ファイルに書き、mv で置き換える。
+    // 途中で接続が切れても authorized_keys が空になることはない
+    let script = format!(
+        "f=~/.ssh/authorized_keys && t=\"$f.sshct\" && grep -qF {keep} \"$f\" && cp -p \"$f\" \"$t\" && \
+         {{ grep -vF {remove} \"$f\" > \"$t\" || [ $? -eq 1 ]; }} && grep -qF {keep} \"$t\" && mv -f \"$t\" \"$f\" || \
+         {{ rm -f \"$t\"; exit 1; }}"
+    );
+    Ok(remote_sh(&script))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use std::process::Command;
+
+    fn random_key(comment: &str) -> PublicKey {
+        let mut key = PrivateKey::random(&mut OsRng, Algorithm::Ed25519).unwrap();
+        key.set_comment(comment);
+        key.public_key().clone()
+    }
+
+    /// リモートの代わりに、一時ディレクトリを HOME にしてローカルの sh で実行する
+    fn run_remote(home: &Path, command: &str) -> i32 {
+        Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .env("HOME", home)
+            .status()
+            .unwrap()
+            .code()
+            .unwrap()
+    }
+
+    fn authorized_keys(home: &Path) -> PathBuf {
+        home.join(".ssh").join("authorized_keys")
+    }
+
+    #[test]
+    fn installs_key_once() {
+        let home = tempfile::tempdir().unwrap();
+        let key = random_key("new@laptop");
+        let command = install_key_command(&key).unwrap();
+
+        assert_eq!(run_remote(home.path(), &command), 0);
+        assert_eq!(run_remote(home.path(), &command), 0);
+        let content = fs::read_to_string(authorized_keys(home.path())).unwrap();
+        assert_eq!(content, format!("{}\n", key.to_openssh().unwrap()));
+        let mode = fs::metadata(authorized_keys(home.path())).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[test]
+    fn removes_old_key_and_keeps_the_rest() {
+        let home = tempfile::tempdir().unwrap();
+        let (old, new, other) = (random_key("old"), random_key("new"), random_key("other"));
+        fs::create_dir(home.path().join(".ssh")).unwrap();
+        let path = authorized_keys(home.path());
+        let lines = [
+            old.to_openssh().unwrap(),
+            "# keep this comment".to_string(),
+            other.to_openssh().unwrap(),
+            new.to_openssh().unwrap(),
+        ];
+        fs::write(&path, lines.join("\n") + "\n").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+
+        assert_eq!(run_remote(home.path(), &remove_key_command(&old, &new).unwrap()), 0);
+        assert_eq!(fs::read_to_string(&path).unwrap(), lines[1..].join("\n") + "\n");
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o640);
+        assert!(!home.path().join(".ssh/authorized_keys.sshct").exists());
+    }
+
+    #[test]
+    fn refuses_to_remove_without_replacement_key() {
+        let home = tempfile::tempdir().unwrap();
+        let (old, new) = (random_key("old"), random_key("new"));
+        fs::create_dir(home.path().join(".ssh")).unwrap();
+        let path = authorized_keys(home.path());
+        let content = format!("{}\n", old.to_openssh().unwrap());
+        fs::write(&path, &content).unwrap();
+
+        assert_ne!(run_remote(home.path(), &remove_key_command(&old, &new).unwrap()), 0);
+        assert_eq!(fs::read_to_string(&path).unwrap(), content);
+        assert!(!home.path().join(".ssh/authorized_keys.sshct").exists());
+    }
+
+    #[test]
+    fn removing_the_only_other_key_leaves_replacement() {
+        let home = tempfile::tempdir().unwrap();
+        let (old, new) = (random_key("old"), random_key("new"));
+        fs::create_dir(home.path().join(".ssh")).unwrap();
+        let path = authorized_keys(home.path());
+        let content = format!("{}\n{}\n", old.to_openssh().unwrap(), new.to_openssh().unwrap());
+        fs::write(&path, content).unwrap();
+
+        assert_eq!(run_remote(home.path(), &remove_key_command(&old, &new).unwrap()), 0);
+        assert_eq!(fs::read_to_string(&path).unwrap(), format!("{}\n", new.to_openssh().unwrap()));
+    }
+}
@@ -0,0 +1,668 @@
+use crate::config::{get_ssh_config_path, host_patterns_match, is_repeatable, pattern_matches, split_directive};
+use crate::utils::{expand_tilde, home_dir};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+// Include の入れ子上限（OpenSSH と同じ値）
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+// ssh_config(5) で定義されているクライアント側のキーワード
+const KNOWN_KEYWORDS: &[&str] = &[
+    "addkeystoagent", "addressfamily", "batchmode", "bindaddress", "bindinterface",
+    "canonicaldomains", "canonicalizefallbacklocal", "canonicalizehostname",
+    "canonicalizemaxdots", "canonicalizepermittedcnames", "casignaturealgorithms",
+    "certificatefile", "channeltimeout", "checkhostip", "ciphers", "clearallforwardings",
+    "compression", "connectionattempts", "connecttimeout", "controlmaster", "controlpath",
+    "controlpersist", "dynamicforward", "enableescapecommandline", "enablesshkeysign",
+    "escapechar", "exitonforwardfailure", "fingerprinthash", "forkafterauthentication",
+    "forwardagent", "forwardx11", "forwardx11timeout", "forwardx11trusted",
+    "gatewayports", "globalknownhostsfile", "gssapiauthentication",
+    "gssapidelegatecredentials", "hashknownhosts", "host", "hostbasedacceptedalgorithms",
+    "hostbasedauthentication", "hostkeyalgorithms", "hostkeyalias", "hostname",
+    "identitiesonly", "identityagent", "identityfile", "ignoreunknown", "include",
+    "ipqos", "kbdinteractiveauthentication", "kbdinteractivedevices", "kexalgorithms",
+    "knownhostscommand", "localcommand", "localforward", "loglevel", "logverbose",
+    "macs", "match", "nohostauthenticationforlocalhost", "numberofpasswordprompts",
+    "obscurekeystroketiming", "passwordauthentication", "permitlocalcommand",
+    "permitremoteopen", "pkcs11provider", "port", "preferredauthentications",
+    "proxycommand", "proxyjump", "proxyusefdpass", "pubkeyacceptedalgorithms",
+    "pubkeyauthentication", "rekeylimit", "remotecommand", "remoteforward",
+    "requesttty", "requiredrsasize", "revokedhostkeys", "securitykeyprovider",
+    "sendenv", "serveralivecountmax", "serveraliveinterval", "sessiontype", "setenv",
+    "stdinnull", "streamlocalbindmask", "streamlocalbindunlink", "stricthostkeychecking",
+    "syslogfacility", "tag", "tcpkeepalive", "tunnel", "tunneldevice",
+    "updatehostkeys", "user", "userknownhostsfile", "verifyhostkeydns",
+    "visualhostkey", "xauthlocation",
+];
+
+// 非推奨・廃止されたキーワードと、その代替
+const DEPRECATED_KEYWORDS: &[(&str, &str)] = &[
+    ("protocol", "SSH protocol 1 support has been removed; delete this line"),
+    ("cipher", "use Ciphers instead"),
+    ("rsaauthentication", "SSH protocol 1 only; delete this line"),
+    ("rhostsrsaauthentication", "SSH protocol 1 only; delete this line"),
+    ("compressionlevel", "SSH protocol 1 only; delete this line"),
+    ("useprivilegedport", "no longer supported; delete this line"),
+    ("useroaming", "no longer supported; delete this line"),
+    ("dsaauthentication", "DSA keys are no longer supported"),
+    ("challengeresponseauthentication", "use KbdInteractiveAuthentication instead"),
+    ("pubkeyacceptedkeytypes", "use PubkeyAcceptedAlgorithms instead"),
+    ("hostbasedkeytypes", "use HostbasedAcceptedAlgorithms instead"),
+    ("fallbacktorsh", "no longer supported; delete this line"),
+    ("usersh", "no longer supported; delete this line"),
+];
+
+// 平文の秘密情報を書く sshr の拡張行と、その警告文
+const SECRET_COMMENTS: &[(&str, &str)] = &[
+    ("#pass", "password is stored in plaintext (#pass)"),
+    ("#passphrase", "key passphrase is stored in plaintext (#passphrase)"),
+    ("#totp", "TOTP secret is stored in plaintext (#totp)"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Finding {
+    pub severity: Severity,
+    pub code: &'static str,
+    pub file: PathBuf,
+    pub line: usize,
+    pub message: String,
+}
+
+#[derive(Debug, Clone)]
+struct Directive {
+    file: PathBuf,
+    line: usize,
+    // 表示用の元の表記と、比較用の小文字表記
+    name: String,
+    keyword: String,
+    value: String,
+}
+
+#[derive(Debug)]
+struct Block {
+    // Host 行のパターン。先頭のグローバル設定は "*"、Match ブロックは None
+    patterns: Option<String>,
+    file: PathBuf,
+    line: usize,
+    directives: Vec<Directive>,
+}
+
+#[derive(Default)]
+struct LintContext {
+    blocks: Vec<Block>,
+    // ディレクティブを追加するブロック（Include から戻ったときに元のブロックへ戻す）
+    current: Option<usize>,
+    // IgnoreUnknown で指定されたパターン（小文字）
+    ignore_unknown: Vec<String>,
+    findings: Vec<Finding>,
+    visited: HashSet<PathBuf>,
+}
+
+impl LintContext {
+    fn report(&mut self, severity: Severity, code: &'static str, file: &Path, line: usize, message: String) {
+        self.findings.push(Finding {
+            severity,
+            code,
+            file: file.to_path_buf(),
+            line,
+            message,
+        });
+    }
+}
+
+/// Include の引数をファイルパスに展開する（ワイルドカードは最後の要素のみ対応）
+fn expand_include(value: &str) -> io::Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for arg in value.split_whitespace() {
+        let arg = arg.trim_matches('"');
+        let path = if arg.starts_with('~') || arg.starts_with('/') {
+            expand_tilde(arg)?
+        } else {
+            home_dir()?.join(".ssh").join(arg)
+        };
+
+        let file_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        if !file_name.contains('*') && !file_name.contains('?') {
+            paths.push(path);
+            continue;
+        }
+
+        let pattern = file_name.to_string();
+        if let Some(dir) = path.parent() {
+            if let Ok(entries) = fs::read_dir(dir) {
+                let mut matched: Vec<PathBuf> = entries
+                    .filter_map(|e| e.ok())
+                    .map(|e| e.path())
+                    .filter(|p| {
+                        p.file_name()
+                            .and_then(|n| n.to_str())
+                            .map(|n| pattern_matches(&pattern, n))
+                            .unwrap_or(false)
+                    })
+                    .collect();
+                matched.sort();
+                paths.extend(matched);
+            }
+        }
+    }
+    Ok(paths)
+}
+
+fn read_config_file(ctx: &mut LintContext, path: &Path, depth: usize) -> io::Result<()> {
+    let canonical = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    if !ctx.visited.insert(canonical) {
+        return Ok(());
+    }
+
+    check_config_permissions(ctx, path);
+
+    let content = fs::read_to_string(path)?;
+    for (idx, raw) in content.lines().enumerate() {
+        let line_no = idx + 1;
+        let trimmed = raw.trim();
+
+        // config の読み込みと同じく、空白（タブを含む）で区切って拡張行を判定する
+        let lower_line = trimmed.to_lowercase();
+        let (word, rest) = lower_line.split_once(char::is_whitespace).unwrap_or((&lower_line, ""));
+        if !rest.trim().is_empty() {
+            if let Some((_, message)) = SECRET_COMMENTS.iter().find(|(w, _)| *w == word) {
+                ctx.report(Severity::Warning, "plaintext-password", path, line_no, message.to_string());
+                continue;
+            }
+        }
+
+        let Some((keyword, value)) = split_directive(raw) else {
+            continue;
+        };
+        let lower = keyword.to_lowercase();
+
+        match lower.as_str() {
+            "host" => {
+                ctx.blocks.push(Block {
+                    patterns: Some(value),
+                    file: path.to_path_buf(),
+                    line: line_no,
+                    directives: Vec::new(),
+                });
+                ctx.current = Some(ctx.blocks.len() - 1);
+            }
+            "match" => {
+                ctx.blocks.push(Block {
+                    patterns: None,
+                    file: path.to_path_buf(),
+                    line: line_no,
+                    directives: Vec::new(),
+                });
+                ctx.current = Some(ctx.blocks.len() - 1);
+            }
+            "include" => {
+                if depth >= MAX_INCLUDE_DEPTH {
+                    ctx.report(
+                        Severity::Error,
+                        "include-depth",
+                        path,
+                        line_no,
+                        format!("Include nested deeper than {} levels", MAX_INCLUDE_DEPTH),
+                    );
+                    continue;
+                }
+                let targets = expand_include(&value)?;
+                if targets.is_empty() {
+                    ctx.report(
+                        Severity::Warning,
+                        "include-missing",
+                        path,
+                        line_no,
+                        format!("Include '{}' does not match any file", value),
+                    );
+                }
+                // Include 先の Host 行は Include 先の中だけで有効で、戻ったあとの行は元のブロックに属する
+                let current = ctx.current;
+                for target in targets {
+                    ctx.current = current;
+                    if !target.exists() {
+                        ctx.report(
+                            Severity::Warning,
+                            "include-missing",
+                            path,
+                            line_no,
+                            format!("included file {} does not exist", target.display()),
+                        );
+                        continue;
+                    }
+                    if let Err(e) = read_config_file(ctx, &target, depth + 1) {
+                        ctx.report(
+                            Severity::Error,
+                            "include-unreadable",
+                            path,
+                            line_no,
+                            format!("cannot read {}: {}", target.display(), e),
+                        );
+                    }
+                }
+                ctx.current = current;
+            }
+            _ => {
+                if let Some((_, hint)) = DEPRECATED_KEYWORDS.iter().find(|(k, _)| *k == lower) {
+                    ctx.report(
+                        Severity::Warning,
+                        "deprecated-option",
+                        path,
+                        line_no,
+                        format!("{} is deprecated: {}", keyword, hint),
+                    );
+                } else if !KNOWN_KEYWORDS.contains(&lower.as_str())
+                    && !ctx.ignore_unknown.iter().any(|p| pattern_matches(p, &lower))
+                {
+                    ctx.report(
+                        Severity::Error,
+                        "unknown-keyword",
+                        path,
+                        line_no,
+                        format!("unknown keyword '{}'", keyword),
+                    );
+                }
+
+                if lower == "ignoreunknown" {
+                    ctx.ignore_unknown
+                        .extend(value.split(',').map(|p| p.trim().trim_matches('"').to_lowercase()));
+                }
+
+                let directive = Directive {
+                    file: path.to_path_buf(),
+                    line: line_no,
+                    name: keyword,
+                    keyword: lower,
+                    value,
+                };
+                match ctx.current {
+                    Some(index) => ctx.blocks[index].directives.push(directive),
+                    None => {
+                        ctx.blocks.push(Block {
+                            patterns: Some("*".to_string()),
+                            file: path.to_path_buf(),
+                            line: line_no,
+                            directives: vec![directive],
+                        });
+                        ctx.current = Some(ctx.blocks.len() - 1);
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn check_config_permissions(ctx: &mut LintContext, path: &Path) {
+    let Ok(metadata) = fs::metadata(path) else {
+        return;
+    };
+    let mode = metadata.permissions().mode() & 0o777;
+    if mode & 0o022 != 0 {
+        ctx.report(
+            Severity::Error,
+            "config-permissions",
+            path,
+            0,
+            format!("config is writable by group/others (mode {:o}); ssh will refuse it, run chmod 600", mode),
+        );
+    } else if mode & 0o004 != 0 {
+        ctx.report(
+            Severity::Warning,
+            "config-permissions",
+            path,
+            0,
+            format!("config is world-readable (mode {:o}); run chmod 600", mode),
+        );
+    }
+}
+
+fn is_wildcard(pattern: &str) -> bool {
+    pattern.contains('*') || pattern.contains('?')
+}
+
+fn check_duplicate_hosts(ctx: &mut LintContext) {
+    let mut seen: HashMap<String, (PathBuf, usize)> = HashMap::new();
+    let mut duplicates = Vec::new();
+
+    for block in &ctx.blocks {
+        let Some(patterns) = &block.patterns else {
+            continue;
+        };
+        for alias in patterns.split_whitespace() {
+            if is_wildcard(alias) || alias.starts_with('!') {
+                continue;
+            }
+            match seen.get(alias) {
+                Some((file, line)) => duplicates.push((
+                    block.file.clone(),
+                    block.line,
+                    format!(
+                        "duplicate Host alias '{}' (first defined at {}:{}); only the first block is used",
+                        alias,
+                        file.display(),
+                        line
+                    ),
+                )),
+                None => {
+                    seen.insert(alias.to_string(), (block.file.clone(), block.line));
+                }
+            }
+        }
+    }
+
+    for (file, line, message) in duplicates {
+        ctx.report(Severity::Error, "duplicate-host", &file, line, message);
+    }
+}
+
+fn check_shadowed_blocks(ctx: &mut LintContext) {
+    let mut reports = Vec::new();
+
+    for (i, block) in ctx.blocks.iter().enumerate() {
+        let Some(patterns) = &block.patterns else {
+            continue;
+        };
+        let aliases: Vec<&str> = patterns
+            .split_whitespace()
+            .filter(|p| !is_wildcard(p) && !p.starts_with('!'))
+            .collect();
+        if aliases.is_empty() || block.directives.is_empty() {
+            continue;
+        }
+
+        // 全エイリアスに共通して、手前のワイルドカードブロックで既に設定済みのキーワードを求める
+        let mut shadowing: Option<HashMap<String, (PathBuf, usize, String)>> = None;
+        for alias in &aliases {
+            let mut set_by: HashMap<String, (PathBuf, usize, String)> = HashMap::new();
+            for earlier in &ctx.blocks[..i] {
+                let Some(earlier_patterns) = &earlier.patterns else {
+                    continue;
+                };
+                if !earlier_patterns.split_whitespace().any(is_wildcard)
+                    || !host_patterns_match(earlier_patterns, alias)
+                {
+                    continue;
+                }
+                for d in &earlier.directives {
+                    set_by
+                        .entry(d.keyword.clone())
+                        .or_insert((earlier.file.clone(), earlier.line, earlier_patterns.clone()));
+                }
+            }
+            shadowing = Some(match shadowing {
+                None => set_by,
+                Some(prev) => prev
+                    .into_iter()
+                    .filter(|(k, _)| set_by.contains_key(k))
+                    .collect(),
+            });
+        }
+        let shadowing = shadowing.unwrap_or_default();
+
+        let shadowed: Vec<&Directive> = block
+            .directives
+            .iter()
+            .filter(|d| {
+                !is_repeatable(&d.keyword)
+                    && shadowing.contains_key(&d.keyword)
+            })
+            .collect();
+        if shadowed.is_empty() {
+            continue;
+        }
+
+        if shadowed.len() == block.directives.len() {
+            reports.push((
+                block.file.clone(),
+                block.line,
+                format!(
+                    "Host block '{}' is unreachable: every setting is overridden by earlier wildcard blocks",
+                    patterns
+                ),
+            ));
+        } else {
+            for d in shadowed {
+                let (file, line, earlier) = &shadowing[&d.keyword];
+                reports.push((
+                    d.file.clone(),
+                    d.line,
+                    format!(
+                        "{} is overridden by earlier 'Host {}' at {}:{}",
+                        d.name,
+                        earlier,
+                        file.display(),
+                        line
+                    ),
+                ));
+            }
+        }
+    }
+
+    for (file, line, message) in reports {
+        ctx.report(Severity::Warning, "shadowed-host", &file, line, message);
+    }
+}
+
+fn check_identity_files(ctx: &mut LintContext) -> io::Result<()> {
+    let mut reports = Vec::new();
+
+    for block in &ctx.blocks {
+        for d in block.directives.iter().filter(|d| d.keyword == "identityfile") {
+            // %h などのトークンや環境変数を含むパスは実行時まで確定しないので対象外
+            if d.value.contains('%') || d.value.contains("${") || d.value.eq_ignore_ascii_case("none") {
+                continue;
+            }
+            let path = expand_tilde(d.value.trim_matches('"'))?;
+            match fs::metadata(&path) {
+                Err(_) => reports.push((
+                    Severity::Error,
+                    "missing-identity",
+                    d.file.clone(),
+                    d.line,
+                    format!("IdentityFile {} does not exist", path.display()),
+                )),
+                Ok(metadata) => {
+                    let mode = metadata.permissions().mode() & 0o777;
+                    if mode & 0o077 != 0 {
+                        reports.push((
+                            Severity::Error,
+                            "key-permissions",
+                            d.file.clone(),
+                            d.line,
+                            format!(
+                                "IdentityFile {} is accessible by others (mode {:o}); ssh will ignore it, run chmod 600",
+                                path.display(),
+                                mode
+                            ),
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    for (severity, code, file, line, message) in reports {
+        ctx.report(severity, code, &file, line, message);
+    }
+    Ok(())
+}
+
+/// SSH config 全体（Include 先を含む）を検査する
+pub fn lint_config() -> io::Result<Vec<Finding>> {
+    let config_path = get_ssh_config_path()?;
+    if !config_path.exists() {
+        return Ok(Vec::new());
+    }
+    lint_file(&config_path)
+}
+
+fn lint_file(config_path: &Path) -> io::Result<Vec<Finding>> {
+    let mut ctx = LintContext::default();
+    read_config_file(&mut ctx, config_path, 0)?;
+    check_duplicate_hosts(&mut ctx);
+    check_shadowed_blocks(&mut ctx);
+    check_identity_files(&mut ctx)?;
+
+    let mut findings = ctx.findings;
+    findings.sort_by(|a, b| (&a.file, a.line).cmp(&(&b.file, b.line)));
+    Ok(findings)
+}
+
+pub fn print_findings(findings: &[Finding]) {
+    for f in findings {
+        let severity = match f.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        println!(
+            "{}:{}: {}[{}]: {}",
+            f.file.display(),
+            f.line,
+            severity,
+            f.code,
+            f.message
+        );
+    }
+
+    let errors = findings.iter().filter(|f| f.severity == Severity::Error).count();
+    let warnings = findings.len() - errors;
+    println!("\n{} error(s), {} warning(s)", errors, warnings);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_config(dir: &Path, name: &str, content: &str) -> PathBuf {
+        let path = dir.join(name);
+        fs::write(&path, content).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+        path
+    }
+
+    fn lint(content: &str) -> Vec<(usize, &'static str)> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_config(dir.path(), "config", content);
+        lint_file(&path).unwrap().iter().map(|f| (f.line, f.code)).collect()
+    }
+
+    #[test]
+    fn reports_duplicate_hosts() {
+        let findings = lint("Host web db\n  HostName a\nHost web\n  HostName b\n");
+        assert_eq!(findings, [(3, "duplicate-host")]);
+    }
+
+    #[test]
+    fn reports_shadowed_blocks() {
+        assert_eq!(lint("Host *\n  User root\nHost web\n  User deploy\n"), [(3, "shadowed-host")]);
+        assert_eq!(
+            lint("Host *.example.com\n  User root\nHost a.example.com\n  User deploy\n  Port 2222\n"),
+            [(4, "shadowed-host")]
+        );
+        // 後ろにあるワイルドカードは上書きしない
+        assert!(lint("Host web\n  User deploy\nHost *\n  User root\n").is_empty());
+    }
+
+    #[test]
+    fn reports_unknown_and_deprecated_keywords() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_config(dir.path(), "config", "Host web\n  Frobnicate yes\n  Protocol 2\n  HostName a\n");
+        let findings = lint_file(&path).unwrap();
+        let summary: Vec<_> = findings.iter().map(|f| (f.line, f.code, f.severity)).collect();
+        assert_eq!(
+            summary,
+            [(2, "unknown-keyword", Severity::Error), (3, "deprecated-option", Severity::Warning)]
+        );
+    }
+
+    #[test]
+    fn honours_ignore_unknown() {
+        let config = "IgnoreUnknown UseKeychain,Add*\nHost *\n  UseKeychain yes\n  AddFoo no\n  Frobnicate yes\n";
+        assert_eq!(lint(config), [(5, "unknown-keyword")]);
+        // ssh と同じく、IgnoreUnknown より前の行には効かない
+        assert_eq!(lint("UseKeychain yes\nIgnoreUnknown UseKeychain\n"), [(1, "unknown-keyword")]);
+    }
+
+    #[test]
+    fn include_does_not_capture_following_directives() {
+        let dir = tempfile::tempdir().unwrap();
+        let included = write_config(dir.path(), "included", "Host other\n  HostName o\n");
+        let config = format!("Host web\n  Include {}\n  User deploy\n", included.display());
+        let path = write_config(dir.path(), "config", &config);
+
+        let mut ctx = LintContext::default();
+        read_config_file(&mut ctx, &path, 0).unwrap();
+        let keywords = |patterns: &str| -> Vec<String> {
+            let block = ctx.blocks.iter().find(|b| b.patterns.as_deref() == Some(patterns)).unwrap();
+            block.directives.iter().map(|d| d.keyword.clone()).collect()
+        };
+        assert_eq!(keywords("web"), ["user"]);
+        assert_eq!(keywords("other"), ["hostname"]);
+    }
+
+    #[test]
+    fn include_at_top_level_keeps_later_lines_global() {
+        let dir = tempfile::tempdir().unwrap();
+        let included = write_config(dir.path(), "included", "Host web\n  User deploy\n");
+        let config = format!("Include {}\nUser root\n", included.display());
+        let path = write_config(dir.path(), "config", &config);
+
+        let mut ctx = LintContext::default();
+        read_config_file(&mut ctx, &path, 0).unwrap();
+        let web = ctx.blocks.iter().find(|b| b.patterns.as_deref() == Some("web")).unwrap();
+        assert_eq!(web.directives.len(), 1);
+        assert_eq!(ctx.blocks.last().unwrap().patterns.as_deref(), Some("*"));
+    }
+
+    #[test]
+    fn reports_missing_identity_file_and_key_permissions() {
+        let dir = tempfile::tempdir().unwrap();
+        let key = dir.path().join("id_test");
+        fs::write(&key, "").unwrap();
+        fs::set_permissions(&key, fs::Permissions::from_mode(0o600)).unwrap();
+        let config = format!(
+            "Host web\n  IdentityFile {}\n  IdentityFile {}/missing\n  IdentityFile ~/.ssh/id_%h\n",
+            key.display(),
+            dir.path().display()
+        );
+        let path = write_config(dir.path(), "config", &config);
+        let codes = |path: &Path| -> Vec<(usize, &'static str)> {
+            lint_file(path).unwrap().iter().map(|f| (f.line, f.code)).collect()
+        };
+        assert_eq!(codes(&path), [(3, "missing-identity")]);
+
+        fs::set_permissions(&key, fs::Permissions::from_mode(0o644)).unwrap();
+        assert_eq!(codes(&path), [(2, "key-permissions"), (3, "missing-identity")]);
+    }
+
+    #[test]
+    fn reports_plaintext_secrets_separated_by_tabs() {
+        let findings = lint("Host web\n  #pass\tsecret\n  #PASSPHRASE  key\n  #totp JBSWY3DP\n  #passwords are in the vault\n");
+        assert_eq!(
+            findings,
+            [(2, "plaintext-password"), (3, "plaintext-password"), (4, "plaintext-password")]
+        );
+    }
+
+    #[test]
+    fn reports_world_readable_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = write_config(dir.path(), "config", "Host web\n  HostName a\n");
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        let findings = lint_file(&path).unwrap();
+        assert_eq!(findings.len(), 1);
+        assert_eq!((findings[0].code, findings[0].severity), ("config-permissions", Severity::Warning));
+    }
+}
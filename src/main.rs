@@ -1,9 +1,27 @@
+mod agent;
+mod check;
 mod config;
+mod discover;
+mod doctor;
+mod history;
+mod importers;
+mod inventory;
+mod keys;
+mod lint;
+mod mux;
+mod record;
+mod rotate;
 mod sshr;
+mod session;
 mod sshct;
+mod sync;
+mod template;
+mod totp;
+mod tunnel;
 mod utils;
 
 use std::env;
+use std::io::{self, IsTerminal};
 
 fn main() {
     let args: Vec<String> = env::args().collect();
@@ -11,18 +29,41 @@ fn main() {
     // 実行されたバイナリ名を取得
     let binary_name = args[0]
         .split('/')
-        .last()
+        .next_back()
         .unwrap_or("unknown");
     
     match binary_name {
         "sshr" => {
-            if args.len() < 2 {
-                eprintln!("Usage: sshr <host>");
+            // トンネルの操作はサブコマンドとして扱う
+            if matches!(args.get(1).map(String::as_str), Some("tunnel" | "tunnels")) {
+                if let Err(e) = tunnel::run_tunnel_command(&args[1..]) {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+                return;
+            }
+            
+            let record = args[1..].iter().any(|a| a == "--record");
+            let hosts: Vec<&String> = args[1..].iter().filter(|a| *a != "--record").collect();
+            if hosts.len() > 1 || (hosts.is_empty() && !io::stdin().is_terminal()) {
+                eprintln!("Usage: sshr [--record] <host>");
+                eprintln!("       sshr tunnel <host> [<profile>] | sshr tunnel stop <host> [<profile>] | sshr tunnels");
                 std::process::exit(1);
             }
             
-            let host = &args[1];
-            if let Err(e) = sshr::run_sshr(host) {
+            // ホストを省略した場合は一覧から選ばせる
+            let host = match hosts.first() {
+                Some(host) => host.to_string(),
+                None => match sshr::pick_host() {
+                    Ok(Some(host)) => host,
+                    Ok(None) => std::process::exit(1),
+                    Err(e) => {
+                        eprintln!("Error: {}", e);
+                        std::process::exit(1);
+                    }
+                },
+            };
+            if let Err(e) = sshr::run_sshr(&host, record) {
                 eprintln!("Error: {}", e);
                 std::process::exit(1);
             }
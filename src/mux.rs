@@ -0,0 +1,145 @@
+use crate::config::{effective_value, ssh_effective_config, SshConfig};
+use crate::utils::home_dir;
+use std::fs;
+use std::io;
+use std::os::unix::fs::DirBuilderExt;
+use std::process::{Command, Stdio};
+
+/// `sshct mux enable` で設定する ControlPath（`%C` は接続先ごとのハッシュ）
+pub const CONTROL_PATH: &str = "~/.ssh/cm/%C";
+
+/// マスター接続を最後のセッションが終わってから残しておく既定の時間
+pub const DEFAULT_PERSIST: &str = "10m";
+
+/// ホストのマスター接続の状態
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MasterStatus {
+    /// ControlMaster / ControlPath が設定されていない
+    Disabled,
+    NotRunning,
+    Running(u32),
+}
+
+/// ssh -G の結果（Host * などの設定も含む）で多重化が有効か
+fn is_enabled(values: &[(String, String)]) -> bool {
+    let master = effective_value(values, "controlmaster").unwrap_or("false");
+    master != "false" && master != "no" && effective_value(values, "controlpath").is_some()
+}
+
+/// `ssh -O <command> <host>` を実行し、成功したかと stderr を返す
+fn control_command(host: &str, command: &str) -> io::Result<(bool, String)> {
+    // ssh_effective_config と同じく -F は付けず、/etc/ssh/ssh_config も読ませる
+    let output = Command::new("ssh")
+        .args(["-O", command, host])
+        .stdin(Stdio::null())
+        .output()?;
+    Ok((output.status.success(), String::from_utf8_lossy(&output.stderr).into_owned()))
+}
+
+/// `ssh -O check` の stderr（`Master running (pid=12345)`）からマスター接続の PID を読み取る
+pub fn parse_master_pid(message: &str) -> Option<u32> {
+    let (_, rest) = message.split_once("pid=")?;
+    rest.split(')').next()?.trim().parse().ok()
+}
+
+/// `values` は host の ssh -G の結果
+pub fn master_status(host: &str, values: &[(String, String)]) -> io::Result<MasterStatus> {
+    if !is_enabled(values) {
+        return Ok(MasterStatus::Disabled);
+    }
+    let (running, message) = control_command(host, "check")?;
+    Ok(match (running, parse_master_pid(&message)) {
+        (true, Some(pid)) => MasterStatus::Running(pid),
+        _ => MasterStatus::NotRunning,
+    })
+}
+
+/// 動いているマスター接続の PID（多重化していないか、動いていなければ None）
+pub fn running_master(host: &str, values: &[(String, String)]) -> Option<u32> {
+    match master_status(host, values) {
+        Ok(MasterStatus::Running(pid)) => Some(pid),
+        _ => None,
+    }
+}
+
+/// マスター接続を閉じる。閉じた場合は true を返す
+pub fn stop_master(host: &str) -> io::Result<bool> {
+    let values = ssh_effective_config(host).unwrap_or_default();
+    if running_master(host, &values).is_none() {
+        return Ok(false);
+    }
+    let (stopped, message) = control_command(host, "exit")?;
+    if !stopped {
+        return Err(io::Error::other(message.trim().to_string()));
+    }
+    Ok(true)
+}
+
+/// ssh -G の ControlPersist の値（秒数や yes/no）を表示用にする
+pub fn persist_label(values: &[(String, String)]) -> String {
+    match effective_value(values, "controlpersist") {
+        Some("no") | None => "no".to_string(),
+        Some(secs) if secs.parse::<u64>().is_ok() => format!("{}s", secs),
+        Some(value) => value.to_string(),
+    }
+}
+
+/// ホストの設定に ControlMaster / ControlPath / ControlPersist を書き込む。
+/// ControlPath のディレクトリは他人に読めないよう 0700 で作る
+pub fn enable(config: &mut SshConfig, persist: &str) -> io::Result<()> {
+    fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(home_dir()?.join(".ssh").join("cm"))?;
+    config.set_directive("ControlMaster", Some("auto"));
+    if config.control_path().is_none() {
+        config.set_directive("ControlPath", Some(CONTROL_PATH));
+    }
+    config.set_directive("ControlPersist", Some(persist));
+    Ok(())
+}
+
+pub fn disable(config: &mut SshConfig) {
+    for keyword in ["ControlMaster", "ControlPath", "ControlPersist"] {
+        config.set_directive(keyword, None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn detects_multiplexing_from_ssh_g_values() {
+        let path = ("controlpath", "/home/me/.ssh/cm/%C");
+        assert!(is_enabled(&values(&[("controlmaster", "auto"), path])));
+        assert!(is_enabled(&values(&[("controlmaster", "true"), path])));
+        assert!(is_enabled(&values(&[("controlmaster", "autoask"), path])));
+        assert!(!is_enabled(&values(&[("controlmaster", "no"), path])));
+        assert!(!is_enabled(&values(&[("controlmaster", "false"), path])));
+        assert!(!is_enabled(&values(&[path])));
+        assert!(!is_enabled(&values(&[("controlmaster", "auto")])));
+        assert!(!is_enabled(&values(&[("controlmaster", "auto"), ("controlpath", "none")])));
+    }
+
+    #[test]
+    fn labels_control_persist() {
+        assert_eq!(persist_label(&values(&[("controlpersist", "600")])), "600s");
+        assert_eq!(persist_label(&values(&[("controlpersist", "no")])), "no");
+        assert_eq!(persist_label(&values(&[("controlpersist", "yes")])), "yes");
+        assert_eq!(persist_label(&values(&[])), "no");
+    }
+
+    #[test]
+    fn parses_master_pid() {
+        assert_eq!(parse_master_pid("Master running (pid=12345)\r\n"), Some(12345));
+        assert_eq!(parse_master_pid("Master running (pid= 42 )"), Some(42));
+        assert_eq!(parse_master_pid("Control socket connect(/tmp/x): No such file or directory\n"), None);
+        assert_eq!(parse_master_pid("Master running (pid=abc)"), None);
+        assert_eq!(parse_master_pid(""), None);
+    }
+}
@@ -0,0 +1,236 @@
+use crate::utils::{data_dir, format_utc, unix_now};
+use std::env;
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+use std::path::PathBuf;
+use std::time::Instant;
+
+const REDACTED: &str = "********";
+
+/// 端末出力を asciicast v2 形式で記録する。
+///
+/// 記録するのは出力（"o" イベント）だけで、利用者の入力は記録しない。
+/// 保存済みのパスワードや自動入力した値が出力に現れた場合は伏せ字にする。
+/// 秘密情報を送った直後の出力は、相手がエコーしても残らないよう行末まで記録しない。
+pub struct Recorder {
+    path: PathBuf,
+    file: BufWriter<fs::File>,
+    start: Instant,
+    secrets: Vec<String>,
+    // 秘密情報の途中で出力が切れた場合に、続きが来るまで保留する文字列
+    held: String,
+    // UTF-8 の途中で切れたバイト列
+    partial: Vec<u8>,
+    // 秘密情報を送ってから、まだ行末が出力されていない
+    hiding: bool,
+}
+
+impl Recorder {
+    /// `~/.local/share/ssh_supporter/recordings/<host>-<日時>.cast` を作成してヘッダーを書く
+    pub fn create(host: &str, width: u16, height: u16) -> io::Result<Self> {
+        let dir = data_dir()?.join("recordings");
+        fs::DirBuilder::new().recursive(true).mode(0o700).create(&dir)?;
+
+        let now = unix_now();
+        let stamp: String = format_utc(now)
+            .chars()
+            .filter(|c| c.is_ascii_digit() || *c == ' ')
+            .map(|c| if c == ' ' { '-' } else { c })
+            .collect();
+        let name: String = host
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() || "-_.".contains(c) { c } else { '_' })
+            .collect();
+        Self::open(dir.join(format!("{}-{}.cast", name, stamp)), host, width, height)
+    }
+
+    pub(crate) fn open(path: PathBuf, host: &str, width: u16, height: u16) -> io::Result<Self> {
+        let now = unix_now();
+        let file = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&path)?;
+
+        let mut header = serde_json::json!({
+            "version": 2,
+            "width": width,
+            "height": height,
+            "timestamp": now,
+            "title": format!("sshr {}", host),
+        });
+        let env: serde_json::Map<String, serde_json::Value> = ["TERM", "SHELL"]
+            .iter()
+            .filter_map(|key| Some((key.to_string(), env::var(key).ok()?.into())))
+            .collect();
+        header["env"] = env.into();
+
+        let mut recorder = Self {
+            path,
+            file: BufWriter::new(file),
+            start: Instant::now(),
+            secrets: Vec::new(),
+            held: String::new(),
+            partial: Vec::new(),
+            hiding: false,
+        };
+        writeln!(recorder.file, "{}", header)?;
+        Ok(recorder)
+    }
+
+    /// 出力に現れたら伏せる文字列を追加する
+    pub fn add_secret(&mut self, secret: &str) {
+        if !secret.is_empty() && !self.secrets.iter().any(|s| s == secret) {
+            self.secrets.push(secret.to_string());
+        }
+    }
+
+    /// 秘密情報を送信した。エコーされても残らないよう、次の行末までの出力を記録しない
+    pub fn secret_sent(&mut self, secret: &str) {
+        self.add_secret(secret);
+        self.hiding = true;
+    }
+
+    fn event(&mut self, kind: &str, data: &str) -> io::Result<()> {
+        let time = self.start.elapsed().as_secs_f64();
+        let line = serde_json::to_string(&(time, kind, data)).map_err(io::Error::other)?;
+        writeln!(self.file, "{}", line)?;
+        // sshr が強制終了されても、それまでの記録は残るようにする
+        self.file.flush()
+    }
+
+    /// 端末に出力したバイト列を記録する
+    pub fn output(&mut self, mut data: &[u8]) -> io::Result<()> {
+        if self.hiding {
+            match data.iter().position(|&b| b == b'\r' || b == b'\n') {
+                Some(end) => {
+                    self.hiding = false;
+                    self.event("o", REDACTED)?;
+                    data = &data[end..];
+                }
+                None => return Ok(()),
+            }
+        }
+        self.partial.extend_from_slice(data);
+        let valid = match std::str::from_utf8(&self.partial) {
+            Ok(text) => text.len(),
+            // 末尾が文字の途中なら、そこまでを記録して残りは次回に回す
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(_) => self.partial.len(),
+        };
+        let bytes: Vec<u8> = self.partial.drain(..valid).collect();
+        self.held.push_str(&String::from_utf8_lossy(&bytes));
+
+        let mut text = std::mem::take(&mut self.held);
+        for secret in &self.secrets {
+            if text.contains(secret.as_str()) {
+                text = text.replace(secret.as_str(), REDACTED);
+            }
+        }
+
+        // 末尾が秘密情報の先頭部分と一致する場合は、続きを見てから記録する
+        let keep = self
+            .secrets
+            .iter()
+            .filter_map(|secret| {
+                (1..secret.len())
+                    .rev()
+                    .filter(|&n| secret.is_char_boundary(n))
+                    .find(|&n| text.ends_with(&secret[..n]))
+            })
+            .max()
+            .unwrap_or(0);
+        self.held = text.split_off(text.len() - keep);
+
+        if text.is_empty() {
+            return Ok(());
+        }
+        self.event("o", &text)
+    }
+
+    /// 端末サイズの変更を記録する
+    pub fn resize(&mut self, width: u16, height: u16) -> io::Result<()> {
+        self.event("r", &format!("{}x{}", width, height))
+    }
+
+    /// 保留中の出力を書き出してファイルを閉じる
+    pub fn finish(mut self) -> io::Result<PathBuf> {
+        let mut rest = std::mem::take(&mut self.held);
+        rest.push_str(&String::from_utf8_lossy(&std::mem::take(&mut self.partial)));
+        if !rest.is_empty() {
+            self.event("o", &rest)?;
+        }
+        self.file.flush()?;
+        Ok(self.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 記録した "o" イベントの文字列をつなげて返す
+    fn recorded_output(path: &std::path::Path) -> String {
+        fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .skip(1)
+            .filter_map(|line| {
+                let (_, kind, data): (f64, String, String) = serde_json::from_str(line).unwrap();
+                (kind == "o").then_some(data)
+            })
+            .collect()
+    }
+
+    fn record(secrets: &[&str], chunks: &[&[u8]]) -> String {
+        let dir = tempfile::tempdir().unwrap();
+        let mut recorder = Recorder::open(dir.path().join("test.cast"), "test", 80, 24).unwrap();
+        for secret in secrets {
+            recorder.add_secret(secret);
+        }
+        for chunk in chunks {
+            recorder.output(chunk).unwrap();
+        }
+        let path = recorder.finish().unwrap();
+        recorded_output(&path)
+    }
+
+    #[test]
+    fn writes_asciicast_header() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = Recorder::open(dir.path().join("test.cast"), "web", 120, 40).unwrap().finish().unwrap();
+        let header: serde_json::Value =
+            serde_json::from_str(fs::read_to_string(&path).unwrap().lines().next().unwrap()).unwrap();
+        assert_eq!(header["version"], 2);
+        assert_eq!(header["width"], 120);
+        assert_eq!(header["title"], "sshr web");
+    }
+
+    #[test]
+    fn redacts_short_secrets() {
+        assert_eq!(record(&["42"], &[b"pin is 42\r\n"]), "pin is ********\r\n");
+        assert_eq!(record(&["x"], &[b"xyz"]), "********yz");
+    }
+
+    #[test]
+    fn redacts_secret_split_across_chunks() {
+        assert_eq!(
+            record(&["hunter2"], &[b"password: hun", b"ter2\r\n$ "]),
+            "password: ********\r\n$ "
+        );
+    }
+
+    #[test]
+    fn hides_echo_after_secret_is_sent() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut recorder = Recorder::open(dir.path().join("test.cast"), "test", 80, 24).unwrap();
+        recorder.output(b"[sudo] password for deploy: ").unwrap();
+        recorder.secret_sent("pw");
+        // エコーされた値が伏せる対象と違っていても（末尾の空白など）記録しない
+        recorder.output(b"p").unwrap();
+        recorder.output(b"w \r\nroot# ").unwrap();
+        let path = recorder.finish().unwrap();
+        assert_eq!(recorded_output(&path), "[sudo] password for deploy: ********\r\nroot# ");
+    }
+}
@@ -0,0 +1,192 @@
+use crate::config::{is_managed_host, update_ssh_config, SshConfig};
+use crate::keys::{
+    generate_key, host_key_path, install_key_command, read_public_key, remove_key_command,
+    remove_key_files, KEY_ONLY_OPTIONS,
+};
+use crate::sshr::run_ssh;
+use crate::utils::{data_dir, expand_tilde, print_table, unix_now};
+use serde::Serialize;
+use ssh_key::PublicKey;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Outcome {
+    /// 新しい鍵に切り替え、古い鍵を authorized_keys から削除した
+    Rotated,
+    /// 新しい鍵に切り替えたが、古い鍵を削除できなかった
+    Partial,
+    /// 新しい鍵でログインできなかったので元に戻した
+    RolledBack,
+    /// 処理できず、元に戻せたかも確認できなかった
+    Failed,
+    Skipped,
+}
+
+/// 1 ホスト分のローテーション結果（rotations.jsonl に 1 行ずつ記録する）
+#[derive(Debug, Clone, Serialize)]
+pub struct Rotation {
+    pub time: u64,
+    pub host: String,
+    pub old_identity: Option<String>,
+    pub new_identity: Option<String>,
+    pub outcome: Outcome,
+    pub detail: String,
+}
+
+impl Rotation {
+    pub fn succeeded(&self) -> bool {
+        matches!(self.outcome, Outcome::Rotated | Outcome::Skipped)
+    }
+}
+
+/// ssh を実行し、終了コードが 0 でなければ理由をエラーとして返す
+fn run_remote(host: &str, options: &[&str], command: &str) -> Result<(), String> {
+    match run_ssh(host, options, Some(command), false) {
+        Ok(0) => Ok(()),
+        Ok(code) => Err(format!("ssh exited with {}", code)),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// ホストの鍵を新しい鍵に入れ替える。
+///
+/// 新しい鍵を追加 → config の IdentityFile を切り替え → 新しい鍵だけでログインを確認 →
+/// 古い鍵を authorized_keys から削除、の順に進める。確認に失敗した場合は
+/// config と authorized_keys を元に戻し、古い鍵でのログインを残す。
+/// 管理領域の外のホストは、`force` を指定しない限り何もしない。
+pub fn rotate_host(config: &SshConfig, force: bool) -> Rotation {
+    let mut rotation = Rotation {
+        time: unix_now(),
+        host: config.host.clone(),
+        old_identity: config.identity_file().map(str::to_string),
+        new_identity: None,
+        outcome: Outcome::Failed,
+        detail: String::new(),
+    };
+
+    if !force && !is_managed_host(&config.host).unwrap_or(false) {
+        rotation.outcome = Outcome::Skipped;
+        rotation.detail = "outside the ssh_supporter managed region; use --force".to_string();
+        return rotation;
+    }
+    let Some(old_identity) = config.identity_file() else {
+        rotation.outcome = Outcome::Skipped;
+        rotation.detail = "no IdentityFile; set one up with sshct keygen and deploy-key".to_string();
+        return rotation;
+    };
+    let old_key = match expand_tilde(old_identity).and_then(|path| read_public_key(&path)) {
+        Ok(key) => key,
+        Err(e) => {
+            rotation.detail = format!("cannot read the current key: {}", e);
+            return rotation;
+        }
+    };
+
+    // 新しい鍵は現在のパスフレーズで暗号化し、#passphrase をそのまま使えるようにする
+    let generated = host_key_path(&config.host, &format!("-{}", rotation.time)).and_then(|(identity, path)| {
+        let key = generate_key(&path, &format!("sshct:{}", config.host), config.passphrase.as_deref())?;
+        Ok((identity, path, key))
+    });
+    let (new_identity, new_path, new_key) = match generated {
+        Ok(generated) => generated,
+        Err(e) => {
+            rotation.detail = format!("cannot generate a new key: {}", e);
+            return rotation;
+        }
+    };
+    rotation.new_identity = Some(new_identity.clone());
+
+    // 1. 古い鍵（またはパスワード）でログインして新しい鍵を追加する
+    let install = install_key_command(&new_key).map_err(|e| e.to_string());
+    if let Err(e) = install.and_then(|command| run_remote(&config.host, &[], &command)) {
+        let _ = remove_key_files(&new_path);
+        rotation.new_identity = None;
+        rotation.outcome = Outcome::RolledBack;
+        rotation.detail = format!("could not install the new key ({})", e);
+        return rotation;
+    }
+
+    // 2. IdentityFile を切り替えて、新しい鍵だけでログインできるか確認する
+    let mut updated = config.clone();
+    updated.set_identity_file(new_identity);
+    let verified = update_ssh_config(&config.host, updated, force)
+        .map_err(|e| e.to_string())
+        .and_then(|_| run_remote(&config.host, KEY_ONLY_OPTIONS, "true"));
+    if let Err(e) = verified {
+        rotation.new_identity = None;
+        (rotation.outcome, rotation.detail) = match roll_back(config, &old_key, &new_key, &new_path, force) {
+            Ok(()) => (Outcome::RolledBack, format!("login with the new key failed ({})", e)),
+            Err(rollback) => (
+                Outcome::Failed,
+                format!("login with the new key failed ({}); rollback incomplete: {}", e, rollback),
+            ),
+        };
+        return rotation;
+    }
+
+    // 3. 新しい鍵でログインして古い鍵を削除する
+    let remove = remove_key_command(&old_key, &new_key).map_err(|e| e.to_string());
+    match remove.and_then(|command| run_remote(&config.host, &[], &command)) {
+        Ok(()) => {
+            rotation.outcome = Outcome::Rotated;
+            rotation.detail = format!("old key {} removed from authorized_keys", old_identity);
+        }
+        Err(e) => {
+            rotation.outcome = Outcome::Partial;
+            rotation.detail = format!("new key works, but the old key is still authorized ({})", e);
+        }
+    }
+    rotation
+}
+
+/// config を元に戻し、古い鍵でログインして新しい鍵を authorized_keys から削除する
+fn roll_back(
+    config: &SshConfig,
+    old_key: &PublicKey,
+    new_key: &PublicKey,
+    new_path: &Path,
+    force: bool,
+) -> Result<(), String> {
+    update_ssh_config(&config.host, config.clone(), force)
+        .map_err(|e| format!("cannot restore IdentityFile: {}", e))?;
+
+    let remove = remove_key_command(new_key, old_key).map_err(|e| e.to_string());
+    let remote = remove.and_then(|command| run_remote(&config.host, &[], &command));
+
+    // 新しい鍵が authorized_keys に残っても、秘密鍵がなければ使われない
+    remove_key_files(new_path).map_err(|e| format!("cannot delete {}: {}", new_path.display(), e))?;
+    remote.map_err(|e| format!("new key may remain in authorized_keys ({})", e))
+}
+
+/// 結果を `~/.local/share/ssh_supporter/rotations.jsonl` に追記する
+pub fn record_rotations(rotations: &[Rotation]) -> io::Result<PathBuf> {
+    let dir = data_dir()?;
+    fs::create_dir_all(&dir)?;
+    let path = dir.join("rotations.jsonl");
+    let mut file = fs::OpenOptions::new().create(true).append(true).open(&path)?;
+    for rotation in rotations {
+        let line = serde_json::to_string(rotation).map_err(io::Error::other)?;
+        writeln!(file, "{}", line)?;
+    }
+    Ok(path)
+}
+
+pub fn print_rotations(rotations: &[Rotation]) {
+    let rows: Vec<Vec<String>> = rotations
+        .iter()
+        .map(|r| {
+            let outcome = match r.outcome {
+                Outcome::Rotated => "rotated",
+                Outcome::Partial => "partial",
+                Outcome::RolledBack => "rolled-back",
+                Outcome::Failed => "FAILED",
+                Outcome::Skipped => "skipped",
+            };
+            vec![r.host.clone(), outcome.to_string(), r.detail.clone()]
+        })
+        .collect();
+    print_table(&["HOST", "RESULT", "DETAIL"], &rows);
+}
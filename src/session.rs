@@ -0,0 +1,434 @@
+use nix::errno::Errno;
+use nix::poll::{poll, PollFd, PollFlags, PollTimeout};
+use nix::pty::{forkpty, ForkptyResult, Winsize};
+use nix::sys::signal::{sigaction, SaFlags, SigAction, SigHandler, SigSet, Signal};
+use nix::sys::termios::{cfmakeraw, tcgetattr, tcsetattr, SetArg, Termios};
+use nix::sys::wait::{waitpid, WaitPidFlag, WaitStatus};
+use nix::unistd::{execvp, isatty, read, write, Pid};
+use crate::record::Recorder;
+use regex::{Captures, Regex};
+use std::ffi::CString;
+use std::io::{self, Write};
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, OwnedFd};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// プロンプト判定のために保持する出力の末尾の長さ
+const PENDING_LIMIT: usize = 2048;
+
+static WINDOW_CHANGED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn on_sigwinch(_: libc::c_int) {
+    WINDOW_CHANGED.store(true, Ordering::SeqCst);
+}
+
+/// プロンプトに対する応答
+pub enum Answer {
+    /// 文字列を送信する（末尾に改行を付ける）
+    Send(String),
+    /// パスワードなどの秘密情報を送信する（セッションの記録からは伏せる）
+    Secret(String),
+    /// 利用者にエコーなしで入力してもらい、それを送信する
+    AskUser,
+    /// このルールでは応答せず、次のルールに判定を譲る
+    Skip,
+}
+
+type Responder = Box<dyn FnMut(&Captures) -> Answer>;
+
+/// 出力に現れるプロンプト（正規表現）と、その応答方法の組
+pub struct PromptRule {
+    pattern: Regex,
+    responder: Responder,
+    after_login: bool,
+}
+
+impl PromptRule {
+    pub fn new(pattern: &str, responder: impl FnMut(&Captures) -> Answer + 'static) -> io::Result<Self> {
+        let pattern = Regex::new(pattern)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        Ok(Self {
+            pattern,
+            responder: Box::new(responder),
+            after_login: false,
+        })
+    }
+
+    /// ログイン中ではなく、ログイン後のシェル上で監視するルールにする
+    pub fn after_login(mut self) -> Self {
+        self.after_login = true;
+        self
+    }
+}
+
+/// 端末を raw モードにし、Drop 時に元の設定へ戻す
+struct RawModeGuard {
+    original: Termios,
+}
+
+impl RawModeGuard {
+    fn enter() -> Option<Self> {
+        let stdin = io::stdin();
+        if !isatty(stdin.as_raw_fd()).unwrap_or(false) {
+            return None;
+        }
+        let original = tcgetattr(stdin.as_fd()).ok()?;
+        let mut raw = original.clone();
+        cfmakeraw(&mut raw);
+        tcsetattr(stdin.as_fd(), SetArg::TCSANOW, &raw).ok()?;
+        Some(Self { original })
+    }
+}
+
+impl Drop for RawModeGuard {
+    fn drop(&mut self) {
+        let _ = tcsetattr(io::stdin().as_fd(), SetArg::TCSANOW, &self.original);
+    }
+}
+
+/// 中継中だけ SIGWINCH を捕まえ、終了時に元の設定へ戻す
+struct SigwinchGuard {
+    previous: Option<SigAction>,
+}
+
+impl SigwinchGuard {
+    fn install() -> Self {
+        let handler = SigAction::new(SigHandler::Handler(on_sigwinch), SaFlags::empty(), SigSet::empty());
+        let previous = unsafe { sigaction(Signal::SIGWINCH, &handler) }.ok();
+        Self { previous }
+    }
+}
+
+impl Drop for SigwinchGuard {
+    fn drop(&mut self) {
+        if let Some(previous) = &self.previous {
+            unsafe {
+                let _ = sigaction(Signal::SIGWINCH, previous);
+            }
+        }
+    }
+}
+
+pub fn terminal_size() -> Option<Winsize> {
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    let result = unsafe { libc::ioctl(io::stdout().as_raw_fd(), libc::TIOCGWINSZ, &mut size) };
+    if result != 0 || size.ws_col == 0 {
+        return None;
+    }
+    Some(Winsize {
+        ws_row: size.ws_row,
+        ws_col: size.ws_col,
+        ws_xpixel: size.ws_xpixel,
+        ws_ypixel: size.ws_ypixel,
+    })
+}
+
+fn resize_pty(master: &OwnedFd) -> Option<Winsize> {
+    let size = terminal_size()?;
+    unsafe {
+        libc::ioctl(master.as_raw_fd(), libc::TIOCSWINSZ, &size);
+    }
+    Some(size)
+}
+
+fn write_all(fd: BorrowedFd, mut data: &[u8]) -> io::Result<()> {
+    while !data.is_empty() {
+        match write(fd, data) {
+            Ok(n) => data = &data[n..],
+            Err(Errno::EINTR) => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(())
+}
+
+/// 利用者からエコーなしで 1 行読み取る（raw モード中でも動くよう 1 バイトずつ処理する）
+fn read_secret_from_user() -> io::Result<String> {
+    let stdin = io::stdin();
+    let mut secret = Vec::new();
+    let mut byte = [0u8; 1];
+    loop {
+        match read(stdin.as_raw_fd(), &mut byte) {
+            Ok(0) => break,
+            Ok(_) => match byte[0] {
+                b'\r' | b'\n' => break,
+                // Backspace / Delete
+                0x08 | 0x7f => {
+                    secret.pop();
+                }
+                // Ctrl-U
+                0x15 => secret.clear(),
+                b => secret.push(b),
+            },
+            Err(Errno::EINTR) => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(String::from_utf8_lossy(&secret).into_owned())
+}
+
+/// PTY 上でコマンドを実行し、出力を中継しながらプロンプトに自動応答する。
+///
+/// 通常のルールが有効なのはログイン中（利用者が最初にキー入力するまで）だけで、
+/// ログイン後のシェルに出たプロンプトに秘密情報を送ってしまうことはない。
+/// ログイン後は `after_login` を指定したルールだけを監視する。
+/// `recorder` を渡すと端末出力を記録する。戻り値は子プロセスの終了コード。
+pub fn run_session(
+    program: &str,
+    args: &[String],
+    mut rules: Vec<PromptRule>,
+    recorder: Option<&mut Recorder>,
+) -> io::Result<i32> {
+    // fork 後にメモリ確保しなくて済むよう、exec の引数は先に用意する
+    let to_cstring = |s: &str| {
+        CString::new(s).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+    };
+    let program_c = to_cstring(program)?;
+    let mut argv = vec![program_c.clone()];
+    for arg in args {
+        argv.push(to_cstring(arg)?);
+    }
+
+    let size = terminal_size();
+    let (child, master) = match unsafe { forkpty(size.as_ref(), None) }? {
+        ForkptyResult::Parent { child, master } => (child, master),
+        ForkptyResult::Child => {
+            let _ = execvp(&program_c, &argv);
+            unsafe { libc::_exit(127) };
+        }
+    };
+
+    let sigwinch_guard = SigwinchGuard::install();
+    let raw_guard = RawModeGuard::enter();
+    let result = relay(&master, child, &mut rules, recorder);
+    drop(raw_guard);
+    drop(sigwinch_guard);
+    match result? {
+        Some(code) => Ok(code),
+        None => wait_for_exit(child),
+    }
+}
+
+/// 終了した子プロセスの終了コード（まだ動いていれば None）
+fn exit_code(status: WaitStatus) -> Option<i32> {
+    match status {
+        WaitStatus::Exited(_, code) => Some(code),
+        WaitStatus::Signaled(_, signal, _) => Some(128 + signal as i32),
+        _ => None,
+    }
+}
+
+/// PTY の出力を端末に中継する。PTY が閉じるか、子プロセスが終了して出力が途切れたら戻る。
+///
+/// `ssh -f` のようにバックグラウンドに移ったプロセスが PTY を開いたままにしていると
+/// PTY は閉じないので、子プロセスの終了も見る。子プロセスを回収した場合はその終了コードを返す
+fn relay(
+    master: &OwnedFd,
+    child: Pid,
+    rules: &mut [PromptRule],
+    mut recorder: Option<&mut Recorder>,
+) -> io::Result<Option<i32>> {
+    let stdin = io::stdin();
+    let mut stdout = io::stdout();
+    let mut stdin_open = true;
+    let mut logging_in = true;
+    let watch_after_login = rules.iter().any(|r| r.after_login);
+    let mut pending = String::new();
+    let mut buf = [0u8; 4096];
+
+    loop {
+        if WINDOW_CHANGED.swap(false, Ordering::SeqCst) {
+            if let (Some(size), Some(recorder)) = (resize_pty(master), recorder.as_deref_mut()) {
+                recorder.resize(size.ws_col, size.ws_row)?;
+            }
+        }
+
+        let mut fds = vec![PollFd::new(master.as_fd(), PollFlags::POLLIN)];
+        if stdin_open {
+            fds.push(PollFd::new(stdin.as_fd(), PollFlags::POLLIN));
+        }
+        match poll(&mut fds, PollTimeout::from(200u16)) {
+            Ok(_) => {}
+            Err(Errno::EINTR) => continue,
+            Err(e) => return Err(e.into()),
+        }
+        let master_ready = fds[0].revents().is_some_and(|r| !r.is_empty());
+        let stdin_ready = stdin_open && fds[1].revents().is_some_and(|r| !r.is_empty());
+
+        // 出力がない間に子プロセスが終了していれば、残りの出力はもう来ない
+        if !master_ready {
+            match waitpid(child, Some(WaitPidFlag::WNOHANG)) {
+                Ok(status) => {
+                    if let Some(code) = exit_code(status) {
+                        drain(master, &mut stdout, recorder)?;
+                        return Ok(Some(code));
+                    }
+                }
+                Err(Errno::EINTR) => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        if master_ready {
+            let n = match read(master.as_raw_fd(), &mut buf) {
+                Ok(n) => n,
+                Err(Errno::EINTR) => continue,
+                // 子プロセスが終了すると EIO になる
+                Err(Errno::EIO) => 0,
+                Err(e) => return Err(e.into()),
+            };
+            if n == 0 {
+                return Ok(None);
+            }
+            stdout.write_all(&buf[..n])?;
+            stdout.flush()?;
+            if let Some(recorder) = recorder.as_deref_mut() {
+                recorder.output(&buf[..n])?;
+            }
+
+            if logging_in || watch_after_login {
+                pending.push_str(&String::from_utf8_lossy(&buf[..n]));
+                if pending.len() > PENDING_LIMIT {
+                    let mut cut = pending.len() - PENDING_LIMIT;
+                    while !pending.is_char_boundary(cut) {
+                        cut += 1;
+                    }
+                    pending.drain(..cut);
+                }
+                if let Some((reply, secret)) = answer_prompt(rules, !logging_in, &pending)? {
+                    pending.clear();
+                    // 相手がエコーしても記録に残らないようにする
+                    if let (true, Some(recorder)) = (secret, recorder.as_deref_mut()) {
+                        recorder.secret_sent(&reply);
+                    }
+                    write_all(master.as_fd(), reply.as_bytes())?;
+                    write_all(master.as_fd(), b"\r")?;
+                }
+            }
+        }
+
+        if stdin_ready {
+            match read(stdin.as_raw_fd(), &mut buf) {
+                Ok(0) => stdin_open = false,
+                Ok(n) => {
+                    // 利用者が入力を始めたらログインは終わったとみなす
+                    logging_in = false;
+                    pending.clear();
+                    write_all(master.as_fd(), &buf[..n])?;
+                }
+                Err(Errno::EINTR) => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+/// すでに PTY に届いている出力を待たずに書き出す
+fn drain(master: &OwnedFd, stdout: &mut io::Stdout, mut recorder: Option<&mut Recorder>) -> io::Result<()> {
+    let mut buf = [0u8; 4096];
+    loop {
+        let mut fds = [PollFd::new(master.as_fd(), PollFlags::POLLIN)];
+        if !matches!(poll(&mut fds, PollTimeout::ZERO), Ok(1)) {
+            return Ok(());
+        }
+        let n = match read(master.as_raw_fd(), &mut buf) {
+            Ok(n) => n,
+            Err(Errno::EINTR) => continue,
+            Err(_) => 0,
+        };
+        if n == 0 {
+            return Ok(());
+        }
+        stdout.write_all(&buf[..n])?;
+        stdout.flush()?;
+        if let Some(recorder) = recorder.as_deref_mut() {
+            recorder.output(&buf[..n])?;
+        }
+    }
+}
+
+/// 最初にマッチしたルールの応答と、それが秘密情報かどうかを返す。応答不要なら None
+fn answer_prompt(rules: &mut [PromptRule], after_login: bool, pending: &str) -> io::Result<Option<(String, bool)>> {
+    for rule in rules.iter_mut().filter(|r| r.after_login == after_login) {
+        let Some(caps) = rule.pattern.captures(pending) else {
+            continue;
+        };
+        match (rule.responder)(&caps) {
+            Answer::Send(text) => return Ok(Some((text, false))),
+            Answer::Secret(text) => return Ok(Some((text, true))),
+            Answer::AskUser => return read_secret_from_user().map(|text| Some((text, true))),
+            Answer::Skip => continue,
+        }
+    }
+    Ok(None)
+}
+
+fn wait_for_exit(child: Pid) -> io::Result<i32> {
+    loop {
+        match waitpid(child, None) {
+            Ok(status) => {
+                if let Some(code) = exit_code(status) {
+                    return Ok(code);
+                }
+            }
+            Err(Errno::EINTR) => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use std::time::{Duration, Instant};
+
+    // SIGWINCH の設定はプロセス全体で共有されるので、セッションを並行して動かさない
+    static SESSION_LOCK: Mutex<()> = Mutex::new(());
+
+    fn run_sh(script: &str, rules: Vec<PromptRule>) -> i32 {
+        let _lock = SESSION_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        run_session("sh", &["-c".to_string(), script.to_string()], rules, None).unwrap()
+    }
+
+    #[test]
+    fn returns_exit_code() {
+        assert_eq!(run_sh("exit 3", Vec::new()), 3);
+    }
+
+    #[test]
+    fn relays_output_written_just_before_exit() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut recorder = Recorder::open(dir.path().join("test.cast"), "test", 80, 24).unwrap();
+        let script = "setsid sleep 5 & sleep 0.2; printf 'done'; exit 0";
+        let _lock = SESSION_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        run_session("sh", &["-c".to_string(), script.to_string()], Vec::new(), Some(&mut recorder)).unwrap();
+        let path = recorder.finish().unwrap();
+        assert!(std::fs::read_to_string(path).unwrap().contains("done"));
+    }
+
+    #[test]
+    fn returns_when_background_process_keeps_pty_open() {
+        // ssh -f と同じく、別のセッションに移って PTY を開いたままのプロセスを残して終了する
+        let start = Instant::now();
+        assert_eq!(run_sh("setsid sleep 5 & sleep 0.2; exit 7", Vec::new()), 7);
+        assert!(start.elapsed() < Duration::from_secs(3), "{:?}", start.elapsed());
+    }
+
+    #[test]
+    fn answers_prompt() {
+        let rule = PromptRule::new(r"Password: $", |_| Answer::Secret("s3cret".to_string())).unwrap();
+        let code = run_sh(r#"stty -echo; printf 'Password: '; read -r pw; [ "$pw" = s3cret ]"#, vec![rule]);
+        assert_eq!(code, 0);
+    }
+
+    #[test]
+    fn restores_sigwinch_disposition() {
+        let ignore = SigAction::new(SigHandler::SigIgn, SaFlags::empty(), SigSet::empty());
+        let default = SigAction::new(SigHandler::SigDfl, SaFlags::empty(), SigSet::empty());
+        let _lock = SESSION_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        unsafe { sigaction(Signal::SIGWINCH, &ignore) }.unwrap();
+        run_session("sh", &["-c".to_string(), "exit 0".to_string()], Vec::new(), None).unwrap();
+        let current = unsafe { sigaction(Signal::SIGWINCH, &default) }.unwrap();
+        assert_eq!(current.handler(), SigHandler::SigIgn);
+    }
+}
@@ -1,26 +1,72 @@
-use crate::config::{add_ssh_config, find_config_by_host, update_ssh_config, SshConfig};
-use crate::sshr::run_sshr;
-use dialoguer::{Input, Confirm};
+use crate::check::{check_hosts, print_results, DEFAULT_TIMEOUT_SECS};
+use crate::config::{
+    add_ssh_config, canonical_keyword, find_config_by_host, parse_duration_secs, parse_ssh_config, parse_tags,
+    is_managed_host, is_typed_keyword, managed_hosts, pattern_matches, resolve_jump_chain, ssh_effective_config,
+    update_ssh_config,
+    AgentOptions, EnvironmentLabel, ForwardKind, PromptRuleSpec, RuleResponse, SshConfig, TunnelProfile,
+};
+use crate::discover::{discover, Candidate};
+use crate::doctor::{print_checks, run_checks, Status};
+use crate::lint::{lint_config, print_findings, Severity};
+use crate::mux::{self, MasterStatus};
+use crate::totp::base32_decode;
+use crate::history::{self, format_duration, host_stats};
+use crate::inventory::{export_hosts, import_preview, overwrite_existing, read_inventory, ExportFormat, Format};
+use crate::keys::{generate_key, host_key_path, install_key_command, read_public_key, KEY_ONLY_OPTIONS};
+use crate::rotate::{print_rotations, record_rotations, rotate_host};
+use crate::sshr::{run_ssh, run_sshr};
+use crate::sync::{self, read_team_inventory};
+use crate::template::{is_valid_name, list_templates, load_template, save_template, template_path, Template};
+use crate::utils::{expand_tilde, format_utc, print_table, unix_now, write_atomic};
+use dialoguer::{Input, Confirm, MultiSelect, Select};
 use rpassword::read_password;
-use std::io::{self, Write};
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 pub fn run_sshct(args: &[String]) -> io::Result<()> {
     if args.is_empty() {
-        eprintln!("Usage: sshct <new|show|edit> [server_name]");
+        eprintln!("Usage: sshct <new|show|edit|list|lint|doctor|check|history|stats|keygen|deploy-key|rotate-key|import|export|discover|sync|template|mux> [server_name]");
         std::process::exit(1);
     }
     
     match args[0].as_str() {
         "new" => {
-            let server_name = if args.len() > 1 {
-                args[1].clone()
-            } else {
+            let usage = "Usage: sshct new [server_name] [--template <name>]";
+            let mut server_name: Option<String> = None;
+            let mut template_name: Option<String> = None;
+            let mut iter = args[1..].iter();
+            while let Some(arg) = iter.next() {
+                match arg.as_str() {
+                    "--template" | "-t" => template_name = iter.next().cloned(),
+                    _ if server_name.is_none() && !arg.starts_with('-') => server_name = Some(arg.clone()),
+                    _ => {
+                        eprintln!("{}", usage);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            
+            let template = match &template_name {
+                Some(name) => match load_template(name)? {
+                    Some(template) => Some(template),
+                    None => {
+                        eprintln!("Error: template '{}' not found", name);
+                        eprintln!("Save one with: sshct template save <server_name> {}", name);
+                        std::process::exit(1);
+                    }
+                },
+                None => None,
+            };
+            let server_name = server_name.unwrap_or_else(|| {
                 Input::<String>::new()
                     .with_prompt("input client name(must)")
                     .interact_text()
                     .unwrap()
-            };
-            sshct_new(&server_name)
+            });
+            sshct_new(&server_name, template.as_ref())
         }
         "show" => {
             if args.len() < 2 {
@@ -31,52 +77,115 @@ pub fn run_sshct(args: &[String]) -> io::Result<()> {
         }
         "edit" => {
             if args.len() < 2 {
-                eprintln!("Usage: sshct edit <server_name>");
+                eprintln!("Usage: sshct edit <server_name> [--force]");
+                std::process::exit(1);
+            }
+            let force = args[2..].iter().any(|a| a == "--force");
+            sshct_edit(&args[1], force)
+        }
+        "list" => sshct_list(&args[1..]),
+        "lint" => {
+            let json = args[1..].iter().any(|a| a == "--json");
+            let strict = args[1..].iter().any(|a| a == "--strict");
+            sshct_lint(json, strict)
+        }
+        "doctor" => sshct_doctor(),
+        "check" => sshct_check(&args[1..]),
+        "history" => sshct_history(&args[1..]),
+        "stats" => sshct_stats(&args[1..]),
+        "keygen" => {
+            if args.len() < 2 {
+                eprintln!("Usage: sshct keygen <server_name> [--force]");
+                std::process::exit(1);
+            }
+            let force = args[2..].iter().any(|a| a == "--force");
+            sshct_keygen(&args[1], force)
+        }
+        "deploy-key" => {
+            if args.len() < 2 {
+                eprintln!("Usage: sshct deploy-key <server_name> [--force]");
                 std::process::exit(1);
             }
-            sshct_edit(&args[1])
+            let force = args[2..].iter().any(|a| a == "--force");
+            sshct_deploy_key(&args[1], force)
         }
+        "rotate-key" => sshct_rotate_key(&args[1..]),
+        "import" => sshct_import(&args[1..]),
+        "export" => sshct_export(&args[1..]),
+        "discover" => sshct_discover(&args[1..]),
+        "sync" => sshct_sync(&args[1..]),
+        "template" => sshct_template(&args[1..]),
+        "mux" => sshct_mux(&args[1..]),
         _ => {
             eprintln!("Unknown command: {}", args[0]);
-            eprintln!("Available commands: new, show, edit");
+            eprintln!("Available commands: new, show, edit, list, lint, doctor, check, history, stats, keygen, deploy-key, rotate-key, import, export, discover, sync, template, mux");
             std::process::exit(1);
         }
     }
 }
 
-fn sshct_new(server_name: &str) -> io::Result<()> {
+fn sshct_new(server_name: &str, template: Option<&Template>) -> io::Result<()> {
     println!("Creating new SSH configuration for '{}'", server_name);
     
+    // テンプレートの値は `[値]` として表示し、空のまま Enter で採用する
+    let base = match template {
+        Some(template) => template.to_config(server_name),
+        None => SshConfig::new(server_name.to_string()),
+    };
+    
     let hostname: String = Input::new()
         .with_prompt("input host name(must)")
         .interact_text()
         .unwrap();
     
-    let username: String = Input::new()
-        .with_prompt("input user name(must)")
-        .interact_text()
-        .unwrap();
+    let username = prompt_with_default("input user name(must)", base.user.as_deref(), true);
     
-    let port: String = Input::new()
-        .with_prompt("input port number")
-        .allow_empty(true)
-        .interact_text()
-        .unwrap();
+    let port = prompt_with_default("input port number", base.port.as_deref(), false);
     
-    let identity_file: String = Input::new()
-        .with_prompt("input IdentityFile path")
-        .allow_empty(true)
-        .interact_text()
-        .unwrap();
+    // テンプレートに鍵が複数ある場合は、最初の鍵だけを入力で置き換えられる
+    let identity_file = prompt_with_default("input IdentityFile path", base.identity_file(), false);
+    
+    let mut passphrase = String::new();
+    let mut agent_options = base.agent_options.clone();
+    if !identity_file.is_empty() {
+        print!("input key passphrase (loaded into ssh-agent by sshr): ");
+        io::stdout().flush()?;
+        passphrase = read_password()?;
+        if !passphrase.is_empty() {
+            agent_options = prompt_agent_options(&agent_options);
+        }
+    }
     
     print!("input password: ");
     io::stdout().flush()?;
     let password = read_password()?;
     
-    let proxy_command: String = Input::new()
-        .with_prompt("input ProxyCommand")
-        .allow_empty(true)
-        .interact_text()
+    let auto_sudo = !password.is_empty()
+        && Confirm::new()
+            .with_prompt("enter this password at sudo prompts after login?")
+            .default(false)
+            .interact()
+            .unwrap();
+    
+    let totp_secret = prompt_totp_secret("input TOTP secret (base32): ")?;
+    
+    let proxy_command = prompt_with_default("input ProxyCommand", base.proxy_command.as_deref(), false);
+    
+    let proxy_jump = prompt_proxy_jump(server_name, base.proxy_jump.as_deref())?;
+    
+    let current_tags = base.tags.join(",");
+    let tags = prompt_with_default(
+        "input tags (comma separated)",
+        Some(current_tags.as_str()).filter(|t| !t.is_empty()),
+        false,
+    );
+    
+    let environment = prompt_environment(base.environment.as_ref());
+    
+    let record = Confirm::new()
+        .with_prompt("record sessions to this host?")
+        .default(base.record)
+        .interact()
         .unwrap();
     
     let mut config = SshConfig::new(server_name.to_string());
@@ -86,15 +195,28 @@ fn sshct_new(server_name: &str) -> io::Result<()> {
     if !port.is_empty() {
         config.port = Some(port);
     }
+    config.identity_files = base.identity_files.clone();
     if !identity_file.is_empty() {
-        config.identity_file = Some(identity_file);
+        config.set_identity_file(identity_file);
+    }
+    if !passphrase.is_empty() {
+        config.passphrase = Some(passphrase);
+        config.agent_options = agent_options;
     }
     if !password.is_empty() {
         config.password = Some(password);
     }
+    config.auto_sudo = auto_sudo;
+    if !totp_secret.is_empty() {
+        config.totp_secret = Some(totp_secret);
+    }
     if !proxy_command.is_empty() {
         config.proxy_command = Some(proxy_command);
     }
+    config.proxy_jump = proxy_jump;
+    config.tags = parse_tags(&tags);
+    config.environment = environment;
+    config.record = record;
     
     add_ssh_config(config)?;
     println!("\nSSH configuration for '{}' has been added successfully!", server_name);
@@ -102,6 +224,487 @@ fn sshct_new(server_name: &str) -> io::Result<()> {
     Ok(())
 }
 
+/// 初期値があれば `[値]` として表示し、空のまま Enter ならその値を返す
+fn prompt_with_default(prompt: &str, default: Option<&str>, required: bool) -> String {
+    let prompt = match default {
+        Some(default) => format!("{} [{}]", prompt, default),
+        None => prompt.to_string(),
+    };
+    let value: String = Input::new()
+        .with_prompt(prompt)
+        .allow_empty(!required || default.is_some())
+        .interact_text()
+        .unwrap();
+    if value.is_empty() {
+        default.unwrap_or_default().to_string()
+    } else {
+        value
+    }
+}
+
+/// ssh-agent に鍵を登録するときの制約（有効期限と使用時の確認）を入力させる
+fn prompt_agent_options(current: &AgentOptions) -> AgentOptions {
+    let current_lifetime = current.lifetime.map(|l| l.to_string()).unwrap_or_default();
+    let lifetime: String = Input::new()
+        .with_prompt(format!("input agent key lifetime (e.g. 3600, 30m, 8h) [{}]", current_lifetime))
+        .allow_empty(true)
+        .validate_with(|value: &String| -> Result<(), String> {
+            if value.is_empty() || parse_duration_secs(value).is_some() {
+                Ok(())
+            } else {
+                Err("lifetime must be a number of seconds, optionally suffixed with s/m/h/d".to_string())
+            }
+        })
+        .interact_text()
+        .unwrap();
+    
+    let confirm = Confirm::new()
+        .with_prompt("require confirmation each time the agent uses the key?")
+        .default(current.confirm)
+        .interact()
+        .unwrap();
+    
+    AgentOptions {
+        lifetime: parse_duration_secs(&lifetime).or(current.lifetime),
+        confirm,
+    }
+}
+
+/// 環境ラベル（`prod` や `staging bg=#3a0000` など）を入力させる。`-` で削除する
+fn prompt_environment(current: Option<&EnvironmentLabel>) -> Option<EnvironmentLabel> {
+    let prompt = match current {
+        Some(current) => format!("input environment label, '-' to clear [{}]", current),
+        None => "input environment label (e.g. prod, staging bg=#3a3a00)".to_string(),
+    };
+    let value: String = Input::new()
+        .with_prompt(prompt)
+        .allow_empty(true)
+        .validate_with(|value: &String| -> Result<(), String> {
+            if value.is_empty() || value == "-" || EnvironmentLabel::parse(value).is_some() {
+                Ok(())
+            } else {
+                Err("expected: <label> [bg=<colour>]".to_string())
+            }
+        })
+        .interact_text()
+        .unwrap();
+    
+    match value.as_str() {
+        "" => current.cloned(),
+        "-" => None,
+        _ => EnvironmentLabel::parse(&value),
+    }
+}
+
+/// 認証コード用のシードを非表示で入力させる（空なら設定しない）
+fn prompt_totp_secret(prompt: &str) -> io::Result<String> {
+    loop {
+        print!("{}", prompt);
+        io::stdout().flush()?;
+        let secret = read_password()?.replace(' ', "").to_uppercase();
+        if secret.is_empty() || base32_decode(&secret).is_some() {
+            return Ok(secret);
+        }
+        eprintln!("TOTP secret must be base32 (A-Z, 2-7)");
+    }
+}
+
+/// ホストのプロンプト応答ルールを対話的に追加・削除する
+fn edit_prompt_rules(config: &mut SshConfig) {
+    loop {
+        println!();
+        for (i, rule) in config.prompt_rules.iter().enumerate() {
+            println!("  {}: {}", i + 1, rule.to_config_line());
+        }
+        
+        let action = Select::new()
+            .with_prompt("prompt rules")
+            .items(&["add rule", "remove rule", "done"])
+            .default(2)
+            .interact()
+            .unwrap();
+        
+        match action {
+            0 => {
+                let pattern: String = Input::new()
+                    .with_prompt("input prompt regex")
+                    .validate_with(|value: &String| -> Result<(), String> {
+                        regex::Regex::new(value).map(|_| ()).map_err(|e| e.to_string())
+                    })
+                    .interact_text()
+                    .unwrap();
+                
+                let after_login = Select::new()
+                    .with_prompt("when")
+                    .items(&["while logging in", "after login (e.g. sudo)"])
+                    .default(0)
+                    .interact()
+                    .unwrap()
+                    == 1;
+                
+                let kinds = [
+                    "enter (empty line)",
+                    "send text",
+                    "secret (this host's password)",
+                    "secret of another host",
+                    "totp code",
+                    "ask interactively",
+                ];
+                let response = match Select::new()
+                    .with_prompt("response")
+                    .items(&kinds)
+                    .default(0)
+                    .interact()
+                    .unwrap()
+                {
+                    0 => RuleResponse::Enter,
+                    1 => RuleResponse::Send(
+                        Input::new().with_prompt("input text to send").interact_text().unwrap(),
+                    ),
+                    2 => RuleResponse::Secret(None),
+                    3 => RuleResponse::Secret(Some(
+                        Input::new().with_prompt("input host alias").interact_text().unwrap(),
+                    )),
+                    4 => RuleResponse::Totp,
+                    _ => RuleResponse::Ask,
+                };
+                
+                config.prompt_rules.push(PromptRuleSpec {
+                    after_login,
+                    pattern,
+                    response,
+                });
+            }
+            1 => {
+                if config.prompt_rules.is_empty() {
+                    continue;
+                }
+                let items: Vec<String> = config
+                    .prompt_rules
+                    .iter()
+                    .map(|r| r.to_config_line())
+                    .collect();
+                let index = Select::new()
+                    .with_prompt("remove which rule?")
+                    .items(&items)
+                    .interact()
+                    .unwrap();
+                config.prompt_rules.remove(index);
+            }
+            _ => break,
+        }
+    }
+}
+
+/// `sshr tunnel` で使うポート転送のプロファイルを対話的に追加・削除する
+fn edit_tunnels(config: &mut SshConfig) {
+    loop {
+        println!();
+        for (i, tunnel) in config.tunnels.iter().enumerate() {
+            println!("  {}: {}  {}", i + 1, tunnel.name, tunnel.forwards_string());
+        }
+        
+        let action = Select::new()
+            .with_prompt("tunnel profiles")
+            .items(&["add profile", "remove profile", "done"])
+            .default(2)
+            .interact()
+            .unwrap();
+        
+        match action {
+            0 => {
+                let name: String = Input::new()
+                    .with_prompt("input profile name (e.g. db-tunnel)")
+                    .validate_with(|value: &String| -> Result<(), String> {
+                        if !is_valid_name(value) {
+                            Err("use letters, digits, '-', '_' and '.' (not starting with '.')".to_string())
+                        } else {
+                            Ok(())
+                        }
+                    })
+                    .interact_text()
+                    .unwrap();
+                let forwards: String = Input::new()
+                    .with_prompt("input forwards (e.g. L 5432:db.internal:5432 D 1080)")
+                    .validate_with(|value: &String| -> Result<(), String> {
+                        TunnelProfile::parse(&format!("{} {}", name, value))
+                            .map(|_| ())
+                            .ok_or_else(|| "use <L|R|D> <spec> pairs, as for ssh -L/-R/-D".to_string())
+                    })
+                    .interact_text()
+                    .unwrap();
+                if let Some(tunnel) = TunnelProfile::parse(&format!("{} {}", name, forwards)) {
+                    // 同じ名前のプロファイルは置き換える
+                    match config.tunnels.iter().position(|t| t.name == tunnel.name) {
+                        Some(index) => config.tunnels[index] = tunnel,
+                        None => config.tunnels.push(tunnel),
+                    }
+                }
+            }
+            1 => {
+                if config.tunnels.is_empty() {
+                    continue;
+                }
+                let items: Vec<String> = config
+                    .tunnels
+                    .iter()
+                    .map(|t| format!("{}  {}", t.name, t.forwards_string()))
+                    .collect();
+                let index = Select::new()
+                    .with_prompt("remove which profile?")
+                    .items(&items)
+                    .default(0)
+                    .interact()
+                    .unwrap();
+                config.tunnels.remove(index);
+            }
+            _ => break,
+        }
+    }
+}
+
+/// IdentityFile の一覧を対話的に追加・削除・並べ替える（ssh は上から順に試す）
+fn edit_identity_files(config: &mut SshConfig) {
+    loop {
+        println!();
+        for (i, identity_file) in config.identity_files.iter().enumerate() {
+            let note = if i == 0 { " (tried first; #passphrase applies to this key)" } else { "" };
+            println!("  {}: {}{}", i + 1, identity_file, note);
+        }
+        
+        let action = Select::new()
+            .with_prompt("IdentityFile")
+            .items(&["add key", "remove key", "move key up", "done"])
+            .default(3)
+            .interact()
+            .unwrap();
+        
+        match action {
+            0 => {
+                let identity_file: String = Input::new()
+                    .with_prompt("input IdentityFile path")
+                    .interact_text()
+                    .unwrap();
+                if !config.identity_files.contains(&identity_file) {
+                    config.identity_files.push(identity_file);
+                }
+            }
+            1 => {
+                if config.identity_files.is_empty() {
+                    continue;
+                }
+                let index = Select::new()
+                    .with_prompt("remove which key?")
+                    .items(&config.identity_files)
+                    .default(0)
+                    .interact()
+                    .unwrap();
+                config.identity_files.remove(index);
+            }
+            2 => {
+                if config.identity_files.len() < 2 {
+                    continue;
+                }
+                // 先頭の鍵はこれ以上上に移動できないので、2 番目以降から選ばせる
+                let index = Select::new()
+                    .with_prompt("move which key up?")
+                    .items(&config.identity_files[1..])
+                    .default(0)
+                    .interact()
+                    .unwrap()
+                    + 1;
+                config.identity_files.swap(index - 1, index);
+            }
+            _ => break,
+        }
+    }
+}
+
+/// HostName などの専用項目以外のディレクティブを対話的に追加・変更・削除する
+fn edit_directives(config: &mut SshConfig) {
+    loop {
+        println!();
+        for (i, directive) in config.directives.iter().enumerate() {
+            println!("  {}: {} {}", i + 1, directive.keyword, directive.value);
+        }
+        
+        let action = Select::new()
+            .with_prompt("directives")
+            .items(&["add directive", "modify directive", "remove directive", "move directive up", "done"])
+            .default(4)
+            .interact()
+            .unwrap();
+        
+        let items: Vec<String> = config
+            .directives
+            .iter()
+            .map(|d| format!("{} {}", d.keyword, d.value))
+            .collect();
+        match action {
+            0 => {
+                let keyword: String = Input::new()
+                    .with_prompt("input keyword (e.g. LocalForward, ServerAliveInterval)")
+                    .validate_with(|value: &String| -> Result<(), String> {
+                        if value.contains(|c: char| c.is_whitespace() || c == '=') {
+                            Err("keyword must be a single word".to_string())
+                        } else if value.eq_ignore_ascii_case("match") || is_typed_keyword(value) {
+                            Err(format!("{} is edited by its own prompt", value))
+                        } else {
+                            Ok(())
+                        }
+                    })
+                    .interact_text()
+                    .unwrap();
+                let value: String = Input::new()
+                    .with_prompt(format!("input {} value", canonical_keyword(&keyword)))
+                    .interact_text()
+                    .unwrap();
+                // 複数回書けないキーワードは既存の値を置き換える
+                config.add_directive(&keyword, &value);
+            }
+            1 => {
+                if items.is_empty() {
+                    continue;
+                }
+                let index = Select::new()
+                    .with_prompt("modify which directive?")
+                    .items(&items)
+                    .default(0)
+                    .interact()
+                    .unwrap();
+                let directive = &mut config.directives[index];
+                let value: String = Input::new()
+                    .with_prompt(format!("input {} value", directive.keyword))
+                    .with_initial_text(directive.value.clone())
+                    .interact_text()
+                    .unwrap();
+                directive.value = value.trim().to_string();
+            }
+            2 => {
+                if items.is_empty() {
+                    continue;
+                }
+                let index = Select::new()
+                    .with_prompt("remove which directive?")
+                    .items(&items)
+                    .default(0)
+                    .interact()
+                    .unwrap();
+                config.directives.remove(index);
+            }
+            3 => {
+                if items.len() < 2 {
+                    continue;
+                }
+                // 同じキーワードが複数ある場合（LocalForward など）は書かれている順に使われる
+                let index = Select::new()
+                    .with_prompt("move which directive up?")
+                    .items(&items[1..])
+                    .default(0)
+                    .interact()
+                    .unwrap()
+                    + 1;
+                config.directives.swap(index - 1, index);
+            }
+            _ => break,
+        }
+    }
+}
+
+/// よく使うディレクティブの設定内容を短くまとめて表示する
+fn print_directive_summary(config: &SshConfig) {
+    let mut lines = Vec::new();
+    for (kind, spec) in config.forwards() {
+        let kind = match kind {
+            ForwardKind::Local => "local",
+            ForwardKind::Remote => "remote",
+            ForwardKind::Dynamic => "dynamic (SOCKS)",
+        };
+        lines.push(format!("forward {}: {}", kind, spec));
+    }
+    if config.forward_agent() == Some(true) {
+        lines.push("agent forwarding: on".to_string());
+    }
+    if config.identities_only() == Some(true) {
+        lines.push("only the configured IdentityFile is offered".to_string());
+    }
+    if let Some(interval) = config.server_alive_interval() {
+        lines.push(format!("keepalive: every {}s", interval));
+    }
+    if let Some(checking) = config.strict_host_key_checking() {
+        lines.push(format!("host key checking: {}", checking));
+    }
+    if let Some(master) = config.control_master() {
+        let mut sharing = format!("connection sharing: ControlMaster {}", master);
+        if let Some(path) = config.control_path() {
+            sharing.push_str(&format!(", socket {}", path));
+        }
+        if let Some(persist) = config.control_persist() {
+            sharing.push_str(&format!(", persist {}", persist));
+        }
+        lines.push(sharing);
+    }
+    let set_env: Vec<String> = config.set_env().iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+    if !set_env.is_empty() {
+        lines.push(format!("remote environment: {}", set_env.join(" ")));
+    }
+    if let Some(tty) = config.request_tty() {
+        lines.push(format!("request tty: {}", tty));
+    }
+    
+    if !lines.is_empty() {
+        for line in lines {
+            println!("  - {}", line);
+        }
+        println!();
+    }
+}
+
+/// 既存のエイリアスを踏み台の候補として表示し、ProxyJump を入力させる。
+/// 現在の値を入力欄に入れておき、空にするか `none` を入力すると踏み台なし（None）にする
+fn prompt_proxy_jump(server_name: &str, current: Option<&str>) -> io::Result<Option<String>> {
+    let configs: Vec<SshConfig> = parse_ssh_config()?
+        .into_iter()
+        .filter(|c| c.host != server_name)
+        .collect();
+    
+    let candidates: Vec<&str> = configs
+        .iter()
+        .map(|c| c.host.as_str())
+        .filter(|h| !h.contains('*') && !h.contains('?'))
+        .collect();
+    if !candidates.is_empty() {
+        println!("available jump hosts: {}", candidates.join(", "));
+    }
+    
+    let mut input = Input::<String>::new()
+        .with_prompt("input ProxyJump (comma separated, empty or 'none' for no jump host)")
+        .allow_empty(true);
+    if let Some(current) = current {
+        input = input.with_initial_text(current);
+    }
+    let proxy_jump = input
+        .validate_with(|value: &String| -> Result<(), String> {
+            if is_no_jump(value) {
+                return Ok(());
+            }
+            // 入力されたチェーンが自分自身に戻ってこないか確認する
+            let mut probe = SshConfig::new(server_name.to_string());
+            probe.proxy_jump = Some(value.clone());
+            resolve_jump_chain(&probe, &configs)
+                .map(|_| ())
+                .map_err(|cycle| format!("jump chain loops: {}", cycle.join(" -> ")))
+        })
+        .interact_text()
+        .unwrap();
+    
+    Ok(Some(proxy_jump.trim().to_string()).filter(|value| !is_no_jump(value)))
+}
+
+fn is_no_jump(value: &str) -> bool {
+    value.trim().is_empty() || value.trim().eq_ignore_ascii_case("none")
+}
+
 fn sshct_show(server_name: &str) -> io::Result<()> {
     let config = find_config_by_host(server_name)?;
     
@@ -114,7 +717,14 @@ fn sshct_show(server_name: &str) -> io::Result<()> {
     };
     
     // 設定情報を表示
+    if let Some(environment) = &config.environment {
+        println!("Environment: {}\n", environment.name.to_uppercase());
+    }
     println!("{}", config.to_config_string());
+    print_directive_summary(&config);
+    if !is_managed_host(server_name)? {
+        println!("(hand-written block outside the ssh_supporter region; edit with --force)\n");
+    }
     
     // 接続確認
     if Confirm::new()
@@ -122,13 +732,13 @@ fn sshct_show(server_name: &str) -> io::Result<()> {
         .interact()
         .unwrap()
     {
-        run_sshr(server_name)?;
+        run_sshr(server_name, false)?;
     }
     
     Ok(())
 }
 
-fn sshct_edit(server_name: &str) -> io::Result<()> {
+fn sshct_edit(server_name: &str, force: bool) -> io::Result<()> {
     let config = find_config_by_host(server_name)?;
     
     let mut config = match config {
@@ -138,6 +748,7 @@ fn sshct_edit(server_name: &str) -> io::Result<()> {
             std::process::exit(1);
         }
     };
+    require_managed(server_name, force)?;
     
     println!("Editing SSH configuration for '{}'", server_name);
     println!("(Press Enter to keep current value)\n");
@@ -145,7 +756,7 @@ fn sshct_edit(server_name: &str) -> io::Result<()> {
     // HostName
     let current_hostname = config.hostname.as_deref().unwrap_or("");
     let hostname: String = Input::new()
-        .with_prompt(&format!("input host name [{}]", current_hostname))
+        .with_prompt(format!("input host name [{}]", current_hostname))
         .allow_empty(true)
         .interact_text()
         .unwrap();
@@ -156,7 +767,7 @@ fn sshct_edit(server_name: &str) -> io::Result<()> {
     // User
     let current_user = config.user.as_deref().unwrap_or("");
     let username: String = Input::new()
-        .with_prompt(&format!("input user name [{}]", current_user))
+        .with_prompt(format!("input user name [{}]", current_user))
         .allow_empty(true)
         .interact_text()
         .unwrap();
@@ -167,7 +778,7 @@ fn sshct_edit(server_name: &str) -> io::Result<()> {
     // Port
     let current_port = config.port.as_deref().unwrap_or("");
     let port: String = Input::new()
-        .with_prompt(&format!("input port number [{}]", current_port))
+        .with_prompt(format!("input port number [{}]", current_port))
         .allow_empty(true)
         .interact_text()
         .unwrap();
@@ -176,14 +787,31 @@ fn sshct_edit(server_name: &str) -> io::Result<()> {
     }
     
     // IdentityFile
-    let current_identity = config.identity_file.as_deref().unwrap_or("");
-    let identity_file: String = Input::new()
-        .with_prompt(&format!("input IdentityFile path [{}]", current_identity))
-        .allow_empty(true)
-        .interact_text()
-        .unwrap();
-    if !identity_file.is_empty() {
-        config.identity_file = Some(identity_file);
+    if config.identity_files.is_empty() {
+        let identity_file: String = Input::new()
+            .with_prompt("input IdentityFile path []")
+            .allow_empty(true)
+            .interact_text()
+            .unwrap();
+        if !identity_file.is_empty() {
+            config.identity_files.push(identity_file);
+        }
+    } else {
+        edit_identity_files(&mut config);
+    }
+    
+    // Key passphrase
+    if !config.identity_files.is_empty() {
+        let current_passphrase_display = if config.passphrase.is_some() { "****" } else { "" };
+        print!("input key passphrase [{}]: ", current_passphrase_display);
+        io::stdout().flush()?;
+        let passphrase = read_password()?;
+        if !passphrase.is_empty() {
+            config.passphrase = Some(passphrase);
+        }
+        if config.passphrase.is_some() {
+            config.agent_options = prompt_agent_options(&config.agent_options);
+        }
     }
     
     // Password
@@ -195,10 +823,26 @@ fn sshct_edit(server_name: &str) -> io::Result<()> {
         config.password = Some(password);
     }
     
+    // sudo
+    if config.password.is_some() {
+        config.auto_sudo = Confirm::new()
+            .with_prompt("enter this password at sudo prompts after login?")
+            .default(config.auto_sudo)
+            .interact()
+            .unwrap();
+    }
+    
+    // TOTP secret
+    let current_totp_display = if config.totp_secret.is_some() { "****" } else { "" };
+    let totp_secret = prompt_totp_secret(&format!("input TOTP secret [{}]: ", current_totp_display))?;
+    if !totp_secret.is_empty() {
+        config.totp_secret = Some(totp_secret);
+    }
+    
     // ProxyCommand
     let current_proxy = config.proxy_command.as_deref().unwrap_or("");
     let proxy_command: String = Input::new()
-        .with_prompt(&format!("input ProxyCommand [{}]", current_proxy))
+        .with_prompt(format!("input ProxyCommand [{}]", current_proxy))
         .allow_empty(true)
         .interact_text()
         .unwrap();
@@ -206,8 +850,1186 @@ fn sshct_edit(server_name: &str) -> io::Result<()> {
         config.proxy_command = Some(proxy_command);
     }
     
-    update_ssh_config(server_name, config)?;
+    // ProxyJump
+    config.proxy_jump = prompt_proxy_jump(server_name, config.proxy_jump.as_deref())?;
+    
+    // Tags
+    let current_tags = config.tags.join(",");
+    let tags: String = Input::new()
+        .with_prompt(format!("input tags [{}]", current_tags))
+        .allow_empty(true)
+        .interact_text()
+        .unwrap();
+    if !tags.is_empty() {
+        config.tags = parse_tags(&tags);
+    }
+    
+    // Environment
+    config.environment = prompt_environment(config.environment.as_ref());
+    
+    // Recording
+    config.record = Confirm::new()
+        .with_prompt("record sessions to this host?")
+        .default(config.record)
+        .interact()
+        .unwrap();
+    
+    // Prompt rules
+    if Confirm::new()
+        .with_prompt(format!("edit prompt rules? ({} defined)", config.prompt_rules.len()))
+        .default(false)
+        .interact()
+        .unwrap()
+    {
+        edit_prompt_rules(&mut config);
+    }
+    
+    // Tunnel profiles
+    if Confirm::new()
+        .with_prompt(format!("edit tunnel profiles? ({} defined)", config.tunnels.len()))
+        .default(false)
+        .interact()
+        .unwrap()
+    {
+        edit_tunnels(&mut config);
+    }
+    
+    // Other directives
+    if Confirm::new()
+        .with_prompt(format!("edit other directives? ({} defined)", config.directives.len()))
+        .default(false)
+        .interact()
+        .unwrap()
+    {
+        edit_directives(&mut config);
+    }
+    
+    update_ssh_config(server_name, config, force)?;
     println!("\nSSH configuration for '{}' has been updated successfully!", server_name);
     
     Ok(())
 }
+
+/// 管理領域の外のホストは、`--force` がなければ書き換えない
+fn require_managed(server_name: &str, force: bool) -> io::Result<()> {
+    if !force && !is_managed_host(server_name)? {
+        eprintln!(
+            "Error: '{}' is outside the ssh_supporter managed region of ~/.ssh/config",
+            server_name
+        );
+        eprintln!("Re-run with --force to edit a hand-written block.");
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn sshct_lint(json: bool, strict: bool) -> io::Result<()> {
+    let findings = lint_config()?;
+
+    if json {
+        let output = serde_json::to_string_pretty(&findings)
+            .map_err(io::Error::other)?;
+        println!("{}", output);
+    } else {
+        print_findings(&findings);
+    }
+
+    // CI 用: error があれば（--strict なら warning でも）非ゼロで終了する
+    let failed = findings
+        .iter()
+        .any(|f| f.severity == Severity::Error || strict);
+    if failed {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+fn sshct_doctor() -> io::Result<()> {
+    let checks = run_checks();
+    print_checks(&checks);
+
+    if checks.iter().any(|c| c.status == Status::Fail) {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+fn sshct_check(args: &[String]) -> io::Result<()> {
+    let usage = "Usage: sshct check <server_name...|--all|--tag <tag>> [--banner] [--timeout <secs>]";
+    let mut hosts = Vec::new();
+    let mut all = false;
+    let mut tag: Option<String> = None;
+    let mut banner = false;
+    let mut timeout = DEFAULT_TIMEOUT_SECS;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--all" => all = true,
+            "--banner" => banner = true,
+            "--tag" => tag = iter.next().cloned(),
+            "--timeout" => {
+                timeout = match iter.next().and_then(|v| v.parse().ok()) {
+                    Some(secs) => secs,
+                    None => {
+                        eprintln!("{}", usage);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            _ => hosts.push(arg.clone()),
+        }
+    }
+
+    let configs = parse_ssh_config()?;
+    let targets: Vec<SshConfig> = if all {
+        configs
+            .into_iter()
+            .filter(|c| !c.host.contains('*') && !c.host.contains('?'))
+            .collect()
+    } else if let Some(tag) = &tag {
+        configs.into_iter().filter(|c| c.has_tag(tag)).collect()
+    } else if !hosts.is_empty() {
+        let mut targets = Vec::new();
+        for host in &hosts {
+            match configs.iter().find(|c| &c.host == host) {
+                Some(c) => targets.push(c.clone()),
+                // config にないホストも ssh と同様にそのまま確認する
+                None => targets.push(SshConfig::new(host.clone())),
+            }
+        }
+        targets
+    } else {
+        eprintln!("{}", usage);
+        std::process::exit(1);
+    };
+
+    if targets.is_empty() {
+        eprintln!("Error: no hosts matched");
+        std::process::exit(1);
+    }
+
+    let results = check_hosts(&targets, banner, Duration::from_secs(timeout));
+    print_results(&results);
+
+    if results.iter().any(|r| !r.ok) {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+fn sshct_keygen(server_name: &str, force: bool) -> io::Result<()> {
+    let config = find_config_by_host(server_name)?;
+    
+    let mut config = match config {
+        Some(c) => c,
+        None => {
+            eprintln!("Error: Host '{}' not found in SSH config", server_name);
+            std::process::exit(1);
+        }
+    };
+    require_managed(server_name, force)?;
+    
+    let (identity_file, path) = host_key_path(server_name, "")?;
+    if path.exists() {
+        eprintln!("Error: {} already exists", path.display());
+        std::process::exit(1);
+    }
+    
+    // ssh-keygen と同じく、確認のため 2 回入力させる（空ならパスフレーズなし）
+    let passphrase = loop {
+        print!("input passphrase for the new key (empty for none): ");
+        io::stdout().flush()?;
+        let first = read_password()?;
+        print!("input same passphrase again: ");
+        io::stdout().flush()?;
+        if read_password()? == first {
+            break first;
+        }
+        eprintln!("Passphrases do not match. Try again.");
+    };
+    
+    let public_key = generate_key(
+        &path,
+        &format!("sshct:{}", server_name),
+        Some(passphrase.as_str()).filter(|p| !p.is_empty()),
+    )?;
+    println!("Generated {}", path.display());
+    println!("{}", public_key.to_openssh().map_err(io::Error::other)?);
+    
+    config.set_identity_file(identity_file);
+    if !passphrase.is_empty()
+        && Confirm::new()
+            .with_prompt("store the passphrase (#passphrase) so sshr can load the key into ssh-agent?")
+            .default(true)
+            .interact()
+            .unwrap()
+    {
+        config.passphrase = Some(passphrase);
+    }
+    
+    update_ssh_config(server_name, config, force)?;
+    println!("\nIdentityFile for '{}' has been updated.", server_name);
+    println!("Install the key on the server with: sshct deploy-key {}", server_name);
+    
+    Ok(())
+}
+
+fn sshct_deploy_key(server_name: &str, force: bool) -> io::Result<()> {
+    let config = find_config_by_host(server_name)?;
+    
+    let mut config = match config {
+        Some(c) => c,
+        None => {
+            eprintln!("Error: Host '{}' not found in SSH config", server_name);
+            std::process::exit(1);
+        }
+    };
+    require_managed(server_name, force)?;
+    
+    let Some(identity_file) = config.identity_file().map(str::to_string) else {
+        eprintln!("Error: Host '{}' has no IdentityFile", server_name);
+        eprintln!("Generate one with: sshct keygen {}", server_name);
+        std::process::exit(1);
+    };
+    let public_key = read_public_key(&expand_tilde(&identity_file)?)?;
+    
+    // 保存済みのパスワードでログインし、authorized_keys に追記する
+    println!("Installing {} on '{}'...", identity_file, server_name);
+    let code = run_ssh(server_name, &[], Some(&install_key_command(&public_key)?), false)?;
+    if code != 0 {
+        eprintln!("Error: failed to install the key (ssh exited with {})", code);
+        std::process::exit(1);
+    }
+    
+    // パスワードを使わずに新しい鍵だけでログインできるか確認する
+    let code = run_ssh(server_name, KEY_ONLY_OPTIONS, Some("true"), false)?;
+    if code != 0 {
+        eprintln!("Error: the key was installed but logging in with it failed; #pass is kept");
+        std::process::exit(1);
+    }
+    println!("Key login to '{}' works.", server_name);
+    
+    if config.password.is_some() {
+        let prompt = if config.auto_sudo {
+            "remove the stored password (#pass)? sudo auto-entry (#sudo) will be turned off too"
+        } else {
+            "remove the stored password (#pass)?"
+        };
+        if Confirm::new()
+            .with_prompt(prompt)
+            .default(true)
+            .interact()
+            .unwrap()
+        {
+            config.password = None;
+            config.auto_sudo = false;
+            update_ssh_config(server_name, config, force)?;
+            println!("Removed #pass from '{}'.", server_name);
+        }
+    }
+    
+    Ok(())
+}
+
+fn sshct_rotate_key(args: &[String]) -> io::Result<()> {
+    let usage = "Usage: sshct rotate-key <server_name...|--all|--tag <tag>> [--yes] [--force]";
+    let mut hosts = Vec::new();
+    let mut all = false;
+    let mut tag: Option<String> = None;
+    let mut yes = false;
+    let mut force = false;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--all" => all = true,
+            "--yes" | "-y" => yes = true,
+            "--force" => force = true,
+            "--tag" => tag = iter.next().cloned(),
+            _ => hosts.push(arg.clone()),
+        }
+    }
+
+    let configs = parse_ssh_config()?;
+    let targets: Vec<SshConfig> = if all {
+        configs
+            .into_iter()
+            .filter(|c| !c.host.contains('*') && !c.host.contains('?'))
+            .collect()
+    } else if let Some(tag) = &tag {
+        configs.into_iter().filter(|c| c.has_tag(tag)).collect()
+    } else if !hosts.is_empty() {
+        let mut targets = Vec::new();
+        for host in &hosts {
+            match configs.iter().find(|c| &c.host == host) {
+                Some(c) => targets.push(c.clone()),
+                None => {
+                    eprintln!("Error: Host '{}' not found in SSH config", host);
+                    std::process::exit(1);
+                }
+            }
+        }
+        targets
+    } else {
+        eprintln!("{}", usage);
+        std::process::exit(1);
+    };
+
+    if targets.is_empty() {
+        eprintln!("Error: no hosts matched");
+        std::process::exit(1);
+    }
+
+    let names: Vec<&str> = targets.iter().map(|c| c.host.as_str()).collect();
+    println!("Rotating keys on: {}", names.join(", "));
+    if !yes
+        && !Confirm::new()
+            .with_prompt(format!("generate and install new keys on {} host(s)?", targets.len()))
+            .default(false)
+            .interact()
+            .unwrap()
+    {
+        return Ok(());
+    }
+
+    // ログインにパスフレーズなどの入力が必要になることがあるので、1 台ずつ順に処理する
+    let mut rotations = Vec::new();
+    for config in &targets {
+        println!("\n==> {}", config.host);
+        rotations.push(rotate_host(config, force));
+    }
+
+    println!();
+    print_rotations(&rotations);
+    let log = record_rotations(&rotations)?;
+    println!("\nResults recorded in {}", log.display());
+
+    if rotations.iter().any(|r| !r.succeeded()) {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+fn sshct_list(args: &[String]) -> io::Result<()> {
+    let usage = "Usage: sshct list [--sort name|recent|count] [--tag <tag>]";
+    let mut sort = "name".to_string();
+    let mut tag: Option<String> = None;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--sort" => sort = iter.next().cloned().unwrap_or_default(),
+            "--tag" => tag = iter.next().cloned(),
+            _ => {
+                eprintln!("{}", usage);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let mut configs: Vec<SshConfig> = parse_ssh_config()?
+        .into_iter()
+        .filter(|c| tag.as_ref().is_none_or(|t| c.has_tag(t)))
+        .collect();
+    let stats = host_stats(&history::load()?);
+    let stats_of = |host: &str| stats.iter().find(|s| s.alias == host);
+
+    match sort.as_str() {
+        "name" => configs.sort_by(|a, b| a.host.cmp(&b.host)),
+        // 最近接続した順（接続したことのないホストは名前順で最後に並べる）
+        "recent" => configs.sort_by(|a, b| {
+            let last = |host: &str| stats_of(host).map(|s| s.last_used);
+            last(&b.host).cmp(&last(&a.host)).then(a.host.cmp(&b.host))
+        }),
+        "count" => configs.sort_by(|a, b| {
+            let count = |host: &str| stats_of(host).map_or(0, |s| s.connections);
+            count(&b.host).cmp(&count(&a.host)).then(a.host.cmp(&b.host))
+        }),
+        _ => {
+            eprintln!("{}", usage);
+            std::process::exit(1);
+        }
+    }
+
+    let rows: Vec<Vec<String>> = configs
+        .iter()
+        .map(|c| {
+            let stats = stats_of(&c.host);
+            vec![
+                c.host.clone(),
+                c.hostname.clone().unwrap_or_else(|| "-".to_string()),
+                c.user.clone().unwrap_or_else(|| "-".to_string()),
+                if c.tags.is_empty() { "-".to_string() } else { c.tags.join(",") },
+                c.environment.as_ref().map_or("-".to_string(), |e| e.name.clone()),
+                stats.map_or("-".to_string(), |s| format_utc(s.last_used)),
+                stats.map_or(0, |s| s.connections).to_string(),
+            ]
+        })
+        .collect();
+    print_table(&["HOST", "HOSTNAME", "USER", "TAGS", "ENV", "LAST USED (UTC)", "CONNECTIONS"], &rows);
+
+    Ok(())
+}
+
+/// `--since <期間>` の値を、それより前を除外する UNIX 時刻に変換する
+fn parse_since(value: Option<&String>, usage: &str) -> u64 {
+    match value.and_then(|v| parse_duration_secs(v)) {
+        Some(secs) => unix_now().saturating_sub(secs as u64),
+        None => {
+            eprintln!("{}", usage);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn sshct_history(args: &[String]) -> io::Result<()> {
+    let usage = "Usage: sshct history [server_name] [--since <duration>] [--failed] [--limit <n>]";
+    let mut host: Option<String> = None;
+    let mut since = 0;
+    let mut failed = false;
+    let mut limit = 20;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--since" => since = parse_since(iter.next(), usage),
+            "--failed" => failed = true,
+            "--limit" => {
+                limit = match iter.next().and_then(|v| v.parse().ok()) {
+                    Some(n) => n,
+                    None => {
+                        eprintln!("{}", usage);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            _ if host.is_none() && !arg.starts_with('-') => host = Some(arg.clone()),
+            _ => {
+                eprintln!("{}", usage);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let entries: Vec<history::Entry> = history::load()?
+        .into_iter()
+        .filter(|e| host.as_ref().is_none_or(|h| &e.alias == h))
+        .filter(|e| e.started >= since)
+        .filter(|e| !failed || e.exit_status != 0)
+        .collect();
+
+    // 新しいものを limit 件、古い順に表示する
+    let skip = entries.len().saturating_sub(limit);
+    let rows: Vec<Vec<String>> = entries[skip..]
+        .iter()
+        .map(|e| {
+            vec![
+                format_utc(e.started),
+                e.alias.clone(),
+                e.hostname.clone(),
+                format_duration(e.duration),
+                e.exit_status.to_string(),
+            ]
+        })
+        .collect();
+    print_table(&["TIME (UTC)", "HOST", "HOSTNAME", "DURATION", "EXIT"], &rows);
+
+    Ok(())
+}
+
+fn sshct_stats(args: &[String]) -> io::Result<()> {
+    let usage = "Usage: sshct stats [--since <duration>] [--limit <n>]";
+    let mut since = 0;
+    let mut limit = 10;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--since" => since = parse_since(iter.next(), usage),
+            "--limit" => {
+                limit = match iter.next().and_then(|v| v.parse().ok()) {
+                    Some(n) => n,
+                    None => {
+                        eprintln!("{}", usage);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            _ => {
+                eprintln!("{}", usage);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let entries: Vec<history::Entry> = history::load()?
+        .into_iter()
+        .filter(|e| e.started >= since)
+        .collect();
+    let rows: Vec<Vec<String>> = host_stats(&entries)
+        .iter()
+        .take(limit)
+        .map(|s| {
+            vec![
+                s.alias.clone(),
+                s.connections.to_string(),
+                s.failures.to_string(),
+                format_duration(s.total_duration),
+                format_utc(s.last_used),
+            ]
+        })
+        .collect();
+    print_table(&["HOST", "CONNECTIONS", "FAILURES", "TOTAL TIME", "LAST USED (UTC)"], &rows);
+
+    Ok(())
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ConflictPolicy {
+    Ask,
+    Skip,
+    Overwrite,
+    Rename,
+}
+
+/// 既存のホストと重ならない `<alias>-2` のような名前を探す
+fn free_alias(alias: &str, taken: &HashSet<String>) -> String {
+    (2..)
+        .map(|n| format!("{}-{}", alias, n))
+        .find(|candidate| !taken.contains(candidate))
+        .unwrap()
+}
+
+fn sshct_import(args: &[String]) -> io::Result<()> {
+    let usage = "Usage: sshct import <file|dir> [--format csv|json|yaml|putty|remmina|termius] [--on-conflict skip|overwrite|rename] [--dry-run] [--yes] [--force]";
+    let mut file: Option<String> = None;
+    let mut format: Option<Format> = None;
+    let mut policy = ConflictPolicy::Ask;
+    let mut dry_run = false;
+    let mut yes = false;
+    let mut force = false;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--format" => match iter.next().and_then(|v| Format::parse(v)) {
+                Some(f) => format = Some(f),
+                None => {
+                    eprintln!("{}", usage);
+                    std::process::exit(1);
+                }
+            },
+            "--on-conflict" => {
+                policy = match iter.next().map(|v| v.as_str()) {
+                    Some("skip") => ConflictPolicy::Skip,
+                    Some("overwrite") => ConflictPolicy::Overwrite,
+                    Some("rename") => ConflictPolicy::Rename,
+                    _ => {
+                        eprintln!("{}", usage);
+                        std::process::exit(1);
+                    }
+                }
+            }
+            "--dry-run" => dry_run = true,
+            "--yes" | "-y" => yes = true,
+            "--force" => force = true,
+            _ if file.is_none() && !arg.starts_with('-') => file = Some(arg.clone()),
+            _ => {
+                eprintln!("{}", usage);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let Some(file) = file else {
+        eprintln!("{}", usage);
+        std::process::exit(1);
+    };
+    let path = expand_tilde(&file)?;
+    let Some(format) = format.or_else(|| Format::from_path(&path)) else {
+        eprintln!("Error: cannot tell the format of '{}'; use --format csv|json|yaml|putty|remmina|termius", file);
+        std::process::exit(1);
+    };
+    let hosts = match read_inventory(&path, format) {
+        Ok(imported) => {
+            // 他のクライアントの設定で、ssh_config に対応付けられなかったものを知らせる
+            for note in &imported.notes {
+                eprintln!("Note: {}", note);
+            }
+            imported.hosts
+        }
+        Err(e) => {
+            eprintln!("Error: cannot read '{}': {}", file, e);
+            std::process::exit(1);
+        }
+    };
+
+    let existing = parse_ssh_config()?;
+    let managed = managed_hosts()?;
+    let mut taken: HashSet<String> = existing.iter().map(|c| c.host.clone()).collect();
+    let mut additions: Vec<SshConfig> = Vec::new();
+    let mut overwrites: Vec<SshConfig> = Vec::new();
+    let mut skipped = 0;
+
+    for (index, host) in hosts.iter().enumerate() {
+        let mut config = match host.to_ssh_config() {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("Warning: skipping entry {} ('{}'): {}", index + 1, host.alias, e);
+                skipped += 1;
+                continue;
+            }
+        };
+
+        if taken.contains(&config.host) {
+            // ファイル内で重複したエイリアスと、管理領域の外の手書きのブロックは上書きしない
+            let in_config = existing
+                .iter()
+                .find(|c| c.host == config.host)
+                .filter(|c| force || managed.contains(&c.host));
+            let choice = match policy {
+                ConflictPolicy::Ask => {
+                    let mut items = vec!["skip", "rename"];
+                    if in_config.is_some() {
+                        items.push("overwrite");
+                    }
+                    let selection = Select::new()
+                        .with_prompt(format!("'{}' already exists", config.host))
+                        .items(&items)
+                        .default(0)
+                        .interact()
+                        .unwrap();
+                    match items[selection] {
+                        "rename" => ConflictPolicy::Rename,
+                        "overwrite" => ConflictPolicy::Overwrite,
+                        _ => ConflictPolicy::Skip,
+                    }
+                }
+                ConflictPolicy::Overwrite if in_config.is_none() => ConflictPolicy::Skip,
+                policy => policy,
+            };
+
+            match choice {
+                ConflictPolicy::Overwrite => {
+                    let merged = overwrite_existing(in_config.unwrap(), config);
+                    overwrites.retain(|c| c.host != merged.host);
+                    overwrites.push(merged);
+                    continue;
+                }
+                ConflictPolicy::Rename => {
+                    let suggestion = free_alias(&config.host, &taken);
+                    let alias = if policy == ConflictPolicy::Ask {
+                        Input::<String>::new()
+                            .with_prompt(format!("new alias for '{}'", config.host))
+                            .default(suggestion)
+                            .validate_with(|input: &String| {
+                                if taken.contains(input) {
+                                    Err("already exists")
+                                } else if input.is_empty() || input.contains(char::is_whitespace) {
+                                    Err("invalid alias")
+                                } else {
+                                    Ok(())
+                                }
+                            })
+                            .interact_text()
+                            .unwrap()
+                    } else {
+                        suggestion
+                    };
+                    println!("Importing '{}' as '{}'", config.host, alias);
+                    config.host = alias;
+                }
+                _ => {
+                    println!("Skipping '{}'", config.host);
+                    skipped += 1;
+                    continue;
+                }
+            }
+        }
+
+        taken.insert(config.host.clone());
+        additions.push(config);
+    }
+
+    if additions.is_empty() && overwrites.is_empty() {
+        println!("Nothing to import ({} skipped).", skipped);
+        return Ok(());
+    }
+
+    print!("{}", import_preview(&additions, &overwrites));
+    println!(
+        "\n{} new, {} overwritten, {} skipped",
+        additions.len(),
+        overwrites.len(),
+        skipped
+    );
+
+    if dry_run {
+        return Ok(());
+    }
+    if !yes
+        && !Confirm::new()
+            .with_prompt("write these hosts to ~/.ssh/config?")
+            .default(false)
+            .interact()
+            .unwrap()
+    {
+        return Ok(());
+    }
+
+    for config in overwrites {
+        let host = config.host.clone();
+        update_ssh_config(&host, config, force)?;
+    }
+    for config in additions {
+        add_ssh_config(config)?;
+    }
+    println!("Imported.");
+
+    Ok(())
+}
+
+fn sshct_export(args: &[String]) -> io::Result<()> {
+    let usage = "Usage: sshct export [--format json|csv|yaml|ansible-ini|ansible-yaml] [--tag <tag>] [--pattern <pattern>] [--output <file>] [--include-secrets]";
+    let mut format = ExportFormat::Json;
+    let mut tag: Option<String> = None;
+    let mut pattern: Option<String> = None;
+    let mut output: Option<String> = None;
+    let mut include_secrets = false;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--format" => match iter.next().and_then(|v| ExportFormat::parse(v)) {
+                Some(f) => format = f,
+                None => {
+                    eprintln!("{}", usage);
+                    std::process::exit(1);
+                }
+            },
+            "--tag" => tag = iter.next().cloned(),
+            "--pattern" => pattern = iter.next().cloned(),
+            "--output" | "-o" => output = iter.next().cloned(),
+            "--include-secrets" => include_secrets = true,
+            _ => {
+                eprintln!("{}", usage);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    // ワイルドカードの Host ブロックは個別のホストではないので書き出さない
+    let configs: Vec<SshConfig> = parse_ssh_config()?
+        .into_iter()
+        .filter(|c| !c.host.contains('*') && !c.host.contains('?'))
+        .filter(|c| tag.as_ref().is_none_or(|t| c.has_tag(t)))
+        .filter(|c| {
+            pattern.as_ref().is_none_or(|p| {
+                pattern_matches(p, &c.host) || c.hostname.as_ref().is_some_and(|h| pattern_matches(p, h))
+            })
+        })
+        .collect();
+
+    if include_secrets {
+        if !io::stdin().is_terminal() {
+            eprintln!("Error: --include-secrets must be confirmed interactively");
+            std::process::exit(1);
+        }
+        let with_secrets = configs
+            .iter()
+            .filter(|c| c.password.is_some() || c.passphrase.is_some() || c.totp_secret.is_some())
+            .count();
+        if !Confirm::new()
+            .with_prompt(format!("export plaintext secrets of {} host(s)?", with_secrets))
+            .default(false)
+            .interact()
+            .unwrap()
+        {
+            return Ok(());
+        }
+    }
+
+    let text = export_hosts(&configs, format, include_secrets)?;
+    match output {
+        Some(output) => {
+            let path = expand_tilde(&output)?;
+            // 既存のファイルに上書きする場合も、秘密情報を含むなら自分だけが読めるようにする
+            write_atomic(&path, text.as_bytes(), if include_secrets { 0o600 } else { 0o644 })?;
+            eprintln!("Exported {} host(s) to {}", configs.len(), path.display());
+        }
+        None => print!("{}", text),
+    }
+
+    Ok(())
+}
+
+fn sshct_discover(args: &[String]) -> io::Result<()> {
+    let usage = "Usage: sshct discover [--list]";
+    let mut list = false;
+    for arg in args {
+        match arg.as_str() {
+            "--list" => list = true,
+            _ => {
+                eprintln!("{}", usage);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let candidates = discover(&parse_ssh_config()?)?;
+    if candidates.is_empty() {
+        println!("No new hosts found in known_hosts or shell history.");
+        return Ok(());
+    }
+
+    let describe = |c: &Candidate| {
+        let sources: Vec<String> = c
+            .sources
+            .iter()
+            .map(|&s| if s == "history" { format!("history ({})", c.uses) } else { s.to_string() })
+            .collect();
+        sources.join(", ")
+    };
+
+    if list || !io::stdin().is_terminal() {
+        let rows: Vec<Vec<String>> = candidates
+            .iter()
+            .map(|c| vec![c.alias.clone(), c.destination(), describe(c)])
+            .collect();
+        print_table(&["ALIAS", "DESTINATION", "FOUND IN"], &rows);
+        return Ok(());
+    }
+
+    let items: Vec<String> = candidates
+        .iter()
+        .map(|c| format!("{:<20} {:<40} ({})", c.alias, c.destination(), describe(c)))
+        .collect();
+    let selected = MultiSelect::new()
+        .with_prompt("select hosts to add (space to toggle, enter to confirm)")
+        .items(&items)
+        .interact()
+        .unwrap();
+    if selected.is_empty() {
+        return Ok(());
+    }
+
+    for index in selected {
+        let config = candidates[index].to_ssh_config();
+        println!("Added '{}' ({})", config.host, candidates[index].destination());
+        add_ssh_config(config)?;
+    }
+    println!("Use `sshct edit <server_name>` to rename hosts or add keys and tags.");
+
+    Ok(())
+}
+
+/// チームのインベントリを出力先に反映する。変更があれば差分を表示する
+fn sync_inventory(source: &Path, output: Option<PathBuf>, dry_run: bool, add_include: bool) -> io::Result<()> {
+    let inventory = match read_team_inventory(source) {
+        Ok(inventory) => inventory,
+        Err(e) => {
+            eprintln!("Error: cannot read '{}': {}", source.display(), e);
+            std::process::exit(1);
+        }
+    };
+    let configs = match inventory.resolve() {
+        Ok(configs) => configs,
+        Err(e) => {
+            eprintln!("Error: {}: {}", source.display(), e);
+            std::process::exit(1);
+        }
+    };
+    let output = match output {
+        Some(output) => output,
+        None => sync::default_output(&inventory, source)?,
+    };
+
+    let old = match fs::read_to_string(&output) {
+        Ok(content) => {
+            if sync::managed_source(&content).is_none() {
+                eprintln!(
+                    "Error: {} was not generated by sshct sync; refusing to overwrite it",
+                    output.display()
+                );
+                std::process::exit(1);
+            }
+            content
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e),
+    };
+
+    // ssh は最初に読んだ値を使うので、どちらが優先されるかは Include の位置による
+    let own = parse_ssh_config()?;
+    for config in &configs {
+        if own.iter().any(|c| c.host == config.host) {
+            eprintln!(
+                "Warning: '{}' is also defined in ~/.ssh/config; ssh uses whichever definition it reads first",
+                config.host
+            );
+        }
+    }
+
+    let new = sync::render(source, &configs);
+    if old == new {
+        println!("{} is up to date ({} hosts).", output.display(), configs.len());
+    } else {
+        print!("{}", sync::diff(&old, &new, &output));
+        if !dry_run {
+            if let Some(parent) = output.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&output, &new)?;
+            println!("Wrote {} hosts to {}", configs.len(), output.display());
+        }
+    }
+
+    if sync::is_config_including(&output)? {
+        return Ok(());
+    }
+    if add_include && !dry_run {
+        sync::add_include(&output)?;
+        println!("Added an Include for {} to the end of ~/.ssh/config", output.display());
+    } else {
+        println!(
+            "\n~/.ssh/config does not include {}. Add these lines to the end of it (or rerun with --add-include):",
+            output.display()
+        );
+        for line in sync::include_lines(&output)?.lines() {
+            println!("  {}", line);
+        }
+    }
+    Ok(())
+}
+
+fn sshct_sync(args: &[String]) -> io::Result<()> {
+    let usage = "Usage: sshct sync [<inventory.toml|inventory.yaml>] [--output <file>] [--dry-run] [--add-include]";
+    let mut source: Option<String> = None;
+    let mut output: Option<String> = None;
+    let mut dry_run = false;
+    let mut add_include = false;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--output" | "-o" => output = iter.next().cloned(),
+            "--dry-run" => dry_run = true,
+            "--add-include" => add_include = true,
+            _ if source.is_none() && !arg.starts_with('-') => source = Some(arg.clone()),
+            _ => {
+                eprintln!("{}", usage);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    if let Some(source) = source {
+        let source = expand_tilde(&source)?;
+        // 生成したファイルに記録し、引数なしで再同期できるようにする
+        let source = fs::canonicalize(&source).unwrap_or(source);
+        let output = output.map(|o| expand_tilde(&o)).transpose()?;
+        return sync_inventory(&source, output, dry_run, add_include);
+    }
+    if output.is_some() {
+        eprintln!("{}", usage);
+        std::process::exit(1);
+    }
+
+    // 引数なしの場合は、以前に生成したファイルをそれぞれの同期元から作り直す
+    let managed = sync::managed_files()?;
+    if managed.is_empty() {
+        eprintln!("{}", usage);
+        std::process::exit(1);
+    }
+    for (output, source) in managed {
+        match source {
+            Some(source) => sync_inventory(&source, Some(output), dry_run, add_include)?,
+            None => eprintln!("Warning: {} does not record its source; skipping", output.display()),
+        }
+    }
+    Ok(())
+}
+
+fn sshct_template(args: &[String]) -> io::Result<()> {
+    let usage = "Usage: sshct template <save <server_name> <name> [--force]|show <name>|list>";
+    match args.first().map(|a| a.as_str()) {
+        Some("save") if args.len() >= 3 => {
+            let (server_name, name) = (&args[1], &args[2]);
+            let force = args[3..].iter().any(|a| a == "--force");
+            if !is_valid_name(name) {
+                eprintln!("Error: template names may contain only letters, digits, '-', '_' and '.'");
+                std::process::exit(1);
+            }
+            let Some(config) = find_config_by_host(server_name)? else {
+                eprintln!("Error: Host '{}' not found in SSH config", server_name);
+                std::process::exit(1);
+            };
+            if !force && template_path(name)?.exists() {
+                eprintln!("Error: template '{}' already exists (use --force to replace it)", name);
+                std::process::exit(1);
+            }
+            
+            let template = Template::from_config(&config);
+            let path = save_template(name, &template)?;
+            print!("{}", toml::to_string(&template).map_err(io::Error::other)?);
+            println!("\nSaved template '{}' to {}", name, path.display());
+            if config.password.is_some() || config.passphrase.is_some() || config.totp_secret.is_some() {
+                println!("(HostName and stored secrets are not included)");
+            }
+            println!("Use it with: sshct new <server_name> --template {}", name);
+        }
+        Some("show") if args.len() >= 2 => match load_template(&args[1])? {
+            Some(template) => print!("{}", toml::to_string(&template).map_err(io::Error::other)?),
+            None => {
+                eprintln!("Error: template '{}' not found", args[1]);
+                std::process::exit(1);
+            }
+        },
+        Some("list") => {
+            for name in list_templates()? {
+                println!("{}", name);
+            }
+        }
+        _ => {
+            eprintln!("{}", usage);
+            std::process::exit(1);
+        }
+    }
+    Ok(())
+}
+
+fn sshct_mux(args: &[String]) -> io::Result<()> {
+    let usage = "Usage: sshct mux <status [server_name]|stop <server_name|--all>|enable <server_name> [--persist <duration>] [--force]|disable <server_name> [--force]>";
+    let force = args.iter().any(|a| a == "--force");
+    let args: Vec<&str> = args.iter().map(String::as_str).filter(|a| *a != "--force").collect();
+    match args.as_slice() {
+        ["status"] => mux_status_all(),
+        ["status", server_name] => mux_status(server_name),
+        ["stop", "--all"] => {
+            let hosts: Vec<String> = parse_ssh_config()?
+                .into_iter()
+                .map(|c| c.host)
+                .filter(|h| !h.contains('*') && !h.contains('?'))
+                .collect();
+            let mut stopped = 0;
+            for host in hosts {
+                if mux::stop_master(&host)? {
+                    println!("Closed the master connection for '{}'", host);
+                    stopped += 1;
+                }
+            }
+            if stopped == 0 {
+                println!("No master connections running.");
+            }
+            Ok(())
+        }
+        ["stop", server_name] => {
+            if mux::stop_master(server_name)? {
+                println!("Closed the master connection for '{}'", server_name);
+            } else {
+                println!("No master connection running for '{}'", server_name);
+            }
+            Ok(())
+        }
+        ["enable", server_name] => mux_enable(server_name, mux::DEFAULT_PERSIST, force),
+        ["enable", server_name, "--persist", persist] => mux_enable(server_name, persist, force),
+        ["disable", server_name] => mux_disable(server_name, force),
+        _ => {
+            eprintln!("{}", usage);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn mux_status(server_name: &str) -> io::Result<()> {
+    if find_config_by_host(server_name)?.is_none() {
+        eprintln!("Error: Host '{}' not found in SSH config", server_name);
+        std::process::exit(1);
+    }
+    let values = ssh_effective_config(server_name).unwrap_or_default();
+    match mux::master_status(server_name, &values)? {
+        MasterStatus::Disabled => {
+            println!("'{}' does not use connection multiplexing", server_name);
+            println!("Enable it with: sshct mux enable {}", server_name);
+        }
+        MasterStatus::NotRunning => {
+            println!("'{}': no master connection (the next sshr {} opens one)", server_name, server_name);
+        }
+        MasterStatus::Running(pid) => {
+            println!(
+                "'{}': master connection running (pid {}, ControlPersist {})",
+                server_name,
+                pid,
+                mux::persist_label(&values)
+            );
+        }
+    }
+    Ok(())
+}
+
+/// 多重化を有効にしているホストと、マスター接続の状態の一覧
+fn mux_status_all() -> io::Result<()> {
+    let mut rows = Vec::new();
+    for config in parse_ssh_config()? {
+        if config.host.contains('*') || config.host.contains('?') {
+            continue;
+        }
+        let values = ssh_effective_config(&config.host).unwrap_or_default();
+        let master = match mux::master_status(&config.host, &values)? {
+            MasterStatus::Disabled => continue,
+            MasterStatus::NotRunning => "-".to_string(),
+            MasterStatus::Running(pid) => format!("running (pid {})", pid),
+        };
+        rows.push(vec![config.host.clone(), master, mux::persist_label(&values)]);
+    }
+    if rows.is_empty() {
+        println!("No hosts use connection multiplexing.");
+        println!("Enable it with: sshct mux enable <server_name>");
+        return Ok(());
+    }
+    print_table(&["HOST", "MASTER", "PERSIST"], &rows);
+    Ok(())
+}
+
+fn mux_enable(server_name: &str, persist: &str, force: bool) -> io::Result<()> {
+    let Some(mut config) = find_config_by_host(server_name)? else {
+        eprintln!("Error: Host '{}' not found in SSH config", server_name);
+        std::process::exit(1);
+    };
+    require_managed(server_name, force)?;
+    if persist != "yes" && parse_duration_secs(persist).is_none() {
+        eprintln!("Error: --persist takes a duration such as 600, 10m or 1h, or 'yes' to keep it until stopped");
+        std::process::exit(1);
+    }
+    
+    mux::enable(&mut config, persist)?;
+    update_ssh_config(server_name, config, force)?;
+    println!("Connection multiplexing enabled for '{}' (ControlPersist {})", server_name, persist);
+    println!(
+        "The next sshr {} authenticates once; later sshr, ssh and scp calls reuse that connection.",
+        server_name
+    );
+    Ok(())
+}
+
+fn mux_disable(server_name: &str, force: bool) -> io::Result<()> {
+    let Some(mut config) = find_config_by_host(server_name)? else {
+        eprintln!("Error: Host '{}' not found in SSH config", server_name);
+        std::process::exit(1);
+    };
+    require_managed(server_name, force)?;
+    
+    // ControlPath を消すと ssh -O で閉じられなくなるので、先に閉じる
+    if mux::stop_master(server_name)? {
+        println!("Closed the master connection for '{}'", server_name);
+    }
+    mux::disable(&mut config);
+    update_ssh_config(server_name, config, force)?;
+    println!("Connection multiplexing disabled for '{}'", server_name);
+    Ok(())
+}
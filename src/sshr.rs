@@ -1,118 +1,562 @@
-use crate::config::find_config_by_host;
+use crate::agent::{load_key, LoadOutcome};
+use crate::config::{
+    effective_value, find_config_by_host, parse_ssh_config, resolve_jump_chain, EffectiveConfigs,
+    EnvironmentLabel, PromptRuleSpec, RuleResponse, SshConfig,
+};
+use crate::history;
+use crate::mux;
+use crate::record::Recorder;
+use crate::session::{run_session, terminal_size, Answer, PromptRule};
+use crate::totp::totp_now;
+use crate::utils::{expand_tilde, unix_now};
+use dialoguer::FuzzySelect;
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::env;
-use std::fs;
-use std::io;
-use std::os::unix::fs::PermissionsExt;
+use std::io::{self, IsTerminal, Write};
 use std::process::Command;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
 
-pub fn run_sshr(host: &str) -> io::Result<()> {
-    // SSH configからホスト情報を取得
-    let config = find_config_by_host(host)?;
-    
-    let config = match config {
-        Some(c) => c,
-        None => {
-            eprintln!("Error: Host '{}' not found in SSH config", host);
-            std::process::exit(1);
+// 認証コードのプロンプトとみなす既定のパターン（#totp-prompt で上書きできる）
+const DEFAULT_TOTP_PROMPT: &str =
+    r"(?i)(verification code|one-time password|one-time code|authenticator code|otp code)[^:\n]*:\s*$";
+
+// sudo のパスワードプロンプト（既定の `sudo -p` の書式）
+const SUDO_PROMPT: &str = r"\[sudo\] password for [^:\n]+:\s*$";
+
+// 自動入力した直後にこの時間内で再びプロンプトが出たら、パスワードが誤っているとみなす
+const SUDO_RETRY_WINDOW: Duration = Duration::from_secs(10);
+
+/// 接続経路上の 1 ホップ分の認証情報
+struct HopSecret {
+    // ssh のプロンプトに表示される `user@hostname`
+    login: String,
+    identity_files: Vec<String>,
+    password: Option<String>,
+    // 鍵のパスフレーズ（#passphrase がなければ従来どおり #pass を使う）
+    passphrase: Option<String>,
+    totp_secret: Option<String>,
+    totp_prompt: Option<String>,
+    prompt_rules: Vec<PromptRuleSpec>,
+    auto_sudo: bool,
+}
+
+impl HopSecret {
+    fn resolve(config: &SshConfig, effective: &mut EffectiveConfigs) -> Self {
+        // ssh -G でワイルドカードブロック由来の User や HostName も反映する
+        let values = effective.get(&config.host);
+        let user = effective_value(values, "user")
+            .map(|u| u.to_string())
+            .or_else(|| config.user.clone())
+            .or_else(|| env::var("USER").ok())
+            .unwrap_or_default();
+        let hostname = effective_value(values, "hostname")
+            .map(|h| h.to_string())
+            .or_else(|| config.hostname.clone())
+            .unwrap_or_else(|| config.host.clone());
+
+        // ssh -G は既定の鍵パスも列挙するので、明示的に設定された鍵だけを対象にする
+        let identity_files: Vec<String> = config
+            .identity_files
+            .iter()
+            .filter_map(|path| expand_tilde(path).ok())
+            .map(|path| path.display().to_string())
+            .collect();
+
+        Self {
+            login: format!("{}@{}", user, hostname),
+            identity_files,
+            password: config.password.clone(),
+            passphrase: config.passphrase.clone().or_else(|| config.password.clone()),
+            totp_secret: config.totp_secret.clone(),
+            totp_prompt: config.totp_prompt.clone(),
+            prompt_rules: config.prompt_rules.clone(),
+            auto_sudo: config.auto_sudo,
+        }
+    }
+}
+
+/// プロンプトへの応答状況（どのホップを認証中か、同じプロンプトが何度出たか）
+#[derive(Default)]
+struct PromptState {
+    current_hop: Option<usize>,
+    asked: HashMap<String, usize>,
+}
+
+/// sudo パスワードの自動入力が誤ったパスワードで繰り返されないようにする
+#[derive(Default)]
+struct SudoLimiter {
+    last_answer: Option<Instant>,
+    disabled: bool,
+}
+
+impl SudoLimiter {
+    fn answer(&mut self, password: &str) -> Answer {
+        if self.disabled {
+            return Answer::Skip;
+        }
+        if let Some(last) = self.last_answer {
+            if last.elapsed() < SUDO_RETRY_WINDOW {
+                // 直前の自動入力が拒否されたので、以降は利用者に任せる
+                self.disabled = true;
+                eprint!("\r\n(sshr: stored sudo password was rejected; auto-entry disabled)\r\n");
+                return Answer::Skip;
+            }
+        }
+        self.last_answer = Some(Instant::now());
+        Answer::Secret(password.to_string())
+    }
+}
+
+impl PromptState {
+    /// 保存済みの秘密情報を返す。同じプロンプトが再度出た場合は誤りとみなして手入力にする
+    fn answer_once(&mut self, key: &str, secret: Option<&String>) -> Answer {
+        let count = self.asked.entry(key.to_string()).or_insert(0);
+        *count += 1;
+        match secret {
+            Some(secret) if *count == 1 => Answer::Secret(secret.clone()),
+            _ => Answer::AskUser,
+        }
+    }
+}
+
+/// `#passphrase` を保存している鍵を ssh-agent に登録する。
+/// 登録済みか登録できた場合は true、できなかった場合は警告を出して false を返す
+fn unlock_with_agent(config: &SshConfig) -> bool {
+    let (Some(identity_file), Some(passphrase)) = (config.identity_file(), &config.passphrase) else {
+        return false;
+    };
+    let result = expand_tilde(identity_file)
+        .and_then(|path| load_key(&path, passphrase, &config.agent_options));
+    match result {
+        Ok(LoadOutcome::Added) => {
+            eprintln!("Identity added to ssh-agent: {}", identity_file);
+            true
         }
+        Ok(LoadOutcome::AlreadyLoaded) => true,
+        Err(e) => {
+            eprintln!(
+                "Warning: could not add {} to ssh-agent ({}); answering the passphrase prompt instead",
+                identity_file, e
+            );
+            false
+        }
+    }
+}
+
+/// 接続先の環境ラベルをバナーで表示する
+fn print_environment_banner(config: &SshConfig, label: &EnvironmentLabel) {
+    let target = match (&config.user, &config.hostname) {
+        (Some(user), Some(hostname)) => format!("{}@{}", user, hostname),
+        (None, Some(hostname)) => hostname.clone(),
+        _ => config.host.clone(),
     };
+    let text = format!("  {}  {} ({})  ", label.name.to_uppercase(), config.host, target);
     
-    // パスワードがある場合は自動入力、ない場合は通常のSSH接続
-    if let Some(password) = &config.password {
-        run_ssh_with_password(host, password)
+    if !io::stderr().is_terminal() {
+        eprintln!("{}", text.trim());
+        return;
+    }
+    // 本番は赤、検証系は黄、それ以外は青
+    let color = if label.is_production() {
+        "1;97;41"
+    } else if label.name.to_lowercase().starts_with("stag") {
+        "1;30;43"
     } else {
-        // パスワードがない場合は通常のsshコマンドを実行
-        let status = Command::new("ssh")
-            .arg(host)
-            .status()?;
-        
-        if !status.success() {
-            std::process::exit(status.code().unwrap_or(1));
+        "1;97;44"
+    };
+    eprintln!("\x1b[{}m{}\x1b[0m", color, text);
+}
+
+/// 本番環境への接続前に、エイリアスを入力して確認させる
+fn confirm_production(host: &str) -> io::Result<bool> {
+    eprint!("This is a production host. Type '{}' to connect: ", host);
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(answer.trim() == host)
+}
+
+/// セッション中だけ端末のタイトルと背景色を変更し、終了時に元へ戻す
+struct TerminalDecoration {
+    background: bool,
+}
+
+impl TerminalDecoration {
+    fn apply(host: &str, label: &EnvironmentLabel) -> Option<Self> {
+        let mut stdout = io::stdout();
+        if !stdout.is_terminal() {
+            return None;
         }
-        Ok(())
+        // 現在のタイトルを退避してから設定する（xterm の XTWINOPS）
+        let _ = write!(stdout, "\x1b[22;0t\x1b]0;[{}] {}\x07", label.name.to_uppercase(), host);
+        if let Some(background) = &label.background {
+            let _ = write!(stdout, "\x1b]11;{}\x07", background);
+        }
+        let _ = stdout.flush();
+        Some(Self {
+            background: label.background.is_some(),
+        })
     }
 }
 
-fn run_ssh_with_password(host: &str, password: &str) -> io::Result<()> {
-    // expectが利用可能かチェック
-    let expect_check = Command::new("which")
-        .arg("expect")
-        .output();
-    
-    match expect_check {
-        Ok(output) if output.status.success() => {
-            // expectスクリプトを使用
-            run_with_expect_script(host, password)
+impl Drop for TerminalDecoration {
+    fn drop(&mut self) {
+        let mut stdout = io::stdout();
+        if self.background {
+            let _ = write!(stdout, "\x1b]111\x07");
         }
-        _ => {
-            // expectが利用できない場合はエラー
-            eprintln!("Error: 'expect' command is not installed.");
-            eprintln!("Please install expect to use password authentication:");
-            eprintln!("  macOS: brew install expect");
-            eprintln!("  Linux: sudo apt-get install expect (Debian/Ubuntu)");
-            eprintln!("         sudo yum install expect (RHEL/CentOS)");
+        let _ = write!(stdout, "\x1b[23;0t");
+        let _ = stdout.flush();
+    }
+}
+
+pub fn run_sshr(host: &str, record: bool) -> io::Result<()> {
+    // 環境ラベルのあるホストはバナーを表示し、本番なら確認を求める
+    let label = find_config_by_host(host)?.and_then(|config| {
+        let label = config.environment.clone()?;
+        print_environment_banner(&config, &label);
+        Some(label)
+    });
+    if let Some(label) = &label {
+        if label.is_production() && !confirm_production(host)? {
+            eprintln!("Aborted.");
             std::process::exit(1);
         }
     }
+    let decoration = label.as_ref().and_then(|label| TerminalDecoration::apply(host, label));
+    
+    let started = unix_now();
+    let clock = Instant::now();
+    let mut effective = EffectiveConfigs::default();
+    let code = run_ssh_with(host, &[], None, record, &mut effective);
+    drop(decoration);
+    let code = code?;
+    
+    // 接続履歴に残す（保存に失敗しても接続の結果は変えない）
+    let hostname = effective.value(host, "hostname").unwrap_or(host).to_string();
+    let entry = history::Entry {
+        alias: host.to_string(),
+        hostname,
+        started,
+        duration: clock.elapsed().as_secs_f64(),
+        exit_status: code,
+    };
+    if let Err(e) = history::append(&entry) {
+        eprintln!("Warning: could not save connection history: {}", e);
+    }
+    
+    if code != 0 {
+        std::process::exit(code);
+    }
+    
+    Ok(())
 }
 
-fn run_with_expect_script(host: &str, password: &str) -> io::Result<()> {
-    // expectスクリプトの内容
-    let script_content = format!(
-        r#"#!/usr/bin/env expect -f
-
-set timeout 30
-
-# SSH接続を開始
-spawn ssh {}
-
-# パスワードまたはパスフレーズのプロンプトを待つ
-expect {{
-    -re "(?i)(password|passphrase).*:" {{
-        send "{}\r"
-        exp_continue
-    }}
-    -re "(?i)yes/no" {{
-        send "yes\r"
-        exp_continue
-    }}
-    eof {{
-        exit 0
-    }}
-    timeout {{
-        puts "Connection timeout"
-        exit 1
-    }}
-}}
-
-# インタラクティブモードに移行
-interact
-"#,
-        host, password
-    );
+/// 接続先を一覧から選ばせる。最近接続したホストほど上に表示する
+pub fn pick_host() -> io::Result<Option<String>> {
+    let mut configs: Vec<SshConfig> = parse_ssh_config()?
+        .into_iter()
+        .filter(|c| !c.host.contains('*') && !c.host.contains('?'))
+        .collect();
+    if configs.is_empty() {
+        return Ok(None);
+    }
     
-    // 一時ファイルにスクリプトを書き込む
-    let temp_dir = env::temp_dir();
-    let script_path = temp_dir.join(format!("ssh_expect_{}.exp", std::process::id()));
+    let last_used = history::last_used(&history::load()?);
+    configs.sort_by(|a, b| {
+        last_used
+            .get(&b.host)
+            .cmp(&last_used.get(&a.host))
+            .then(a.host.cmp(&b.host))
+    });
     
-    fs::write(&script_path, script_content)?;
+    let width = configs.iter().map(|c| c.host.len()).max().unwrap_or(0);
+    let items: Vec<String> = configs
+        .iter()
+        .map(|c| {
+            let mut item = format!("{:<width$}  ", c.host, width = width);
+            if let Some(user) = &c.user {
+                item.push_str(&format!("{}@", user));
+            }
+            item.push_str(c.hostname.as_deref().unwrap_or(&c.host));
+            if !c.tags.is_empty() {
+                item.push_str(&format!("  [{}]", c.tags.join(",")));
+            }
+            item
+        })
+        .collect();
     
-    // スクリプトに実行権限を付与
-    let mut perms = fs::metadata(&script_path)?.permissions();
-    perms.set_mode(0o700);
-    fs::set_permissions(&script_path, perms)?;
+    let selection = FuzzySelect::new()
+        .with_prompt("connect to")
+        .items(&items)
+        .default(0)
+        .interact_opt()
+        .map_err(io::Error::other)?;
+    Ok(selection.map(|i| configs[i].host.clone()))
+}
+
+/// `options` で制御ソケットを差し替えていなければ、config の ControlPath のマスター接続を使う
+fn uses_configured_master(options: &[&str]) -> bool {
+    !options.iter().any(|o| *o == "-S" || o.starts_with("ControlPath="))
+}
+
+/// 保存済みの認証情報を自動入力しながら ssh を実行し、終了コードを返す。
+/// `options` はホスト名の前に、`remote_command` はホスト名の後に渡す。
+/// `record` か接続先の `#record` が指定されていればセッションを記録する
+pub fn run_ssh(host: &str, options: &[&str], remote_command: Option<&str>, record: bool) -> io::Result<i32> {
+    run_ssh_with(host, options, remote_command, record, &mut EffectiveConfigs::default())
+}
+
+/// run_ssh と同じ。各ホップの ssh -G の結果は `effective` のものを使い、なければ取得して追加する
+fn run_ssh_with(
+    host: &str,
+    options: &[&str],
+    remote_command: Option<&str>,
+    record: bool,
+    effective: &mut EffectiveConfigs,
+) -> io::Result<i32> {
+    // SSH configからホスト情報を取得
+    let Some(config) = find_config_by_host(host)? else {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("Host '{}' not found in SSH config", host),
+        ));
+    };
     
-    // expectスクリプトを実行
-    let status = Command::new("expect")
-        .arg(&script_path)
-        .status()?;
+    // 踏み台チェーンを展開し、各ホップの認証情報を集める
+    let configs = parse_ssh_config()?;
+    let chain = resolve_jump_chain(&config, &configs).map_err(|cycle| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("ProxyJump chain loops: {}", cycle.join(" -> ")),
+        )
+    })?;
     
-    // 一時ファイルを削除
-    let _ = fs::remove_file(&script_path);
+    let mut args: Vec<String> = options.iter().map(|o| o.to_string()).collect();
+    args.push(host.to_string());
+    args.extend(remote_command.map(|c| c.to_string()));
     
-    if !status.success() {
-        std::process::exit(status.code().unwrap_or(1));
+    // 多重化のマスター接続があれば認証は済んでいるので、鍵の登録や自動入力は要らない。
+    // ない場合は、この接続が（ControlMaster auto なら）認証を済ませてマスターになる
+    let shared = uses_configured_master(options) && mux::running_master(host, effective.get(host)).is_some();
+
+    // パスフレーズ付きの鍵は、端末にパスフレーズを流さずに済むよう先に ssh-agent へ登録する
+    let locked_keys = chain
+        .iter()
+        .chain(std::iter::once(&config))
+        .filter(|c| !shared && c.passphrase.is_some() && !unlock_with_agent(c))
+        .count();
+
+    // パスワードや認証コードのシードがある場合は自動入力、ない場合は通常のSSH接続。
+    // ログイン後の sudo などへの応答は、マスター接続を使う場合も必要
+    let after_login = config.auto_sudo || config.prompt_rules.iter().any(|r| r.after_login);
+    let needs_answers = locked_keys > 0
+        || after_login
+        || (!shared
+            && chain
+                .iter()
+                .chain(std::iter::once(&config))
+                .any(|c| c.password.is_some() || c.totp_secret.is_some() || !c.prompt_rules.is_empty()));
+    let record = record || config.record;
+    if needs_answers || record {
+        let hops: Vec<HopSecret> = chain
+            .iter()
+            .chain(std::iter::once(&config))
+            .map(|c| HopSecret::resolve(c, effective))
+            .collect();
+        
+        let mut recorder = if record {
+            let (width, height) = terminal_size()
+                .map(|size| (size.ws_col, size.ws_row))
+                .unwrap_or((80, 24));
+            let mut recorder = Recorder::create(host, width, height)?;
+            for hop in &hops {
+                for secret in hop.password.iter().chain(hop.passphrase.iter()) {
+                    recorder.add_secret(secret);
+                }
+            }
+            Some(recorder)
+        } else {
+            None
+        };
+        
+        let rules = build_prompt_rules(hops, &configs)?;
+        let code = run_session("ssh", &args, rules, recorder.as_mut());
+        if let Some(recorder) = recorder {
+            let path = recorder.finish()?;
+            eprintln!("Session recorded to {}", path.display());
+        }
+        code
+    } else {
+        // パスワードがない場合は通常のsshコマンドを実行
+        let status = Command::new("ssh")
+            .args(&args)
+            .status()?;
+        Ok(status.code().unwrap_or(1))
+    }
+}
+
+/// `#rule` / `#rule-after-login` の定義を実行時のルールに変換する
+fn host_prompt_rule(spec: &PromptRuleSpec, hop: &HopSecret, configs: &[SshConfig]) -> io::Result<PromptRule> {
+    let answer = match &spec.response {
+        RuleResponse::Enter => Some(String::new()),
+        RuleResponse::Send(text) => Some(text.clone()),
+        RuleResponse::Secret(None) => hop.password.clone(),
+        RuleResponse::Secret(Some(alias)) => configs
+            .iter()
+            .find(|c| &c.host == alias)
+            .and_then(|c| c.password.clone()),
+        RuleResponse::Totp | RuleResponse::Ask => None,
+    };
+    let totp_secret = match spec.response {
+        RuleResponse::Totp => hop.totp_secret.clone(),
+        _ => None,
+    };
+    let is_secret = matches!(spec.response, RuleResponse::Secret(_));
+
+    let rule = PromptRule::new(&spec.pattern, move |_| {
+        if let Some(secret) = &totp_secret {
+            return totp_now(secret).map(Answer::Secret).unwrap_or(Answer::AskUser);
+        }
+        match &answer {
+            Some(text) if is_secret => Answer::Secret(text.clone()),
+            Some(text) => Answer::Send(text.clone()),
+            None => Answer::AskUser,
+        }
+    })
+    .map_err(|e| io::Error::new(e.kind(), format!("invalid #rule pattern '{}': {}", spec.pattern, e)))?;
+
+    Ok(if spec.after_login { rule.after_login() } else { rule })
+}
+
+/// どのホップのプロンプトかを判別して、パスワード・パスフレーズ・認証コードを答えるルールを作る
+fn build_prompt_rules(hops: Vec<HopSecret>, configs: &[SshConfig]) -> io::Result<Vec<PromptRule>> {
+    let hops = Rc::new(hops);
+    let state = Rc::new(RefCell::new(PromptState::default()));
+    let mut rules = Vec::new();
+
+    // ホストごとのルールを組み込みのルールより優先する。
+    // ログイン後のルールは最終的な接続先のものだけを使う
+    let target = hops.len() - 1;
+    for (index, hop) in hops.iter().enumerate() {
+        for spec in &hop.prompt_rules {
+            if spec.after_login && index != target {
+                continue;
+            }
+            rules.push(host_prompt_rule(spec, hop, configs)?);
+        }
+    }
+
+    // `user@host's password:` と keyboard-interactive の `(user@host) Password:`
+    for pattern in [
+        r"([^\s@()']+)@([^\s@()']+)'s password:\s*$",
+        r"\(([^\s@()']+)@([^\s@()']+)\) [Pp]assword:\s*$",
+    ] {
+        let (hops, state) = (hops.clone(), state.clone());
+        rules.push(PromptRule::new(pattern, move |caps| {
+            let login = format!("{}@{}", &caps[1], &caps[2]);
+            let mut state = state.borrow_mut();
+            let hop = hops.iter().position(|h| h.login == login);
+            if hop.is_some() {
+                state.current_hop = hop;
+            }
+            state.answer_once(&login, hop.and_then(|i| hops[i].password.as_ref()))
+        })?);
+    }
+
+    // 鍵のパスフレーズ
+    {
+        let (hops, state) = (hops.clone(), state.clone());
+        rules.push(PromptRule::new(r"passphrase for key '([^']+)':\s*$", move |caps| {
+            let key = &caps[1];
+            let mut state = state.borrow_mut();
+            let hop = hops.iter().position(|h| h.identity_files.iter().any(|k| k == key));
+            if hop.is_some() {
+                state.current_hop = hop;
+            }
+            state.answer_once(&format!("key:{}", key), hop.and_then(|i| hops[i].passphrase.as_ref()))
+        })?);
+    }
+
+    // 認証コード。直前にパスワードを答えたホップのシードを優先して使う
+    for (index, hop) in hops.iter().enumerate() {
+        if hop.totp_secret.is_none() {
+            continue;
+        }
+        let pattern = hop.totp_prompt.as_deref().unwrap_or(DEFAULT_TOTP_PROMPT);
+        let (hops, state) = (hops.clone(), state.clone());
+        rules.push(PromptRule::new(pattern, move |_| {
+            let mut state = state.borrow_mut();
+            if let Some(current) = state.current_hop {
+                if current != index && hops[current].totp_secret.is_some() {
+                    return Answer::Skip;
+                }
+            }
+            // 時刻に依存するので、プロンプトが出た時点で計算する
+            let code = hops[index].totp_secret.as_deref().and_then(totp_now);
+            state.answer_once(&format!("totp:{}", index), code.as_ref())
+        })?);
+    }
+
+    // 判別できないプロンプト。踏み台がなければ従来どおり接続先のパスワードを送る
+    {
+        let (hops, state) = (hops.clone(), state.clone());
+        rules.push(PromptRule::new(
+            r"(?i)(password|passphrase|verification code)[^:\n]*:\s*$",
+            move |_| {
+                let fallback = match hops.as_slice() {
+                    [only] => only.password.as_ref(),
+                    _ => None,
+                };
+                state.borrow_mut().answer_once("fallback", fallback)
+            },
+        )?);
+    }
+
+    rules.push(PromptRule::new(r"(?i)\(yes/no(/\[fingerprint\])?\)\?\s*$", |_| {
+        Answer::Send("yes".to_string())
+    })?);
+
+    // ログイン後の sudo プロンプト（#sudo を指定した接続先のみ）
+    if let Some(password) = hops[target].password.clone().filter(|_| hops[target].auto_sudo) {
+        let mut limiter = SudoLimiter::default();
+        rules.push(PromptRule::new(SUDO_PROMPT, move |_| limiter.answer(&password))?.after_login());
+    }
+
+    Ok(rules)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::test_agent::{public_key_blob, TestAgent};
+    use crate::agent::AgentClient;
+
+    #[test]
+    fn unlocks_encrypted_key_through_agent() {
+        let agent = TestAgent::start();
+        let path = agent.generate_key("id_ed25519", "correct horse");
+        let mut config = SshConfig::new("web".to_string());
+        config.identity_files.push(path.to_string_lossy().into_owned());
+        config.passphrase = Some("correct horse".to_string());
+
+        assert!(unlock_with_agent(&config));
+        let identities = AgentClient::connect().unwrap().identities().unwrap();
+        assert!(identities.contains(&public_key_blob(&path)));
+        // 登録済みなら何もせずに成功する
+        assert!(unlock_with_agent(&config));
+    }
+
+    #[test]
+    fn falls_back_when_passphrase_is_wrong() {
+        let agent = TestAgent::start();
+        let path = agent.generate_key("id_ed25519", "correct horse");
+        let mut config = SshConfig::new("web".to_string());
+        config.identity_files.push(path.to_string_lossy().into_owned());
+        config.passphrase = Some("battery staple".to_string());
+
+        assert!(!unlock_with_agent(&config));
     }
-    
-    Ok(())
 }
@@ -0,0 +1,416 @@
+use crate::config::{get_ssh_config_path, pattern_matches, SshConfig};
+use crate::utils::{expand_tilde, home_dir, one_or_many, write_atomic};
+use serde::Deserialize;
+use similar::TextDiff;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+/// sshct sync が生成したファイルの先頭行（これがないファイルは上書きしない）
+pub const MANAGED_HEADER: &str = "# Managed by sshct sync. Do not edit; changes will be overwritten.";
+const SOURCE_PREFIX: &str = "# Source: ";
+
+/// defaults・groups・hosts のそれぞれで指定できる接続設定
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HostSettings {
+    pub hostname: Option<String>,
+    pub user: Option<String>,
+    pub port: Option<u16>,
+    #[serde(default, alias = "identity_file", deserialize_with = "one_or_many")]
+    pub identity_files: Vec<String>,
+    pub proxy_jump: Option<String>,
+    pub proxy_command: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+impl HostSettings {
+    /// `other` で指定された項目で上書きする（鍵は一覧ごと置き換え、タグは追加する）
+    fn apply(&mut self, other: &HostSettings) {
+        let pick = |current: &mut Option<String>, new: &Option<String>| {
+            if new.is_some() {
+                current.clone_from(new);
+            }
+        };
+        pick(&mut self.hostname, &other.hostname);
+        pick(&mut self.user, &other.user);
+        pick(&mut self.proxy_jump, &other.proxy_jump);
+        pick(&mut self.proxy_command, &other.proxy_command);
+        if other.port.is_some() {
+            self.port = other.port;
+        }
+        if !other.identity_files.is_empty() {
+            self.identity_files.clone_from(&other.identity_files);
+        }
+        for tag in &other.tags {
+            if !self.tags.contains(tag) {
+                self.tags.push(tag.clone());
+            }
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TeamHost {
+    pub alias: String,
+    #[serde(default)]
+    pub groups: Vec<String>,
+    pub hostname: Option<String>,
+    pub user: Option<String>,
+    pub port: Option<u16>,
+    #[serde(default, alias = "identity_file", deserialize_with = "one_or_many")]
+    pub identity_files: Vec<String>,
+    pub proxy_jump: Option<String>,
+    pub proxy_command: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+impl TeamHost {
+    fn settings(&self) -> HostSettings {
+        HostSettings {
+            hostname: self.hostname.clone(),
+            user: self.user.clone(),
+            port: self.port,
+            identity_files: self.identity_files.clone(),
+            proxy_jump: self.proxy_jump.clone(),
+            proxy_command: self.proxy_command.clone(),
+            tags: self.tags.clone(),
+        }
+    }
+}
+
+/// チームで管理する宣言的なインベントリ（TOML または YAML）
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TeamInventory {
+    pub name: Option<String>,
+    #[serde(default)]
+    pub defaults: HostSettings,
+    #[serde(default)]
+    pub groups: BTreeMap<String, HostSettings>,
+    #[serde(default)]
+    pub hosts: Vec<TeamHost>,
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+pub fn read_team_inventory(path: &Path) -> io::Result<TeamInventory> {
+    let content = fs::read_to_string(path)?;
+    let is_toml = path.extension().is_some_and(|e| e == "toml");
+    if is_toml {
+        toml::from_str(&content).map_err(|e| invalid_data(e.to_string()))
+    } else {
+        serde_yaml::from_str(&content).map_err(|e| invalid_data(e.to_string()))
+    }
+}
+
+impl TeamInventory {
+    /// defaults → groups（指定順）→ ホスト自身、の順に設定を重ねて SshConfig にする
+    pub fn resolve(&self) -> io::Result<Vec<SshConfig>> {
+        let mut seen = BTreeSet::new();
+        let mut configs = Vec::new();
+        for host in &self.hosts {
+            if host.alias.is_empty() || host.alias.contains(char::is_whitespace) {
+                return Err(invalid_data(format!("invalid alias '{}'", host.alias)));
+            }
+            if !seen.insert(host.alias.as_str()) {
+                return Err(invalid_data(format!("'{}' is defined more than once", host.alias)));
+            }
+
+            let mut settings = self.defaults.clone();
+            for group in &host.groups {
+                let Some(group_settings) = self.groups.get(group) else {
+                    return Err(invalid_data(format!("'{}': unknown group '{}'", host.alias, group)));
+                };
+                settings.apply(group_settings);
+                if !settings.tags.contains(group) {
+                    settings.tags.push(group.clone());
+                }
+            }
+            settings.apply(&host.settings());
+
+            let mut config = SshConfig::new(host.alias.clone());
+            config.hostname = settings.hostname;
+            config.user = settings.user;
+            config.port = settings.port.map(|p| p.to_string());
+            config.identity_files = settings.identity_files;
+            config.proxy_jump = settings.proxy_jump;
+            config.proxy_command = settings.proxy_command;
+            config.tags = settings.tags;
+            configs.push(config);
+        }
+        Ok(configs)
+    }
+}
+
+/// 生成するファイルの内容
+pub fn render(source: &Path, configs: &[SshConfig]) -> String {
+    let mut out = format!("{}\n{}{}\n", MANAGED_HEADER, SOURCE_PREFIX, source.display());
+    for config in configs {
+        out.push('\n');
+        out.push_str(&config.to_config_string());
+    }
+    out
+}
+
+/// 既定の出力先 `~/.ssh/config.d/<name>.conf`
+pub fn default_output(inventory: &TeamInventory, source: &Path) -> io::Result<PathBuf> {
+    let name = match &inventory.name {
+        Some(name) => name.clone(),
+        None => source
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("team")
+            .to_string(),
+    };
+    Ok(home_dir()?.join(".ssh").join("config.d").join(format!("{}.conf", name)))
+}
+
+/// 既存のファイルが sshct sync の管理下かどうか。管理下なら同期元のパスを返す
+pub fn managed_source(content: &str) -> Option<Option<PathBuf>> {
+    let mut lines = content.lines();
+    if lines.next()? != MANAGED_HEADER {
+        return None;
+    }
+    Some(lines.next().and_then(|l| l.strip_prefix(SOURCE_PREFIX)).map(PathBuf::from))
+}
+
+/// `~/.ssh/config.d/*.conf` のうち、sshct sync が生成したファイルとその同期元
+pub fn managed_files() -> io::Result<Vec<(PathBuf, Option<PathBuf>)>> {
+    let dir = home_dir()?.join(".ssh").join("config.d");
+    let mut files = Vec::new();
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(files),
+        Err(e) => return Err(e),
+    };
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().is_some_and(|e| e == "conf") {
+            if let Some(source) = fs::read_to_string(&path).ok().as_deref().and_then(managed_source) {
+                files.push((path, source));
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// 変更内容を unified diff 形式で返す
+pub fn diff(old: &str, new: &str, path: &Path) -> String {
+    TextDiff::from_lines(old, new)
+        .unified_diff()
+        .context_radius(2)
+        .header(&path.display().to_string(), &format!("{} (new)", path.display()))
+        .to_string()
+}
+
+/// ~/.ssh/config の `Include` が出力先を読み込むようになっているか
+fn is_included(config: &str, output: &Path) -> io::Result<bool> {
+    let ssh_dir = home_dir()?.join(".ssh");
+    let output = output.to_string_lossy();
+    for line in config.lines() {
+        let mut words = line.split_whitespace();
+        if !words.next().is_some_and(|w| w.eq_ignore_ascii_case("include")) {
+            continue;
+        }
+        for pattern in words {
+            // 相対パスは ~/.ssh からの相対になる
+            let pattern = if pattern.starts_with('~') || pattern.starts_with('/') {
+                expand_tilde(pattern)?
+            } else {
+                ssh_dir.join(pattern)
+            };
+            if pattern_matches(&pattern.to_string_lossy(), &output) {
+                return Ok(true);
+            }
+        }
+    }
+    Ok(false)
+}
+
+/// ~/.ssh/config に書く `Include` の引数（~/.ssh の下なら相対パスにする）
+fn include_target(output: &Path) -> io::Result<String> {
+    let ssh_dir = home_dir()?.join(".ssh");
+    Ok(match output.strip_prefix(&ssh_dir) {
+        Ok(relative) => relative.display().to_string(),
+        Err(_) => output.display().to_string(),
+    })
+}
+
+/// 出力先を読み込むために ~/.ssh/config の末尾に足す行。
+///
+/// 末尾の Host ブロックの中に書くとそのホストにしか効かないので、`Match all` で区切る。
+/// ssh は最初に見つけた値を使うので、末尾に置けば利用者自身の Host の設定が優先される
+pub fn include_lines(output: &Path) -> io::Result<String> {
+    Ok(format!("Match all\nInclude {}\n", include_target(output)?))
+}
+
+/// ~/.ssh/config が出力先を読み込むようになっているか
+pub fn is_config_including(output: &Path) -> io::Result<bool> {
+    match fs::read_to_string(get_ssh_config_path()?) {
+        Ok(content) => is_included(&content, output),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// 出力先を読み込む行を ~/.ssh/config の末尾に追加する（`sshct sync --add-include`）。
+/// 追加した場合は true を返す
+pub fn add_include(output: &Path) -> io::Result<bool> {
+    add_include_to(&get_ssh_config_path()?, output)
+}
+
+fn add_include_to(config_path: &Path, output: &Path) -> io::Result<bool> {
+    let (content, mode) = match fs::read_to_string(config_path) {
+        Ok(content) => (content, fs::metadata(config_path)?.permissions().mode() & 0o7777),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => (String::new(), 0o600),
+        Err(e) => return Err(e),
+    };
+    if is_included(&content, output)? {
+        return Ok(false);
+    }
+
+    let mut updated = content;
+    if !updated.is_empty() {
+        if !updated.ends_with('\n') {
+            updated.push('\n');
+        }
+        updated.push('\n');
+    }
+    updated.push_str(&include_lines(output)?);
+    if let Some(parent) = config_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    write_atomic(config_path, updated.as_bytes(), mode)?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INVENTORY: &str = r#"
+name = "team"
+
+[defaults]
+user = "deploy"
+port = 22
+identity_file = "~/.ssh/team"
+
+[groups.web]
+port = 2222
+tags = ["frontend"]
+
+[groups.bastioned]
+proxy_jump = "bastion"
+identity_files = ["~/.ssh/team-jump", "~/.ssh/team"]
+
+[[hosts]]
+alias = "web1"
+hostname = "10.0.0.1"
+groups = ["web", "bastioned"]
+
+[[hosts]]
+alias = "db1"
+hostname = "10.0.0.2"
+user = "postgres"
+tags = ["db"]
+"#;
+
+    fn parse(content: &str) -> TeamInventory {
+        toml::from_str(content).unwrap()
+    }
+
+    #[test]
+    fn layers_defaults_groups_and_host_settings() {
+        let configs = parse(INVENTORY).resolve().unwrap();
+        assert_eq!(configs.len(), 2);
+
+        let web = &configs[0];
+        assert_eq!(web.host, "web1");
+        assert_eq!(web.hostname.as_deref(), Some("10.0.0.1"));
+        assert_eq!(web.user.as_deref(), Some("deploy"));
+        assert_eq!(web.port.as_deref(), Some("2222"));
+        assert_eq!(web.proxy_jump.as_deref(), Some("bastion"));
+        assert_eq!(web.identity_files, ["~/.ssh/team-jump", "~/.ssh/team"]);
+        assert_eq!(web.tags, ["frontend", "web", "bastioned"]);
+
+        let db = &configs[1];
+        assert_eq!(db.user.as_deref(), Some("postgres"));
+        assert_eq!(db.port.as_deref(), Some("22"));
+        assert_eq!(db.identity_files, ["~/.ssh/team"]);
+        assert_eq!(db.proxy_jump, None);
+        assert_eq!(db.tags, ["db"]);
+    }
+
+    #[test]
+    fn rejects_duplicate_aliases_and_unknown_groups() {
+        let duplicate = parse("[[hosts]]\nalias = \"a\"\n[[hosts]]\nalias = \"a\"\n");
+        let err = duplicate.resolve().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("more than once"));
+
+        let unknown = parse("[[hosts]]\nalias = \"a\"\ngroups = [\"nope\"]\n");
+        assert!(unknown.resolve().unwrap_err().to_string().contains("unknown group 'nope'"));
+
+        let invalid = parse("[[hosts]]\nalias = \"a b\"\n");
+        assert!(invalid.resolve().is_err());
+    }
+
+    #[test]
+    fn render_is_idempotent() {
+        let source = Path::new("/srv/team/inventory.toml");
+        let first = render(source, &parse(INVENTORY).resolve().unwrap());
+        let second = render(source, &parse(INVENTORY).resolve().unwrap());
+        assert_eq!(first, second);
+        assert_eq!(diff(&first, &second, Path::new("team.conf")), "");
+        assert!(first.starts_with(&format!("{}\n# Source: /srv/team/inventory.toml\n\nHost web1\n", MANAGED_HEADER)));
+        assert!(!diff("", &first, Path::new("team.conf")).is_empty());
+    }
+
+    #[test]
+    fn recognizes_managed_files() {
+        let rendered = render(Path::new("/srv/team.toml"), &[]);
+        assert_eq!(managed_source(&rendered), Some(Some(PathBuf::from("/srv/team.toml"))));
+        assert_eq!(managed_source(MANAGED_HEADER), Some(None));
+        assert_eq!(managed_source("Host web\n"), None);
+        assert_eq!(managed_source(""), None);
+    }
+
+    #[test]
+    fn detects_include_of_output() {
+        let ssh_dir = home_dir().unwrap().join(".ssh");
+        let output = ssh_dir.join("config.d").join("team.conf");
+        assert!(is_included("Include config.d/*.conf\n", &output).unwrap());
+        assert!(is_included("  include ~/.ssh/config.d/team.conf\n", &output).unwrap());
+        assert!(is_included(&format!("Include other {}\n", output.display()), &output).unwrap());
+        assert!(!is_included("Include config.d/other.conf\n", &output).unwrap());
+        assert!(!is_included("# Include config.d/team.conf\nHost web\n", &output).unwrap());
+    }
+
+    #[test]
+    fn appends_include_after_users_blocks() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = dir.path().join("config");
+        let output = dir.path().join("team.conf");
+        fs::write(&config, "Host mine\n  User me").unwrap();
+        fs::set_permissions(&config, fs::Permissions::from_mode(0o600)).unwrap();
+
+        assert!(add_include_to(&config, &output).unwrap());
+        let expected = format!("Host mine\n  User me\n\nMatch all\nInclude {}\n", output.display());
+        assert_eq!(fs::read_to_string(&config).unwrap(), expected);
+        assert_eq!(fs::metadata(&config).unwrap().permissions().mode() & 0o777, 0o600);
+
+        assert!(!add_include_to(&config, &output).unwrap());
+        assert_eq!(fs::read_to_string(&config).unwrap(), expected);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+}
@@ -0,0 +1,211 @@
+use crate::config::{AgentOptions, EnvironmentLabel, SshConfig};
+use crate::utils::{config_dir, one_or_many};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// `sshct new --template` で使う既定値。
+///
+/// ホスト固有の HostName と、パスワードなどの秘密情報は持たない。
+/// `~/.config/ssh_supporter/templates/<名前>.toml` に保存する。
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Template {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<String>,
+    #[serde(
+        default,
+        alias = "identity_file",
+        deserialize_with = "one_or_many",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub identity_files: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy_jump: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy_command: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// `#env` と同じ書式（`prod bg=#3a0000` など）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub environment: Option<String>,
+    /// `#agent` と同じ書式（`lifetime=8h confirm` など）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agent: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub record: bool,
+}
+
+impl Template {
+    /// 既存のホスト設定からテンプレートを作る
+    pub fn from_config(config: &SshConfig) -> Self {
+        Self {
+            user: config.user.clone(),
+            port: config.port.clone(),
+            identity_files: config.identity_files.clone(),
+            proxy_jump: config.proxy_jump.clone(),
+            proxy_command: config.proxy_command.clone(),
+            tags: config.tags.clone(),
+            environment: config.environment.as_ref().map(|e| e.to_string()),
+            agent: Some(config.agent_options.to_string()).filter(|_| !config.agent_options.is_default()),
+            record: config.record,
+        }
+    }
+
+    /// `environment` と `agent` が `#env` / `#agent` として解釈できるか確かめる
+    fn validate(&self) -> Result<(), String> {
+        if let Some(environment) = self.environment.as_deref() {
+            if EnvironmentLabel::parse(environment).is_none() {
+                return Err(format!("invalid environment '{}'", environment));
+            }
+        }
+        if let Some(agent) = self.agent.as_deref() {
+            if AgentOptions::parse(agent).is_none() {
+                return Err(format!("invalid agent '{}' (use lifetime=<duration> and/or confirm)", agent));
+            }
+        }
+        Ok(())
+    }
+
+    /// テンプレートの値を入れた SshConfig を作る（入力の初期値として使う）。
+    /// `environment` と `agent` は読み込み時に検査済み
+    pub fn to_config(&self, host: &str) -> SshConfig {
+        let mut config = SshConfig::new(host.to_string());
+        config.user = self.user.clone();
+        config.port = self.port.clone();
+        config.identity_files = self.identity_files.clone();
+        config.proxy_jump = self.proxy_jump.clone();
+        config.proxy_command = self.proxy_command.clone();
+        config.tags = self.tags.clone();
+        config.environment = self.environment.as_deref().and_then(EnvironmentLabel::parse);
+        config.agent_options = self.agent.as_deref().and_then(AgentOptions::parse).unwrap_or_default();
+        config.record = self.record;
+        config
+    }
+}
+
+/// テンプレート名として使える文字だけで構成されているか
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('.')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c))
+}
+
+fn templates_dir() -> io::Result<PathBuf> {
+    Ok(config_dir()?.join("templates"))
+}
+
+pub fn template_path(name: &str) -> io::Result<PathBuf> {
+    Ok(templates_dir()?.join(format!("{}.toml", name)))
+}
+
+/// テンプレートを読み込む。存在しなければ None を返す
+pub fn load_template(name: &str) -> io::Result<Option<Template>> {
+    let content = match fs::read_to_string(template_path(name)?) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    parse_template(name, &content).map(Some)
+}
+
+/// 解釈できない値は、制約のないホストを黙って作らないようエラーにする
+fn parse_template(name: &str, content: &str) -> io::Result<Template> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, format!("template '{}': {}", name, message));
+    let template: Template = toml::from_str(content).map_err(|e| invalid(e.to_string()))?;
+    template.validate().map_err(invalid)?;
+    Ok(template)
+}
+
+pub fn save_template(name: &str, template: &Template) -> io::Result<PathBuf> {
+    let path = template_path(name)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let content = toml::to_string(template).map_err(io::Error::other)?;
+    fs::write(&path, content)?;
+    Ok(path)
+}
+
+/// 保存済みのテンプレート名（名前順）
+pub fn list_templates() -> io::Result<Vec<String>> {
+    let entries = match fs::read_dir(templates_dir()?) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut names = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().is_some_and(|e| e == "toml") {
+            if let Some(name) = path.file_stem().and_then(|s| s.to_str()) {
+                names.push(name.to_string());
+            }
+        }
+    }
+    names.sort();
+    Ok(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_through_toml() {
+        let mut config = SshConfig::new("web".to_string());
+        config.hostname = Some("10.0.0.1".to_string());
+        config.user = Some("deploy".to_string());
+        config.port = Some("2222".to_string());
+        config.identity_files = vec!["~/.ssh/web".to_string(), "~/.ssh/backup".to_string()];
+        config.proxy_jump = Some("bastion".to_string());
+        config.tags = vec!["prod".to_string(), "web".to_string()];
+        config.environment = EnvironmentLabel::parse("prod bg=#3a0000");
+        config.agent_options = AgentOptions::parse("lifetime=8h confirm").unwrap();
+        config.record = true;
+        config.password = Some("s3cret".to_string());
+
+        let text = toml::to_string(&Template::from_config(&config)).unwrap();
+        assert!(!text.contains("10.0.0.1"));
+        assert!(!text.contains("s3cret"));
+
+        let restored = parse_template("web", &text).unwrap().to_config("web2");
+        assert_eq!(restored.host, "web2");
+        assert_eq!(restored.hostname, None);
+        assert_eq!(restored.password, None);
+        assert_eq!(restored.user, config.user);
+        assert_eq!(restored.port, config.port);
+        assert_eq!(restored.identity_files, config.identity_files);
+        assert_eq!(restored.proxy_jump, config.proxy_jump);
+        assert_eq!(restored.tags, config.tags);
+        assert_eq!(restored.environment, config.environment);
+        assert_eq!(restored.agent_options, config.agent_options);
+        assert!(restored.record);
+    }
+
+    #[test]
+    fn rejects_unparsable_agent_and_environment() {
+        let err = parse_template("t", "agent = \"lifetime=forever\"\n").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("invalid agent"));
+
+        let err = parse_template("t", "environment = \"prod color=red\"\n").unwrap_err();
+        assert!(err.to_string().contains("invalid environment"));
+
+        assert!(parse_template("t", "hostname = \"x\"\n").is_err());
+        assert!(parse_template("t", "identity_file = \"~/.ssh/a\"\n").is_ok());
+    }
+
+    #[test]
+    fn validates_names() {
+        for name in ["web", "db-prod", "team_a.v2", "A1"] {
+            assert!(is_valid_name(name), "{}", name);
+        }
+        for name in ["", ".hidden", "..", "../x", "a/b", "with space", "a@b", "日本"] {
+            assert!(!is_valid_name(name), "{}", name);
+        }
+    }
+}
@@ -0,0 +1,278 @@
+use crate::config::{find_config_by_host, SshConfig, TunnelProfile};
+use crate::mux::parse_master_pid;
+use crate::sshr::run_ssh;
+use crate::template::is_valid_name;
+use crate::utils::{data_dir, format_utc, print_table, unix_now};
+use dialoguer::Select;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::os::unix::fs::DirBuilderExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const USAGE: &str = "Usage: sshr tunnel <host> [<profile>] | sshr tunnel stop <host> [<profile>] | sshr tunnel stop --all | sshr tunnels";
+
+/// 起動したトンネルの記録。
+///
+/// トンネルの ssh は専用の制御ソケット（ControlPath）を持つマスターとして起動し、
+/// 状態の確認と停止は `ssh -O check` / `ssh -O exit` で行う。
+#[derive(Debug, Serialize, Deserialize)]
+struct TunnelState {
+    host: String,
+    profile: String,
+    forwards: String,
+    ports: Vec<String>,
+    started: u64,
+}
+
+/// 記録と制御ソケットを置くディレクトリ（`~/.local/share/ssh_supporter/tunnels`）
+fn tunnels_dir() -> io::Result<PathBuf> {
+    Ok(data_dir()?.join("tunnels"))
+}
+
+/// `<host>@<profile>.json` と `<host>@<profile>.sock`。
+/// `/` や `..` を含む名前で tunnels ディレクトリの外に書かないよう、使える文字を限る
+fn state_paths(host: &str, profile: &str) -> io::Result<(PathBuf, PathBuf)> {
+    for name in [host, profile] {
+        if !is_valid_name(name) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("'{}' cannot be used as a tunnel host or profile name", name),
+            ));
+        }
+    }
+    let dir = tunnels_dir()?;
+    let name = format!("{}@{}", host, profile);
+    Ok((dir.join(format!("{}.json", name)), dir.join(format!("{}.sock", name))))
+}
+
+/// `-S` に渡すパス。ssh は `%` をトークンとして展開するのでエスケープする
+fn control_path_arg(socket: &Path) -> String {
+    socket.to_string_lossy().replace('%', "%%")
+}
+
+/// 制御ソケットに問い合わせ、トンネルの ssh が動いていれば PID を返す
+fn master_pid(host: &str, socket: &Path) -> Option<u32> {
+    if !socket.exists() {
+        return None;
+    }
+    let output = Command::new("ssh")
+        .args(["-S", &control_path_arg(socket), "-O", "check", host])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    parse_master_pid(&String::from_utf8_lossy(&output.stderr))
+}
+
+/// 起動するプロファイルを決める。省略時は 1 つだけならそれを、端末なら一覧から選ばせる
+fn select_profile(config: &SshConfig, name: Option<&str>) -> io::Result<TunnelProfile> {
+    if config.tunnels.is_empty() {
+        eprintln!("Error: '{}' has no tunnel profiles", config.host);
+        eprintln!("Add one with: sshct edit {}", config.host);
+        std::process::exit(1);
+    }
+    let names: Vec<&str> = config.tunnels.iter().map(|t| t.name.as_str()).collect();
+    let index = match name {
+        Some(name) => names.iter().position(|n| *n == name),
+        None if config.tunnels.len() == 1 => Some(0),
+        None if io::stdin().is_terminal() => {
+            let items: Vec<String> = config
+                .tunnels
+                .iter()
+                .map(|t| format!("{}  {}", t.name, t.forwards_string()))
+                .collect();
+            Select::new()
+                .with_prompt("start which tunnel?")
+                .items(&items)
+                .default(0)
+                .interact_opt()
+                .map_err(io::Error::other)?
+        }
+        None => None,
+    };
+    match index {
+        Some(index) => Ok(config.tunnels[index].clone()),
+        None => {
+            if let Some(name) = name {
+                eprintln!("Error: '{}' has no tunnel profile '{}'", config.host, name);
+            } else {
+                eprintln!("Error: specify a tunnel profile");
+            }
+            eprintln!("Available profiles: {}", names.join(", "));
+            std::process::exit(1);
+        }
+    }
+}
+
+/// トンネルをバックグラウンドで起動する。パスワードなどは通常の接続と同じく自動入力する
+pub fn start_tunnel(host: &str, profile: Option<&str>) -> io::Result<()> {
+    let Some(config) = find_config_by_host(host)? else {
+        eprintln!("Error: Host '{}' not found in SSH config", host);
+        std::process::exit(1);
+    };
+    let profile = select_profile(&config, profile)?;
+    let (state_path, socket) = state_paths(host, &profile.name)?;
+    if let Some(pid) = master_pid(host, &socket) {
+        eprintln!("Error: tunnel '{}' to '{}' is already running (pid {})", profile.name, host, pid);
+        std::process::exit(1);
+    }
+
+    // 制御ソケットは接続を横取りできるので、自分だけが読めるディレクトリに置く
+    fs::DirBuilder::new().recursive(true).mode(0o700).create(tunnels_dir()?)?;
+    // 前回の ssh が異常終了して残ったソケット
+    let _ = fs::remove_file(&socket);
+
+    // -f は認証と転送の確立が済んでからバックグラウンドに移る。
+    // バックグラウンドの ssh が PTY を開いたままでも、run_session は最初の ssh の終了を見て戻る
+    // （session のテストと、`sshr tunnel <host>` の後にプロンプトが戻ることで確認する）
+    let control_path = control_path_arg(&socket);
+    let mut options = vec![
+        "-f", "-N", "-M", "-S", &control_path,
+        "-o", "ControlPersist=no",
+        "-o", "ExitOnForwardFailure=yes",
+    ];
+    let forward_args = profile.ssh_args();
+    options.extend(forward_args.iter().map(String::as_str));
+    let code = run_ssh(host, &options, None, false)?;
+    if code != 0 {
+        eprintln!("Error: could not start tunnel '{}' (ssh exited with {})", profile.name, code);
+        std::process::exit(code);
+    }
+    let Some(pid) = master_pid(host, &socket) else {
+        eprintln!("Error: tunnel '{}' exited right after starting", profile.name);
+        std::process::exit(1);
+    };
+
+    let state = TunnelState {
+        host: host.to_string(),
+        profile: profile.name.clone(),
+        forwards: profile.forwards_string(),
+        ports: profile.listen_ports(),
+        started: unix_now(),
+    };
+    fs::write(&state_path, serde_json::to_string(&state).map_err(io::Error::other)?)?;
+    println!("Tunnel '{}' to '{}' started (pid {}): {}", profile.name, host, pid, state.forwards);
+    println!("Stop it with: sshr tunnel stop {} {}", host, profile.name);
+    Ok(())
+}
+
+/// 記録のあるトンネルのうち、動いているものと PID。止まっているものの記録は削除する
+fn running_tunnels() -> io::Result<Vec<(TunnelState, u32)>> {
+    let entries = match fs::read_dir(tunnels_dir()?) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut tunnels = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().is_none_or(|e| e != "json") {
+            continue;
+        }
+        let Some(state) = fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str::<TunnelState>(&content).ok())
+        else {
+            continue;
+        };
+        let socket = path.with_extension("sock");
+        match master_pid(&state.host, &socket) {
+            Some(pid) => tunnels.push((state, pid)),
+            None => {
+                let _ = fs::remove_file(&path);
+                let _ = fs::remove_file(&socket);
+            }
+        }
+    }
+    tunnels.sort_by(|(a, _), (b, _)| (&a.host, &a.profile).cmp(&(&b.host, &b.profile)));
+    Ok(tunnels)
+}
+
+pub fn list_tunnels() -> io::Result<()> {
+    let tunnels = running_tunnels()?;
+    if tunnels.is_empty() {
+        println!("No tunnels running.");
+        return Ok(());
+    }
+    let rows: Vec<Vec<String>> = tunnels
+        .iter()
+        .map(|(state, pid)| {
+            vec![
+                state.host.clone(),
+                state.profile.clone(),
+                pid.to_string(),
+                state.ports.join(","),
+                state.forwards.clone(),
+                format_utc(state.started),
+            ]
+        })
+        .collect();
+    print_table(&["HOST", "PROFILE", "PID", "PORTS", "FORWARDS", "STARTED (UTC)"], &rows);
+    Ok(())
+}
+
+/// 条件に合うトンネルを止める（host も profile も None なら全部）
+pub fn stop_tunnels(host: Option<&str>, profile: Option<&str>) -> io::Result<()> {
+    let targets: Vec<(TunnelState, u32)> = running_tunnels()?
+        .into_iter()
+        .filter(|(state, _)| host.is_none_or(|h| state.host == h) && profile.is_none_or(|p| state.profile == p))
+        .collect();
+    if targets.is_empty() {
+        println!("No matching tunnels running.");
+        return Ok(());
+    }
+
+    for (state, pid) in targets {
+        let (state_path, socket) = state_paths(&state.host, &state.profile)?;
+        let status = Command::new("ssh")
+            .args(["-S", &control_path_arg(&socket), "-O", "exit", &state.host])
+            .stderr(std::process::Stdio::null())
+            .status()?;
+        if !status.success() {
+            eprintln!("Warning: could not stop tunnel '{}' to '{}' (pid {})", state.profile, state.host, pid);
+            continue;
+        }
+        let _ = fs::remove_file(&state_path);
+        println!("Stopped tunnel '{}' to '{}' (pid {})", state.profile, state.host, pid);
+    }
+    Ok(())
+}
+
+/// `sshr tunnel ...` と `sshr tunnels` を処理する（`args[0]` はサブコマンド名）
+pub fn run_tunnel_command(args: &[String]) -> io::Result<()> {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    match args.as_slice() {
+        ["tunnels"] => list_tunnels(),
+        ["tunnel", "stop", "--all"] => stop_tunnels(None, None),
+        ["tunnel", "stop", host] => stop_tunnels(Some(host), None),
+        ["tunnel", "stop", host, profile] => stop_tunnels(Some(host), Some(profile)),
+        ["tunnel", host] if !host.starts_with('-') && *host != "stop" => start_tunnel(host, None),
+        ["tunnel", host, profile] if !host.starts_with('-') && *host != "stop" => start_tunnel(host, Some(profile)),
+        _ => {
+            eprintln!("{}", USAGE);
+            std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_percent_in_control_path() {
+        assert_eq!(control_path_arg(Path::new("/home/a%b/tunnels/web@db.sock")), "/home/a%%b/tunnels/web@db.sock");
+        assert_eq!(control_path_arg(Path::new("/tmp/web@db.sock")), "/tmp/web@db.sock");
+    }
+
+    #[test]
+    fn rejects_names_outside_tunnels_dir() {
+        for (host, profile) in [("../../x", "db"), ("web", "../x"), ("a/b", "db"), ("web", ".."), ("", "db")] {
+            let err = state_paths(host, profile).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput, "{} {}", host, profile);
+        }
+    }
+}